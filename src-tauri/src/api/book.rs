@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+
+use crate::entities::{game, game_header, player, position, r#move};
+use crate::models::{chess_variant, is_chess960, ChessPosition};
+use log::{info, warn};
+use ok_analysis::book::{
+    BookBuildOptions, BookBuilder, BookColor, GameOutcome, PolyglotBook,
+};
+use ok_utils::ts_export;
+use sea_orm::sea_query::{Alias, Expr, Query, SelectStatement};
+use sea_orm::{
+    ColumnTrait, Condition, DatabaseConnection, EntityTrait, LoaderTrait,
+    QueryFilter,
+};
+use shakmaty::uci::UciMove;
+use shakmaty::variant::Variant;
+
+/// Which games of the database a book is built from
+#[ts_export]
+#[derive(Default)]
+pub struct BookGameFilter {
+    /// Only games played by one of these players, by name. When the book
+    /// is for one side, the player has to be playing that side.
+    pub players: Option<Vec<String>>,
+    /// Only games played on or after this date, as a PGN date like
+    /// "2024.01.31"
+    pub date_from: Option<String>,
+    /// Only games played on or before this date
+    pub date_to: Option<String>,
+    /// Only games where both players were rated at least this much, by
+    /// the game's `WhiteElo` and `BlackElo` tags
+    pub min_rating: Option<i32>,
+    /// Only the first moves of each game, the whole main line if not given
+    pub max_plies: Option<u32>,
+}
+
+/// What went into a built book
+#[ts_export]
+pub struct BookBuildReport {
+    /// Number of games the book was built from
+    pub games: usize,
+    /// Number of entries in the book
+    pub entries: usize,
+}
+
+/// Build a Polyglot book from the main lines of the games in the database
+///
+/// Games of variants other than standard chess, Chess960 included, are
+/// left out, as are moves after the first illegal or unreadable one of a
+/// game.
+pub async fn build_book(
+    db: &DatabaseConnection,
+    filter: &BookGameFilter,
+    options: BookBuildOptions,
+) -> anyhow::Result<(PolyglotBook, BookBuildReport)> {
+    let color = options.color;
+    let mut builder = BookBuilder::new(options);
+    let games = find_book_games(db, filter, color).await?;
+
+    let mut games_used = 0;
+    for game in &games {
+        let variant = game.variant.clone();
+        // Polyglot keys don't tell Chess960 castling apart
+        if let Some(name) = &variant {
+            if chess_variant(name) != Some(Variant::Chess) || is_chess960(name)
+            {
+                continue;
+            }
+        }
+
+        let moves = main_line(db, game.game_id).await?;
+        let outcome =
+            GameOutcome::from_result(game.result.as_deref().unwrap_or("*"));
+        let mut position = match &game.fen {
+            Some(fen) => {
+                ChessPosition::from_fen(Some(fen.clone()), variant.clone())
+                    .map_err(|e| anyhow::anyhow!(e.to_string()))?
+            }
+            None => ChessPosition {
+                variant: variant.clone(),
+                ..ChessPosition::default()
+            },
+        };
+
+        let max_plies = filter.max_plies.map_or(usize::MAX, |n| n as usize);
+        for (played, after) in moves.into_iter().take(max_plies) {
+            let Ok(before) = position.to_position() else {
+                warn!("Invalid position in game {}", game.game_id);
+                break;
+            };
+            let Some(m) = UciMove::from_ascii(played.uci.as_bytes())
+                .ok()
+                .and_then(|uci| uci.to_move(&before).ok())
+            else {
+                warn!("Illegal move '{}' in game {}", played.uci, game.game_id);
+                break;
+            };
+            builder.add_move(&before, &m, outcome);
+
+            let Some(after) = after else {
+                break;
+            };
+            position =
+                ChessPosition::from_fen(Some(after.fen), variant.clone())
+                    .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+        }
+        games_used += 1;
+    }
+
+    let book = builder.build();
+    let report = BookBuildReport {
+        games: games_used,
+        entries: book.entries().len(),
+    };
+    info!(
+        "Built opening book with {} entries from {} games",
+        report.entries, report.games
+    );
+    Ok((book, report))
+}
+
+/// Find the games matching a filter
+async fn find_book_games(
+    db: &DatabaseConnection,
+    filter: &BookGameFilter,
+    color: Option<BookColor>,
+) -> anyhow::Result<Vec<game::Model>> {
+    let mut condition = Condition::all();
+
+    if let Some(from) = &filter.date_from {
+        condition = condition.add(game::Column::DatePlayed.gte(from.as_str()));
+    }
+    if let Some(to) = &filter.date_to {
+        condition = condition.add(game::Column::DatePlayed.lte(to.as_str()));
+    }
+
+    if let Some(names) = &filter.players {
+        let named_players = Query::select()
+            .column(player::Column::PlayerId)
+            .from(player::Entity)
+            .and_where(player::Column::Name.is_in(names.clone()))
+            .to_owned();
+        let white =
+            game::Column::WhitePlayerId.in_subquery(named_players.clone());
+        let black = game::Column::BlackPlayerId.in_subquery(named_players);
+        condition = condition.add(match color {
+            Some(BookColor::White) => Condition::all().add(white),
+            Some(BookColor::Black) => Condition::all().add(black),
+            None => Condition::any().add(white).add(black),
+        });
+    }
+
+    if let Some(min_rating) = filter.min_rating {
+        condition = condition
+            .add(
+                game::Column::GameId
+                    .in_subquery(games_rated_at_least("WhiteElo", min_rating)),
+            )
+            .add(
+                game::Column::GameId
+                    .in_subquery(games_rated_at_least("BlackElo", min_rating)),
+            );
+    }
+
+    Ok(game::Entity::find().filter(condition).all(db).await?)
+}
+
+/// The IDs of the games where a rating tag, like `WhiteElo`, is at least
+/// `min_rating`
+fn games_rated_at_least(
+    tag: &str,
+    min_rating: i32,
+) -> SelectStatement {
+    Query::select()
+        .column(game_header::Column::GameId)
+        .from(game_header::Entity)
+        .and_where(game_header::Column::HeaderName.eq(tag))
+        .and_where(
+            Expr::expr(
+                Expr::col(game_header::Column::HeaderValue)
+                    .cast_as(Alias::new("INTEGER")),
+            )
+            .gte(min_rating),
+        )
+        .to_owned()
+}
+
+/// The main line of a game's move tree in order, each move with the
+/// position it leads to
+///
+/// The main line continues with the first saved child of each move.
+async fn main_line(
+    db: &DatabaseConnection,
+    game_id: i32,
+) -> anyhow::Result<Vec<(r#move::Model, Option<position::Model>)>> {
+    let moves = r#move::Entity::find()
+        .filter(r#move::Column::GameId.eq(game_id))
+        .all(db)
+        .await?;
+    let positions = moves.load_one(position::Entity, db).await?;
+
+    let mut children: HashMap<Option<i32>, (r#move::Model, _)> = HashMap::new();
+    for (chess_move, position) in moves.into_iter().zip(positions) {
+        let parent = chess_move.parent_move_id;
+        let is_first = children
+            .get(&parent)
+            .is_none_or(|(sibling, _)| chess_move.move_id < sibling.move_id);
+        if is_first {
+            children.insert(parent, (chess_move, position));
+        }
+    }
+
+    let mut line = Vec::new();
+    let mut parent = None;
+    while let Some((chess_move, position)) = children.remove(&parent) {
+        parent = Some(chess_move.move_id);
+        line.push((chess_move, position));
+    }
+    Ok(line)
+}
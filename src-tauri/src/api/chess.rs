@@ -1,6 +1,10 @@
+use ok_analysis::book::{BookMove, PolyglotBook};
 use ok_utils::ts_export;
 use shakmaty::san::San;
-use shakmaty::{fen::Fen, CastlingMode, Chess, Position};
+use shakmaty::variant::Variant;
+use shakmaty::Position;
+
+use crate::models::ChessPosition;
 
 #[ts_export]
 pub struct LegalMove {
@@ -8,12 +12,22 @@ pub struct LegalMove {
     pub uci: String,
 }
 
+/// Get the legal moves in a position
+///
+/// Moves follow the rules of the variant, like drops in Crazyhouse. In
+/// Chess960 castling is written in UCI as the king capturing its own
+/// rook, the way engines playing Chess960 expect.
 pub fn get_legal_moves(
-    fen: &str
+    fen: &str,
+    variant: Option<&str>,
 ) -> Result<Vec<LegalMove>, Box<dyn std::error::Error>> {
-    // Parse the FEN string into a Fen struct, then convert to Chess position
-    let fen: Fen = fen.parse()?;
-    let position: Chess = fen.into_position(CastlingMode::Standard)?;
+    // Parse the FEN string into a position in the variant
+    let position = ChessPosition::from_fen(
+        Some(fen.to_string()),
+        variant.map(str::to_string),
+    )?
+    .to_position()?;
+    let mode = position.castles().mode();
 
     // Generate all legal moves in this position
     let legal_moves = position.legal_moves();
@@ -23,9 +37,30 @@ pub fn get_legal_moves(
         .into_iter()
         .map(|m| LegalMove {
             san: San::from_move(&position, &m).to_string(),
-            uci: m.to_uci(shakmaty::CastlingMode::Standard).to_string(),
+            uci: m.to_uci(mode).to_string(),
         })
         .collect();
 
     Ok(moves)
 }
+
+/// Get the moves an opening book has for a position
+///
+/// Books only cover standard chess, so positions of other variants have
+/// no book moves. Chess960 positions are looked up like any other.
+pub fn get_book_moves(
+    fen: &str,
+    variant: Option<&str>,
+    book_path: &str,
+) -> Result<Vec<BookMove>, Box<dyn std::error::Error>> {
+    let position = ChessPosition::from_fen(
+        Some(fen.to_string()),
+        variant.map(str::to_string),
+    )?;
+    if position.chess_variant()? != Variant::Chess {
+        return Ok(Vec::new());
+    }
+
+    let book = PolyglotBook::open(book_path)?;
+    Ok(book.moves(&position.to_position()?))
+}
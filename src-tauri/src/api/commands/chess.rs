@@ -1,14 +1,23 @@
+use crate::api::book::{BookBuildReport, BookGameFilter};
 use crate::utils::AppError;
+use crate::AppState;
+use ok_analysis::book::BookBuildOptions;
+use tauri::State;
 
 /// Gets all legal moves for a given chess position
 ///
 /// Parameters:
 /// - `fen`: The FEN string representing the position
+/// - `variant`: The variant the position is played in, "Chess960" uses
+///   Chess960 castling rules. Defaults to standard chess.
 ///
 /// Returns a JSON string containing all legal moves.
 #[tauri::command]
-pub async fn get_legal_moves(fen: String) -> Result<String, AppError> {
-    match crate::api::chess::get_legal_moves(&fen) {
+pub async fn get_legal_moves(
+    fen: String,
+    variant: Option<String>,
+) -> Result<String, AppError> {
+    match crate::api::chess::get_legal_moves(&fen, variant.as_deref()) {
         Ok(moves) => match serde_json::to_string(&moves) {
             Ok(json) => Ok(json),
             Err(e) => Err(AppError::SerializationError(e.to_string())),
@@ -16,3 +25,63 @@ pub async fn get_legal_moves(fen: String) -> Result<String, AppError> {
         Err(e) => Err(AppError::ChessError(e.to_string())),
     }
 }
+
+/// Gets the moves an opening book has for a position
+///
+/// Parameters:
+/// - `fen`: The FEN string representing the position
+/// - `variant`: The variant the position is played in, positions of
+///   variants other than standard chess and Chess960 have no book moves
+/// - `book_path`: The file path to a Polyglot `.bin` book
+///
+/// Returns a JSON string containing the book moves, the most played first.
+#[tauri::command]
+pub async fn get_book_moves(
+    fen: String,
+    variant: Option<String>,
+    book_path: String,
+) -> Result<String, AppError> {
+    match crate::api::chess::get_book_moves(
+        &fen,
+        variant.as_deref(),
+        &book_path,
+    ) {
+        Ok(moves) => match serde_json::to_string(&moves) {
+            Ok(json) => Ok(json),
+            Err(e) => Err(AppError::SerializationError(e.to_string())),
+        },
+        Err(e) => Err(AppError::ChessError(e.to_string())),
+    }
+}
+
+/// Builds a Polyglot opening book from the games in the database
+///
+/// Parameters:
+/// - `book_path`: The file path the `.bin` book is written to
+/// - `filter`: Which games the book is built from, all of them if not
+///   given
+/// - `options`: How moves are weighted and which are kept, see
+///   `BookBuildOptions`
+///
+/// Returns how many games and entries went into the book.
+#[tauri::command]
+pub async fn build_opening_book(
+    book_path: String,
+    filter: Option<BookGameFilter>,
+    options: Option<BookBuildOptions>,
+    state: State<'_, AppState>,
+) -> Result<BookBuildReport, AppError> {
+    let (book, report) = crate::api::book::build_book(
+        &state.db,
+        &filter.unwrap_or_default(),
+        options.unwrap_or_default(),
+    )
+    .await
+    .map_err(|e| {
+        AppError::DatabaseError(format!("Failed to build book: {}", e))
+    })?;
+
+    book.save(&book_path)
+        .map_err(|e| AppError::IoError(e.to_string()))?;
+    Ok(report)
+}
@@ -1,7 +1,11 @@
-use crate::db::reset_database;
+use crate::db::{
+    load_chesscom_file, load_lichess_file, load_pgn_file, load_scid_database,
+    reset_database,
+};
 use crate::models;
 use crate::utils::AppError;
 use crate::AppState;
+use ok_parse::pgn::ValidationPolicy;
 use tauri::State;
 
 /// Resets the database to its initial empty state
@@ -14,17 +18,114 @@ pub async fn empty_db(state: State<'_, AppState>) -> Result<(), AppError> {
     Ok(())
 }
 
-/// Imports chess games from PGN format
+/// Imports the chess games of a PGN file
 ///
-/// Parses the provided PGN string and saves the games to the database.
-/// Returns the number of successfully parsed games.
+/// The file is read one game at a time, so files of any size can be
+/// imported. Games that can't be parsed or saved are skipped, the returned
+/// report lists them along with the reason.
+///
+/// Parameters:
+/// - `file_path`: The PGN file to import
+/// - `policy`: Which tags games must have, all of the Seven Tag Roster
+///   if not given
+/// - `keep_incomplete`: Keep the moves before a game's first illegal move
+///   instead of skipping the game
 #[tauri::command]
 pub async fn import_pgn_games(
+    file_path: &str,
+    policy: Option<ValidationPolicy>,
+    keep_incomplete: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<models::PgnImportReport, AppError> {
+    load_pgn_file(
+        &state.db,
+        file_path,
+        import_options(policy, keep_incomplete),
+    )
+    .await
+}
+
+/// Imports chess games from PGN text, like games pasted in
+///
+/// Takes the same parameters as `import_pgn_games`, but with the PGN
+/// itself in place of a file. Use `import_pgn_games` for files, which
+/// aren't sent over IPC as a whole.
+#[tauri::command]
+pub async fn import_pgn_text(
     pgn: &str,
+    policy: Option<ValidationPolicy>,
+    keep_incomplete: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<models::PgnImportReport, AppError> {
+    let options = import_options(policy, keep_incomplete);
+    models::ChessGame::save_from_pgn(&state.db, pgn, options).await
+}
+
+/// The options for a PGN import, with the defaults for those not given
+fn import_options(
+    policy: Option<ValidationPolicy>,
+    keep_incomplete: Option<bool>,
+) -> models::PgnImportOptions {
+    models::PgnImportOptions {
+        policy: policy.unwrap_or_default(),
+        keep_incomplete_games: keep_incomplete.unwrap_or(false),
+    }
+}
+
+/// Imports the positions of an EPD test suite
+///
+/// Each position is saved as a game starting at it, with its operations
+/// like `bm` and `id` as headers. Lines that can't be parsed are skipped
+/// and listed in the returned report.
+///
+/// Parameters:
+/// - `suite_name`: The name the positions are grouped under, "EPD" if not
+///   given
+#[tauri::command]
+pub async fn import_epd(
+    epd: &str,
+    suite_name: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<models::PgnImportReport, AppError> {
+    let suite_name = suite_name.unwrap_or_else(|| "EPD".to_string());
+    models::ChessGame::save_from_epd(&state.db, epd, &suite_name).await
+}
+
+/// Imports the games of a lichess NDJSON export file
+///
+/// The players' ratings and titles, the opening, clocks and the server's
+/// evaluations are kept. Games that can't be read are skipped and listed
+/// in the returned report.
+#[tauri::command]
+pub async fn import_lichess_games(
+    file_path: &str,
+    state: State<'_, AppState>,
+) -> Result<models::PgnImportReport, AppError> {
+    load_lichess_file(&state.db, file_path).await
+}
+
+/// Imports the games of a chess.com archive file
+///
+/// The players' ratings and accuracies, the opening and clocks are kept.
+/// Games that can't be read are skipped and listed in the returned report.
+#[tauri::command]
+pub async fn import_chesscom_games(
+    file_path: &str,
+    state: State<'_, AppState>,
+) -> Result<models::PgnImportReport, AppError> {
+    load_chesscom_file(&state.db, file_path).await
+}
+
+/// Imports the games of a SCID database
+///
+/// Parameters:
+/// - `file_path`: Any of the database's `.si4`, `.sn4` and `.sg4` files
+#[tauri::command]
+pub async fn import_scid_database(
+    file_path: &str,
     state: State<'_, AppState>,
-) -> Result<String, AppError> {
-    let games = models::ChessGame::save_from_pgn(&state.db, pgn).await?;
-    Ok(format!("Successfully parsed {} games", games.len()))
+) -> Result<models::PgnImportReport, AppError> {
+    load_scid_database(&state.db, file_path).await
 }
 
 /// Function to download and import chess opening ECO database
@@ -1,26 +1,39 @@
 use std::time::Duration;
 
+use crate::models::{self, chess_variant, is_chess960};
 use crate::utils::AppError;
 use crate::AppState;
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use ok_analysis::*;
 use ok_engine_manager::{
-    events::EngineStateInfoEvent, manager::EngineAnalysisConfig,
-    protocol::OptionValue, utils::calculate_analysis_time,
+    manager::{EngineAnalysisConfig, TimeStrategy},
+    protocol::OptionValue,
+    test_suite::SuiteResult,
+    time_management::{score_to_centipawns, AdaptiveTimeBudget, PositionHints},
+    utils::{calculate_analysis_time, EngineManagerError},
 };
+use ok_parse::epd::{parse_epd, write_epd};
+use ok_parse::uci::InfoParams;
+use shakmaty::variant::Variant;
+use shakmaty::CastlingMode;
 use tauri::State;
 use tokio::time::sleep;
 
+/// Time (in milliseconds) an engine gets past a search's time limit before
+/// it's considered hung
+const SEARCH_GRACE_MS: u64 = 5000;
+
 /// Gets the state of all loaded chess engines
 ///
 /// Returns a JSON string containing the state of all engines.
 #[tauri::command]
 pub async fn get_all_engine_state(
     state: State<'_, AppState>
-) -> Result<String, String> {
+) -> Result<String, AppError> {
     let engine_manager = state.engine_manager.lock().await;
     let states = engine_manager.get_all_engine_state().await;
-    Ok(serde_json::to_string(&states).unwrap())
+    serde_json::to_string(&states)
+        .map_err(|e| AppError::SerializationError(e.to_string()))
 }
 
 /// Loads a UCI chess engine
@@ -35,7 +48,7 @@ pub async fn load_engine(
     name: String,
     path: String,
     state: State<'_, AppState>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     debug!("Loading engine: {}", name);
 
     let mut engine_manager = state.engine_manager.lock().await;
@@ -46,7 +59,7 @@ pub async fn load_engine(
         Ok(_) => Ok(()),
         Err(e) => {
             error!("Error loading engine: {}", e);
-            Err(e.to_string())
+            Err(e.into())
         }
     }
 }
@@ -59,11 +72,11 @@ pub async fn load_engine(
 pub async fn unload_engine(
     name: String,
     state: State<'_, AppState>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     let mut engine_manager = state.engine_manager.lock().await;
     let result = engine_manager.remove_engine(&name).await;
     drop(engine_manager);
-    result.map_err(|e| e.to_string())
+    Ok(result?)
 }
 
 /// Analyzes a chess position with the loaded engine
@@ -83,14 +96,11 @@ pub async fn analyze_position(
     time_ms: Option<usize>,
     multipv: Option<u32>,
     state: State<'_, AppState>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     debug!("Analyzing position");
     let mut engine_manager = state.engine_manager.lock().await;
 
-    engine_manager
-        .set_position(Some(&fen), None)
-        .await
-        .map_err(|e| e.to_string())?;
+    engine_manager.set_position(Some(&fen), None).await?;
 
     let result = engine_manager
         .start_position_analysis(
@@ -101,7 +111,7 @@ pub async fn analyze_position(
         .await;
 
     drop(engine_manager);
-    result.map_err(|e| e.to_string())
+    Ok(result?)
 }
 
 /// Analyzes a chess position derived from a game session's current move/position
@@ -119,29 +129,37 @@ pub async fn analyze_move(
     time_ms: Option<usize>,
     multipv: Option<u32>,
     state: State<'_, AppState>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     // First get the game session
     let game_session_manager = state.game_session_manager.lock().await;
     let game_session = game_session_manager
         .get_session(board_id)
-        .ok_or(AppError::SessionError("Game session not found".to_string()))
-        .unwrap();
+        .ok_or(AppError::SessionError("Game session not found".to_string()))?;
 
     // Then get the move from the game session
-    let current_move_id = game_session.game.move_tree.current_node_id;
+    let current_move_id = game_session
+        .game
+        .move_tree
+        .current_node_id
+        .ok_or(AppError::SessionError("No current move".to_string()))?;
     let current_node =
-        game_session.game.move_tree.nodes[current_move_id.unwrap()].clone();
+        game_session.game.move_tree.nodes[current_move_id].clone();
+    drop(game_session_manager);
 
     // Get the position from the current node
     let current_position = current_node.position;
+    let variant = current_position
+        .chess_variant()
+        .map_err(|e| AppError::ChessError(e.to_string()))?;
+    let chess960 = current_position.castling_mode() == CastlingMode::Chess960;
     let fen = current_position.fen;
 
-    // Set the position for the engine
+    // Set the position for the engine, in the game's variant and with
+    // Chess960 castling if the game needs it
     let mut engine_manager = state.engine_manager.lock().await;
-    engine_manager
-        .set_position(Some(&fen), None)
-        .await
-        .map_err(|e| e.to_string())?;
+    engine_manager.set_variant(variant.uci()).await?;
+    engine_manager.set_chess960(chess960).await?;
+    engine_manager.set_position(Some(&fen), None).await?;
 
     // Start the analysis
     let result = engine_manager
@@ -152,7 +170,7 @@ pub async fn analyze_move(
         )
         .await;
     drop(engine_manager);
-    result.map_err(|e| e.to_string())
+    Ok(result?)
 
     // TODO: We need a way to store the evaluation results on the game move
     // I'm not sure if we should do this here/when the analysis events are emitted
@@ -174,8 +192,8 @@ pub async fn analyze_game(
     engine_analysis_config: Option<EngineAnalysisConfig>,
     meta_analysis_config: Option<MetaAnalysisConfig>,
     state: State<'_, AppState>,
-) -> Result<GameAnalysisResult, String> {
-    let _meta_config = meta_analysis_config.unwrap_or_default();
+) -> Result<GameAnalysisResult, AppError> {
+    let meta_config = meta_analysis_config.unwrap_or_default();
     let engine_config = engine_analysis_config.unwrap_or_default();
 
     info!("Starting game analysis for board {}", board_id);
@@ -185,39 +203,79 @@ pub async fn analyze_game(
     let game_session_manager = state.game_session_manager.lock().await;
     let game_session = game_session_manager
         .get_session(board_id)
-        .ok_or(AppError::SessionError("Game session not found".to_string()))
-        .unwrap();
+        .ok_or(AppError::SessionError("Game session not found".to_string()))?;
     let positions =
         game_session.extract_positions(engine_config.include_variations);
+    let variant = &game_session.game.variant;
+    let chess960 = is_chess960(variant);
+    let variant = chess_variant(variant).ok_or_else(|| {
+        AppError::ChessError(format!("Unsupported variant '{}'", variant))
+    })?;
 
     info!("Extracted {} positions for analysis", positions.len());
 
     if positions.is_empty() {
-        return Err("No positions found to analyze".to_string());
+        return Err(AppError::SessionError(
+            "No positions found to analyze".to_string(),
+        ));
     }
 
     // 2. Calculate time per position
-    let (depth, time_ms) =
+    let limits =
         calculate_analysis_time(&engine_config.time_strategy, positions.len());
-    info!(
-        "Analysis time per position: depth={:?}, time_ms={:?}",
-        depth, time_ms
-    );
+    info!("Analysis limits per position: {:?}", limits);
 
-    // 3. Get engine
-    let engine_manager = state.engine_manager.lock().await;
-    let engine_option = engine_manager.get_engine(&engine_config.engine_name);
-    let engine = engine_option
-        // FIXME: Handle this error
-        // .ok_or(AppError::EngineError("Engine not found".to_string()))
-        .unwrap_or_else(|| panic!("Engine not found"));
+    // The adaptive strategy adjusts the time as positions are analyzed
+    let mut adaptive_budget = match engine_config.time_strategy {
+        TimeStrategy::Adaptive { total_seconds } => {
+            Some(AdaptiveTimeBudget::new(total_seconds, positions.len()))
+        }
+        _ => None,
+    };
+
+    // 3. Ensure the engine exists, plays the game's variant and castles by
+    // Chess960 rules if the game needs it
+    let mut engine_manager = state.engine_manager.lock().await;
+    if engine_manager
+        .get_engine(&engine_config.engine_name)
+        .is_none()
+    {
+        return Err(EngineManagerError::unknown_engine(
+            &engine_config.engine_name,
+        )
+        .into());
+    }
+    engine_manager
+        .set_engine_variant(&engine_config.engine_name, variant.uci())
+        .await?;
+    engine_manager
+        .set_engine_chess960(&engine_config.engine_name, chess960)
+        .await?;
+    drop(engine_manager);
+
+    // Moves in the opening book are categorized as book moves, and the
+    // adaptive strategy gives them less time. Books only cover standard
+    // chess, and analysis carries on without the book if it can't be read.
+    let book = match &meta_config.book_path {
+        Some(path) if variant == Variant::Chess => {
+            book::PolyglotBook::open(path)
+                .inspect_err(|e| warn!("Opening book not used: {}", e))
+                .ok()
+        }
+        _ => None,
+    };
+
+    // A hung engine is recovered instead of stalling the whole analysis.
+    // Searches without a time limit are given up on after this long.
+    let max_timeout = Duration::from_secs(meta_config.max_analysis_timeout);
 
     // 4. Analyze each position
     let total_positions = positions.len();
     let mut move_analyses = Vec::new();
+    let mut book_moves = Vec::new();
     let mut total_positions_analyzed = 0u32;
 
-    for current_position in positions {
+    for current_position in &positions {
         // Log a progress update every 10 positions
         if total_positions_analyzed % 10 == 0 {
             info!(
@@ -227,44 +285,88 @@ pub async fn analyze_game(
             );
         }
 
-        let current_fen = &current_position.fen;
+        let current_fen = &current_position.position.fen;
+        // The position the move leading here was played from, which isn't
+        // the previous position at the start of a variation
+        let parent_fen = current_position
+            .parent
+            .map(|parent| positions[parent].position.fen.as_str());
+        let is_book = book.as_ref().is_some_and(|book| {
+            parent_fen.is_some_and(|parent| {
+                book::is_book_move(book, parent, current_fen)
+            })
+        });
+        book_moves.push(is_book);
+
+        // Let the engine decide when the result is good enough
+        if let TimeStrategy::UntilStable { convergence } =
+            &engine_config.time_strategy
+        {
+            let mut engine_manager = state.engine_manager.lock().await;
+            let result = engine_manager
+                .analyze_until_stable(
+                    &engine_config.engine_name,
+                    current_fen,
+                    convergence,
+                    search_timeout(convergence.max_time_ms, max_timeout),
+                )
+                .await;
+            drop(engine_manager);
+
+            match result {
+                Ok(analysis) => {
+                    info!(
+                        "Best move received {} stop reason: {:?}",
+                        analysis.best_move, analysis.stop_reason
+                    );
+                    move_analyses.push(analysis.last_update);
+                }
+                Err(e) => {
+                    error!("Position analysis failed: {}", e);
+                    move_analyses.push(None);
+                }
+            }
+            total_positions_analyzed += 1;
+            continue;
+        }
 
-        // Start the analysis
+        let position_limits = match &adaptive_budget {
+            Some(budget) => budget.next_limits(&PositionHints {
+                is_book,
+                is_forced_recapture: parent_fen.is_some_and(|parent| {
+                    position::is_forced_recapture(parent, current_fen)
+                }),
+            }),
+            None => limits,
+        };
+        let position_start = std::time::Instant::now();
+
+        // Analyze the position and wait for the engine's result
         let mut engine_manager = state.engine_manager.lock().await;
-        let _ = engine_manager
-            .quick_start_position_analysis_for(
+        let result = engine_manager
+            .analyze_position_for(
                 &engine_config.engine_name,
                 current_fen,
-                depth,
-                time_ms,
-                None,
+                position_limits,
+                search_timeout(position_limits.movetime_ms, max_timeout),
             )
             .await;
         drop(engine_manager);
 
-        let mut current_analysis = None;
-        // Monitor the engine's updates
-        let _ = engine
-            .monitor_events(|event| match event {
-                EngineStateInfoEvent::AnalysisUpdate(update) => {
-                    info!("Analysis update received");
-                    current_analysis = Some(update);
-                    true // Continue monitoring
-                }
-                EngineStateInfoEvent::BestMove(best_move, best_move_score) => {
-                    // Indicates the analysis is complete
-                    info!(
-                        "Best move received {} score: {:?}",
-                        best_move, best_move_score
-                    );
-                    false // Stop monitoring
-                }
-                _ => {
-                    // For any other events, ignore them and continue to monitor
-                    true
-                }
-            })
-            .await;
+        let current_analysis = match result {
+            Ok(analysis) => analysis,
+            Err(e) => {
+                error!("Position analysis failed: {}", e);
+                None
+            }
+        };
+
+        if let Some(budget) = adaptive_budget.as_mut() {
+            budget.record(
+                position_start.elapsed().as_millis() as u64,
+                current_analysis.as_ref(),
+            );
+        }
 
         // Current analysis should contain the latest engine evaluation
         // track it
@@ -277,9 +379,25 @@ pub async fn analyze_game(
 
     // All positions analyzed
     info!("Total positions analyzed: {}", total_positions_analyzed);
+    // Categorize each move by how much it changed the evaluation from the
+    // position it was played in, the root has no move leading to it
+    let move_categories: Vec<Option<MoveCategory>> = positions
+        .iter()
+        .enumerate()
+        .map(|(index, position)| {
+            let parent = position.parent?;
+            categorize_analyzed_move(
+                move_analyses[parent].as_ref(),
+                move_analyses[index].as_ref(),
+                book_moves[index],
+                &meta_config.move_thresholds,
+            )
+        })
+        .collect();
+
     // log all positions analyzed
-    for position in &move_analyses {
-        info!("\tPosition: {:?}", position);
+    for (position, category) in move_analyses.iter().zip(&move_categories) {
+        info!("\tPosition: {:?} category: {:?}", position, category);
     }
 
     // // 5. Generate summary statistics
@@ -353,16 +471,162 @@ pub async fn analyze_game(
     todo!("Implement game analysis")
 }
 
+/// How long to wait for a search before recovering the engine
+///
+/// A search limited by time gets a few seconds past its limit. Searches
+/// only limited by depth or nodes can't be timed in advance and get
+/// `max_timeout`.
+fn search_timeout(
+    movetime_ms: Option<u32>,
+    max_timeout: Duration,
+) -> Duration {
+    match movetime_ms {
+        Some(movetime_ms) => {
+            Duration::from_millis(u64::from(movetime_ms) + SEARCH_GRACE_MS)
+        }
+        None => max_timeout,
+    }
+}
+
+/// Categorize the move between two analyzed positions
+///
+/// Book moves are categorized as such even without an evaluation. Other
+/// moves need both positions analyzed, their evaluation difference is
+/// from the moving side's view, negative when the move lost ground.
+fn categorize_analyzed_move(
+    before: Option<&InfoParams>,
+    after: Option<&InfoParams>,
+    is_book: bool,
+    thresholds: &MoveCategoryThresholds,
+) -> Option<MoveCategory> {
+    let score = |analysis: Option<&InfoParams>| {
+        analysis
+            .and_then(|info| info.score.as_ref())
+            .map(score_to_centipawns)
+    };
+    // Scores are from the side to move, which alternates
+    let evaluation_difference = match (score(before), score(after)) {
+        (Some(before), Some(after)) => Some((-after - before) as f32),
+        _ if is_book => Some(0.0),
+        _ => None,
+    }?;
+
+    let context = r#move::MoveContext {
+        is_book,
+        is_capture: false,
+        is_check: false,
+        is_castling: false,
+        piece_moved: None,
+        material_balance: None,
+    };
+    Some(r#move::categorize_move(
+        evaluation_difference,
+        thresholds,
+        &context,
+    ))
+}
+
+/// Runs an engine on an EPD test suite
+///
+/// Parameters:
+/// - `engine_name`: The name of the engine to test
+/// - `epd`: The test suite, positions with `bm` or `am` operations
+/// - `movetime_ms`: How long the engine searches each position, 5 seconds
+///   if not given
+/// - `import_results`: Save the positions along with the engine's results
+///   to the database
+///
+/// Returns the number of positions solved and the result of each one.
+#[tauri::command]
+pub async fn run_test_suite(
+    engine_name: String,
+    epd: String,
+    movetime_ms: Option<u32>,
+    import_results: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<SuiteResult, AppError> {
+    let parsed = parse_epd(&epd);
+    for e in &parsed.errors {
+        error!("Skipping test suite line: {}", e);
+    }
+    if parsed.records.is_empty() {
+        return Err(AppError::GeneralError(
+            "No positions found in test suite".to_string(),
+        ));
+    }
+
+    let movetime_ms = movetime_ms.unwrap_or(5000);
+    // Give the engine a few seconds past its move time before recovering it
+    let timeout =
+        Duration::from_millis(u64::from(movetime_ms) + SEARCH_GRACE_MS);
+
+    let mut engine_manager = state.engine_manager.lock().await;
+    let result = engine_manager
+        .set_engine_variant(&engine_name, Variant::Chess.uci())
+        .await;
+    drop(engine_manager);
+    result?;
+
+    let total = parsed.records.len();
+    let mut positions = Vec::with_capacity(total);
+    for (index, record) in parsed.records.iter().enumerate() {
+        // Release the engine between positions so other commands, like
+        // stopping the engine, aren't blocked for the whole suite
+        let mut engine_manager = state.engine_manager.lock().await;
+        let position = engine_manager
+            .run_test_position(&engine_name, record, movetime_ms, timeout)
+            .await;
+        drop(engine_manager);
+
+        let position = position?;
+        if let Some(ref e) = position.error {
+            error!("Test suite position {} failed: {}", index + 1, e);
+        }
+        info!(
+            "Test suite position {}/{} {}",
+            index + 1,
+            total,
+            if position.solved {
+                "solved"
+            } else {
+                "not solved"
+            }
+        );
+        positions.push(position);
+    }
+    let result = SuiteResult::new(&engine_name, positions);
+    info!(
+        "{} solved {}/{} test suite positions",
+        engine_name, result.solved, result.total
+    );
+
+    if import_results.unwrap_or(false) {
+        let mut records = parsed.records;
+        for (record, position) in records.iter_mut().zip(&result.positions) {
+            position.annotate(record);
+        }
+        let suite_name = format!("Test suite ({})", engine_name);
+        models::ChessGame::save_from_epd(
+            &state.db,
+            &write_epd(&records),
+            &suite_name,
+        )
+        .await?;
+    }
+
+    Ok(result)
+}
+
 /// Stops any ongoing analysis
 ///
 /// Terminates the current engine analysis and returns the engine to idle state.
 #[tauri::command]
-pub async fn stop_analysis(state: State<'_, AppState>) -> Result<(), String> {
+pub async fn stop_analysis(state: State<'_, AppState>) -> Result<(), AppError> {
     debug!("Stopping analysis");
     let mut engine_manager = state.engine_manager.lock().await;
     let result = engine_manager.stop_analysis().await;
     drop(engine_manager);
-    result.map_err(|e| e.to_string())
+    Ok(result?)
 }
 
 /// Sets an option for a specific chess engine
@@ -377,13 +641,13 @@ pub async fn set_engine_option(
     option: String,
     value: String,
     state: State<'_, AppState>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     let mut engine_manager = state.engine_manager.lock().await;
     let result = engine_manager
         .set_engine_option(&engine_name, &option, OptionValue::String(value))
         .await;
     drop(engine_manager);
-    result.map_err(|e| e.to_string())
+    Ok(result?)
 }
 
 /// Sets the current position for the engine
@@ -394,9 +658,9 @@ pub async fn set_engine_option(
 pub async fn set_position(
     fen: String,
     state: State<'_, AppState>,
-) -> Result<(), String> {
+) -> Result<(), AppError> {
     let mut engine_manager = state.engine_manager.lock().await;
     let result = engine_manager.set_position(Some(&fen), None).await;
     drop(engine_manager);
-    result.map_err(|e| e.to_string())
+    Ok(result?)
 }
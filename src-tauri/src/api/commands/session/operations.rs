@@ -179,6 +179,29 @@ pub async fn navigate_to_end(
     }
 }
 
+/// Sets the Numeric Annotation Glyphs of the current move
+///
+/// Parameters:
+/// - `board_id`: The ID of the board/session
+/// - `nags`: The NAG codes, like 1 for "!" or 18 for "+-"
+///
+/// Returns a JSON string containing the updated game state.
+#[tauri::command]
+pub async fn set_move_nags(
+    board_id: i32,
+    nags: Vec<u8>,
+    state: State<'_, AppState>,
+) -> Result<String, AppError> {
+    let mut game_session_manager = state.game_session_manager.lock().await;
+    match game_session_manager.set_move_nags(board_id, &nags) {
+        Ok(_) => {
+            let session = game_session_manager.get_session(board_id).unwrap();
+            Ok(serde_json::to_string(&session.game).unwrap())
+        }
+        Err(e) => Err(e),
+    }
+}
+
 /// Gets the move history for a game session
 ///
 /// Parameters:
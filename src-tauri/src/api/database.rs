@@ -390,6 +390,10 @@ pub async fn get_game_moves(
         .await?;
 
     let move_positions = db_moves.load_one(position::Entity, db).await?;
+    let variant = game::Entity::find_by_id(game_id)
+        .one(db)
+        .await?
+        .and_then(|game| game.variant);
 
     let moves = db_moves
         .into_iter()
@@ -400,13 +404,13 @@ pub async fn get_game_moves(
                     id: 0,
                     fen: "".to_string(),
                     evaluations: Vec::new(),
-                    variant: None,
+                    variant: variant.clone(),
                 },
                 |p| ChessPosition {
                     id: p.position_id,
                     fen: p.fen,
                     evaluations: Vec::new(),
-                    variant: None,
+                    variant: variant.clone(),
                 },
             );
 
@@ -418,6 +422,7 @@ pub async fn get_game_moves(
                 uci: curr_move.uci,
                 position: Some(move_position),
                 annotations: Vec::new(),
+                nags: Vec::new(),
                 time_info: None,
                 parent_move_id: curr_move.parent_move_id,
             }
@@ -1,3 +1,4 @@
+pub mod book;
 pub mod chess;
 pub mod commands;
 pub mod database;
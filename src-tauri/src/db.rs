@@ -1,6 +1,7 @@
 use std::time::Duration;
 
 use log::error;
+use ok_parse::scid::ScidReader;
 use sea_orm::*;
 use sea_orm_migration::*;
 use tauri::{AppHandle, Manager};
@@ -38,13 +39,66 @@ pub async fn reset_database(db: &DatabaseConnection) -> Result<(), AppError> {
     Ok(())
 }
 
+/// Import the games of a PGN file into the database
+///
+/// The file is streamed one game at a time, so its size isn't limited
+/// by the available memory. `options` decide which games are accepted.
 pub async fn load_pgn_file(
     db: &DatabaseConnection,
     file_path: &str,
-) -> Result<Vec<models::ChessGame>, AppError> {
-    let pgn_content = std::fs::read_to_string(file_path)
+    options: models::PgnImportOptions,
+) -> Result<models::PgnImportReport, AppError> {
+    let file = fs::File::open(file_path)
+        .await
         .map_err(|e| AppError::IoError(e.to_string()))?;
-    models::ChessGame::save_from_pgn(db, &pgn_content).await
+    let total_bytes = file.metadata().await.ok().map(|metadata| metadata.len());
+    let reader = std::io::BufReader::new(file.into_std().await);
+    models::ChessGame::save_from_pgn_reader(db, reader, total_bytes, options)
+        .await
+}
+
+/// Import the games of a lichess NDJSON export into the database
+///
+/// Like PGN files, the export is streamed one game at a time.
+pub async fn load_lichess_file(
+    db: &DatabaseConnection,
+    file_path: &str,
+) -> Result<models::PgnImportReport, AppError> {
+    let file = fs::File::open(file_path)
+        .await
+        .map_err(|e| AppError::IoError(e.to_string()))?;
+    let reader = std::io::BufReader::new(file.into_std().await);
+    models::ChessGame::save_from_lichess_reader(db, reader).await
+}
+
+/// Import the games of a chess.com archive into the database
+pub async fn load_chesscom_file(
+    db: &DatabaseConnection,
+    file_path: &str,
+) -> Result<models::PgnImportReport, AppError> {
+    let file = fs::File::open(file_path)
+        .await
+        .map_err(|e| AppError::IoError(e.to_string()))?;
+    models::ChessGame::save_from_chesscom(db, file.into_std().await).await
+}
+
+/// Import the games of a SCID database into the database
+///
+/// The path can be any of the database's `.si4`, `.sn4` and `.sg4` files.
+/// Games are read one at a time, like PGN files.
+pub async fn load_scid_database(
+    db: &DatabaseConnection,
+    file_path: &str,
+) -> Result<models::PgnImportReport, AppError> {
+    // Opening reads the whole name file
+    let path = file_path.to_string();
+    let reader = tokio::task::spawn_blocking(move || ScidReader::open(path))
+        .await
+        .map_err(|e| AppError::GeneralError(e.to_string()))?
+        .map_err(|e| {
+            AppError::GeneralError(format!("Can't open SCID database: {}", e))
+        })?;
+    models::ChessGame::save_from_scid(db, reader).await
 }
 
 /// Function to resolve the database path/url
@@ -8,6 +8,7 @@ pub mod game;
 pub mod game_header;
 pub mod game_tag;
 pub mod r#move;
+pub mod move_nag;
 pub mod move_tag;
 pub mod move_time_tracking;
 pub mod opening;
@@ -37,6 +37,8 @@ pub enum Relation {
         on_delete = "NoAction"
     )]
     SelfRef,
+    #[sea_orm(has_many = "super::move_nag::Entity")]
+    MoveNag,
     #[sea_orm(has_many = "super::move_tag::Entity")]
     MoveTag,
     #[sea_orm(has_many = "super::move_time_tracking::Entity")]
@@ -63,6 +65,12 @@ impl Related<super::game::Entity> for Entity {
     }
 }
 
+impl Related<super::move_nag::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::MoveNag.def()
+    }
+}
+
 impl Related<super::move_tag::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::MoveTag.def()
@@ -5,6 +5,7 @@ pub use super::evaluation::Entity as Evaluation;
 pub use super::game::Entity as Game;
 pub use super::game_header::Entity as GameHeader;
 pub use super::game_tag::Entity as GameTag;
+pub use super::move_nag::Entity as MoveNag;
 pub use super::move_tag::Entity as MoveTag;
 pub use super::move_time_tracking::Entity as MoveTimeTracking;
 pub use super::opening::Entity as Opening;
@@ -18,6 +18,7 @@ pub const DATABASE_FILE_NAME: &str = "chess.db";
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_process::init())
+        .plugin(tauri_plugin_dialog::init())
         .plugin(
             tauri_plugin_log::Builder::new()
                 .target(Target::new(TargetKind::Stdout))
@@ -47,8 +48,13 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             // Database management commands
             api::commands::database::import_pgn_games,
+            api::commands::database::import_pgn_text,
             api::commands::database::empty_db,
             api::commands::database::import_eco_database,
+            api::commands::database::import_epd,
+            api::commands::database::import_lichess_games,
+            api::commands::database::import_chesscom_games,
+            api::commands::database::import_scid_database,
             // Database game commands
             api::commands::database::query_games,
             api::commands::database::get_game_by_id,
@@ -73,12 +79,15 @@ pub fn run() {
             api::commands::session::navigate_to_end,
             api::commands::session::navigate_to_start,
             api::commands::session::reset_to_position,
+            api::commands::session::set_move_nags,
             api::commands::session::get_session_moves,
             // Session persistence commands
             api::commands::session::save_session,
             api::commands::session::save_all_sessions,
             // Chess commands
             api::commands::chess::get_legal_moves,
+            api::commands::chess::get_book_moves,
+            api::commands::chess::build_opening_book,
             // Engine commands
             api::commands::engine::load_engine,
             api::commands::engine::unload_engine,
@@ -89,6 +98,7 @@ pub fn run() {
             api::commands::engine::set_position,
             api::commands::engine::analyze_game,
             api::commands::engine::get_all_engine_state,
+            api::commands::engine::run_test_suite,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
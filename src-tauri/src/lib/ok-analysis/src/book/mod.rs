@@ -0,0 +1,347 @@
+//! Opening books
+//!
+//! Reads opening books in the Polyglot `.bin` format. A book is a list of
+//! 16 byte entries sorted by position key, each one a move that can be
+//! played in the position along with its weight and learn value. All
+//! numbers are big-endian.
+//!
+//! Position keys are the Polyglot Zobrist hashes, which are the 64 bit
+//! hashes shakmaty computes. The en passant square only counts when a pawn
+//! of the side to move stands next to the pawn that moved two squares.
+//!
+//! Books can also be built from games, see `writer`.
+
+use std::cmp::Reverse;
+use std::path::Path;
+
+use ok_utils::ts_export;
+use serde::Serialize;
+use shakmaty::san::San;
+use shakmaty::uci::UciMove;
+use shakmaty::zobrist::Zobrist64;
+use shakmaty::{CastlingMode, EnPassantMode, Move, Position, Role};
+
+use crate::utils::parse_fen;
+
+pub mod writer;
+
+pub use writer::{
+    BookBuildOptions, BookBuilder, BookColor, BookWeighting, GameOutcome,
+};
+
+/// Size of a book entry in bytes
+pub const ENTRY_SIZE: usize = 16;
+
+/// Error types for opening books
+#[derive(Debug, Clone, Serialize, thiserror::Error)]
+pub enum BookError {
+    /// The book file couldn't be read
+    #[error("Failed to read book: {0}")]
+    Io(String),
+    /// The book isn't a whole number of entries
+    #[error("Book size {0} is not a multiple of {ENTRY_SIZE} bytes")]
+    InvalidLength(usize),
+}
+
+/// An entry of a Polyglot book
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PolyglotEntry {
+    /// Key of the position the move is played in
+    pub key: u64,
+    /// The move, encoded as described in `encode_move`
+    pub mv: u16,
+    /// How often the move should be played relative to the position's
+    /// other moves
+    pub weight: u16,
+    /// Used by engines to learn from their games, usually 0
+    pub learn: u32,
+}
+
+impl PolyglotEntry {
+    /// Decode an entry from its 16 bytes
+    pub fn from_bytes(bytes: &[u8; ENTRY_SIZE]) -> Self {
+        let mut key = [0; 8];
+        key.copy_from_slice(&bytes[0..8]);
+        Self {
+            key: u64::from_be_bytes(key),
+            mv: u16::from_be_bytes([bytes[8], bytes[9]]),
+            weight: u16::from_be_bytes([bytes[10], bytes[11]]),
+            learn: u32::from_be_bytes([
+                bytes[12], bytes[13], bytes[14], bytes[15],
+            ]),
+        }
+    }
+
+    /// Encode the entry as its 16 bytes
+    pub fn to_bytes(&self) -> [u8; ENTRY_SIZE] {
+        let mut bytes = [0; ENTRY_SIZE];
+        bytes[0..8].copy_from_slice(&self.key.to_be_bytes());
+        bytes[8..10].copy_from_slice(&self.mv.to_be_bytes());
+        bytes[10..12].copy_from_slice(&self.weight.to_be_bytes());
+        bytes[12..16].copy_from_slice(&self.learn.to_be_bytes());
+        bytes
+    }
+}
+
+/// A move found in an opening book
+#[ts_export]
+pub struct BookMove {
+    pub san: String,
+    pub uci: String,
+    /// How often the move should be played relative to the position's
+    /// other book moves
+    pub weight: u16,
+    /// The entry's learn value
+    pub learn: u32,
+}
+
+/// An opening book in the Polyglot format, held in memory
+#[derive(Debug, Clone, Default)]
+pub struct PolyglotBook {
+    /// Entries sorted by key
+    entries: Vec<PolyglotEntry>,
+}
+
+impl PolyglotBook {
+    /// Read a book from a `.bin` file
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, BookError> {
+        let bytes =
+            std::fs::read(path).map_err(|e| BookError::Io(e.to_string()))?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Read a book from the contents of a `.bin` file
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, BookError> {
+        if !bytes.len().is_multiple_of(ENTRY_SIZE) {
+            return Err(BookError::InvalidLength(bytes.len()));
+        }
+        let entries = bytes
+            .chunks_exact(ENTRY_SIZE)
+            .map(|chunk| {
+                PolyglotEntry::from_bytes(
+                    chunk.try_into().expect("chunk is one entry"),
+                )
+            })
+            .collect();
+        Ok(Self::from_entries(entries))
+    }
+
+    /// Create a book from its entries, in any order
+    pub fn from_entries(mut entries: Vec<PolyglotEntry>) -> Self {
+        // Books should already be sorted, but lookups depend on it. The
+        // most played moves of a position come first like in Polyglot's
+        // own books.
+        entries.sort_by_key(|entry| (entry.key, Reverse(entry.weight)));
+        Self { entries }
+    }
+
+    /// All entries, sorted by key
+    pub fn entries(&self) -> &[PolyglotEntry] {
+        &self.entries
+    }
+
+    /// The entries of a position
+    pub fn entries_for(
+        &self,
+        key: u64,
+    ) -> &[PolyglotEntry] {
+        let start = self.entries.partition_point(|entry| entry.key < key);
+        let end = self.entries.partition_point(|entry| entry.key <= key);
+        &self.entries[start..end]
+    }
+
+    /// Whether the book has moves for a position
+    pub fn contains<P: Position>(
+        &self,
+        position: &P,
+    ) -> bool {
+        !self.entries_for(polyglot_key(position)).is_empty()
+    }
+
+    /// The book moves of a position, the most played first
+    ///
+    /// Entries with moves that aren't legal in the position, which can
+    /// happen with a broken book or a key collision, are left out.
+    pub fn moves<P: Position>(
+        &self,
+        position: &P,
+    ) -> Vec<BookMove> {
+        let mode = position.castles().mode();
+        let legal_moves = position.legal_moves();
+        let mut moves: Vec<BookMove> = self
+            .entries_for(polyglot_key(position))
+            .iter()
+            .filter_map(|entry| {
+                let m = legal_moves
+                    .iter()
+                    .find(|m| encode_move(m) == Some(entry.mv))?;
+                Some(BookMove {
+                    san: San::from_move(position, m).to_string(),
+                    uci: m.to_uci(mode).to_string(),
+                    weight: entry.weight,
+                    learn: entry.learn,
+                })
+            })
+            .collect();
+        moves.sort_by_key(|m| Reverse(m.weight));
+        moves
+    }
+
+    /// Whether a move is in the book for a position
+    pub fn contains_move<P: Position>(
+        &self,
+        position: &P,
+        m: &Move,
+    ) -> bool {
+        let encoded = encode_move(m);
+        encoded.is_some()
+            && self
+                .entries_for(polyglot_key(position))
+                .iter()
+                .any(|entry| Some(entry.mv) == encoded)
+    }
+}
+
+/// The Polyglot key of a position
+pub fn polyglot_key<P: Position>(position: &P) -> u64 {
+    position
+        .zobrist_hash::<Zobrist64>(EnPassantMode::PseudoLegal)
+        .0
+}
+
+/// Encode a move the way Polyglot books store it
+///
+/// The bits from lowest to highest are the destination file and rank, the
+/// origin file and rank, and the promotion piece (knight 1 to queen 4).
+/// Castling is written as the king moving to its rook's square. Moves that
+/// don't exist in standard chess, like drops, can't be encoded.
+pub fn encode_move(m: &Move) -> Option<u16> {
+    match m.to_uci(CastlingMode::Chess960) {
+        UciMove::Normal {
+            from,
+            to,
+            promotion,
+        } => {
+            let promotion = match promotion {
+                None => 0,
+                Some(Role::Knight) => 1,
+                Some(Role::Bishop) => 2,
+                Some(Role::Rook) => 3,
+                Some(Role::Queen) => 4,
+                Some(_) => return None,
+            };
+            // Square indices count a1 = 0 to h8 = 63, file first, the
+            // same as the file and rank fields put together
+            Some(
+                u32::from(to) as u16
+                    | (u32::from(from) as u16) << 6
+                    | promotion << 12,
+            )
+        }
+        _ => None,
+    }
+}
+
+/// Check if the move leading from `previous_fen` to `fen` is a book move
+pub fn is_book_move(
+    book: &PolyglotBook,
+    previous_fen: &str,
+    fen: &str,
+) -> bool {
+    let (Some(previous), Some(current)) =
+        (parse_fen(previous_fen), parse_fen(fen))
+    else {
+        return false;
+    };
+    let key = polyglot_key(&current);
+
+    previous.legal_moves().iter().any(|m| {
+        book.contains_move(&previous, m)
+            && polyglot_key(&previous.clone().play(m).expect("legal move"))
+                == key
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shakmaty::fen::Fen;
+    use shakmaty::Chess;
+
+    /// The key of the starting position given by the Polyglot format
+    const START_KEY: u64 = 0x463b_9618_1691_fc9c;
+
+    fn entry(
+        key: u64,
+        mv: u16,
+        weight: u16,
+    ) -> PolyglotEntry {
+        PolyglotEntry {
+            key,
+            mv,
+            weight,
+            learn: 0,
+        }
+    }
+
+    #[test]
+    fn book_moves_are_found_by_position() {
+        let start = Chess::default();
+        assert_eq!(polyglot_key(&start), START_KEY);
+
+        // e2e4 and d2d4, along with a move of another position
+        let entries = [
+            entry(START_KEY + 1, 0x031c, 1),
+            entry(START_KEY, 0x02db, 5),
+            entry(START_KEY, 0x031c, 10),
+        ];
+        let bytes: Vec<u8> =
+            entries.iter().flat_map(|entry| entry.to_bytes()).collect();
+        let book = PolyglotBook::from_bytes(&bytes).unwrap();
+
+        let moves = book.moves(&start);
+        let found: Vec<_> = moves
+            .iter()
+            .map(|m| (m.san.as_str(), m.uci.as_str(), m.weight))
+            .collect();
+        assert_eq!(found, [("e4", "e2e4", 10), ("d4", "d2d4", 5)]);
+        assert!(book.contains(&start));
+
+        let e4 = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1";
+        let a4 = "rnbqkbnr/pppppppp/8/8/P7/8/1PPPPPPP/RNBQKBNR b KQkq - 0 1";
+        let start_fen =
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert!(is_book_move(&book, start_fen, e4));
+        assert!(!is_book_move(&book, start_fen, a4));
+
+        assert!(matches!(
+            PolyglotBook::from_bytes(&bytes[1..]),
+            Err(BookError::InvalidLength(47))
+        ));
+    }
+
+    #[test]
+    fn castling_is_encoded_as_the_king_taking_its_rook() {
+        let position: Chess = "r1bqk1nr/pppp1ppp/2n5/2b1p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4"
+            .parse::<Fen>()
+            .unwrap()
+            .into_position(CastlingMode::Standard)
+            .unwrap();
+        let castle = position
+            .legal_moves()
+            .into_iter()
+            .find(|m| m.is_castle())
+            .unwrap();
+        // e1h1
+        assert_eq!(encode_move(&castle), Some(7 | 4 << 6));
+
+        let book = PolyglotBook::from_entries(vec![entry(
+            polyglot_key(&position),
+            7 | 4 << 6,
+            1,
+        )]);
+        let moves = book.moves(&position);
+        assert_eq!(moves[0].san, "O-O");
+        assert_eq!(moves[0].uci, "e1g1");
+    }
+}
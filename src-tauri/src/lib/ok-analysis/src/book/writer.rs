@@ -0,0 +1,278 @@
+//! Builds Polyglot books from games
+//!
+//! Every move played in a game counts once towards its entry. The weight
+//! of an entry is either how often the move was played or how well it
+//! scored, two points for each win and one for each draw of the side that
+//! played it, the way Polyglot itself builds books.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use ok_utils::ts_export;
+use shakmaty::{Color, Move, Position};
+
+use super::{
+    encode_move, polyglot_key, BookError, PolyglotBook, PolyglotEntry,
+};
+
+/// How entry weights are computed
+#[ts_export]
+#[derive(Default, PartialEq, Eq)]
+pub enum BookWeighting {
+    /// Number of games the move was played in
+    Frequency,
+    /// Points scored by the move, 2 for a win and 1 for a draw
+    #[default]
+    Score,
+}
+
+/// Which side's moves a book holds
+#[ts_export]
+#[derive(Copy, PartialEq, Eq)]
+pub enum BookColor {
+    White,
+    Black,
+}
+
+/// Options for building a book
+#[ts_export]
+pub struct BookBuildOptions {
+    /// Moves played in fewer games are left out
+    pub min_games: u32,
+    pub weighting: BookWeighting,
+    /// Only keep the moves of one side, for a book that plays that side.
+    /// Both sides' moves are kept if not given.
+    pub color: Option<BookColor>,
+}
+
+impl Default for BookBuildOptions {
+    fn default() -> Self {
+        Self {
+            min_games: 1,
+            weighting: BookWeighting::default(),
+            color: None,
+        }
+    }
+}
+
+/// The result of a game a move was played in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameOutcome {
+    WhiteWins,
+    BlackWins,
+    Draw,
+    Unknown,
+}
+
+impl GameOutcome {
+    /// The outcome of a PGN result like "1-0" or "1/2-1/2"
+    pub fn from_result(result: &str) -> Self {
+        match result.trim() {
+            "1-0" => Self::WhiteWins,
+            "0-1" => Self::BlackWins,
+            "1/2-1/2" | "½-½" => Self::Draw,
+            _ => Self::Unknown,
+        }
+    }
+
+    /// Points scored by a side, 2 for a win and 1 for a draw
+    fn points_for(
+        self,
+        color: Color,
+    ) -> u32 {
+        match (self, color) {
+            (Self::WhiteWins, Color::White)
+            | (Self::BlackWins, Color::Black) => 2,
+            (Self::Draw, _) => 1,
+            _ => 0,
+        }
+    }
+}
+
+/// How often a move was played and how well it scored
+#[derive(Debug, Clone, Copy, Default)]
+struct MoveStats {
+    games: u32,
+    points: u32,
+}
+
+/// Collects the moves of games and builds a book from them
+#[derive(Debug, Clone, Default)]
+pub struct BookBuilder {
+    options: BookBuildOptions,
+    moves: HashMap<(u64, u16), MoveStats>,
+}
+
+impl BookBuilder {
+    pub fn new(options: BookBuildOptions) -> Self {
+        Self {
+            options,
+            moves: HashMap::new(),
+        }
+    }
+
+    /// Count a move played in a game
+    ///
+    /// Moves of the side the book isn't for and moves Polyglot can't
+    /// encode are ignored.
+    pub fn add_move<P: Position>(
+        &mut self,
+        position: &P,
+        m: &Move,
+        outcome: GameOutcome,
+    ) {
+        let turn = position.turn();
+        let side = match turn {
+            Color::White => BookColor::White,
+            Color::Black => BookColor::Black,
+        };
+        if self.options.color.is_some_and(|color| color != side) {
+            return;
+        }
+        let Some(encoded) = encode_move(m) else {
+            return;
+        };
+
+        let stats = self
+            .moves
+            .entry((polyglot_key(position), encoded))
+            .or_default();
+        stats.games += 1;
+        stats.points += outcome.points_for(turn);
+    }
+
+    /// Build the book from the moves counted so far
+    ///
+    /// Moves played in fewer than the minimum number of games and moves
+    /// with no weight, like moves that only lost when weighting by score,
+    /// are left out. Weights are scaled down to fit Polyglot's 16 bits
+    /// when needed.
+    pub fn build(&self) -> PolyglotBook {
+        let weight = |stats: &MoveStats| match self.options.weighting {
+            BookWeighting::Frequency => stats.games,
+            BookWeighting::Score => stats.points,
+        };
+        let kept: Vec<(u64, u16, u32)> = self
+            .moves
+            .iter()
+            .filter(|(_, stats)| stats.games >= self.options.min_games)
+            .map(|(&(key, mv), stats)| (key, mv, weight(stats)))
+            .filter(|&(_, _, weight)| weight > 0)
+            .collect();
+
+        let max_weight = kept.iter().map(|&(_, _, w)| w).max().unwrap_or(0);
+        let scale = |weight: u32| -> u16 {
+            if max_weight <= u32::from(u16::MAX) {
+                weight as u16
+            } else {
+                let scaled = u64::from(weight) * u64::from(u16::MAX)
+                    / u64::from(max_weight);
+                scaled.max(1) as u16
+            }
+        };
+
+        PolyglotBook::from_entries(
+            kept.into_iter()
+                .map(|(key, mv, weight)| PolyglotEntry {
+                    key,
+                    mv,
+                    weight: scale(weight),
+                    learn: 0,
+                })
+                .collect(),
+        )
+    }
+}
+
+impl PolyglotBook {
+    /// Encode the book as the contents of a `.bin` file
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.entries()
+            .iter()
+            .flat_map(|entry| entry.to_bytes())
+            .collect()
+    }
+
+    /// Write the book to a `.bin` file
+    pub fn save(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> Result<(), BookError> {
+        std::fs::write(path, self.to_bytes())
+            .map_err(|e| BookError::Io(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shakmaty::uci::UciMove;
+    use shakmaty::Chess;
+
+    fn play(
+        builder: &mut BookBuilder,
+        moves: &[&str],
+        outcome: GameOutcome,
+    ) {
+        let mut position = Chess::default();
+        for uci in moves {
+            let m = UciMove::from_ascii(uci.as_bytes())
+                .unwrap()
+                .to_move(&position)
+                .unwrap();
+            builder.add_move(&position, &m, outcome);
+            position = position.play(&m).unwrap();
+        }
+    }
+
+    fn weights(
+        book: &PolyglotBook,
+        position: &Chess,
+    ) -> Vec<(String, u16)> {
+        book.moves(position)
+            .into_iter()
+            .map(|m| (m.san, m.weight))
+            .collect()
+    }
+
+    #[test]
+    fn books_are_weighted_by_frequency_or_score() {
+        let games = [
+            (["e2e4", "e7e5"], GameOutcome::WhiteWins),
+            (["e2e4", "c7c5"], GameOutcome::BlackWins),
+            (["e2e4", "e7e5"], GameOutcome::Draw),
+            (["d2d4", "d7d5"], GameOutcome::Draw),
+        ];
+        let build = |options: BookBuildOptions| {
+            let mut builder = BookBuilder::new(options);
+            for (moves, outcome) in &games {
+                play(&mut builder, moves, *outcome);
+            }
+            builder.build()
+        };
+        let start = Chess::default();
+
+        let by_score = build(BookBuildOptions::default());
+        assert_eq!(
+            weights(&by_score, &start),
+            [("e4".to_string(), 3), ("d4".to_string(), 1)]
+        );
+
+        let by_frequency = build(BookBuildOptions {
+            weighting: BookWeighting::Frequency,
+            min_games: 2,
+            color: None,
+        });
+        assert_eq!(weights(&by_frequency, &start), [("e4".to_string(), 3)]);
+
+        let black = build(BookBuildOptions {
+            color: Some(BookColor::Black),
+            ..Default::default()
+        });
+        assert!(black.moves(&start).is_empty());
+        assert_eq!(black.entries().len(), 3);
+
+        let written = PolyglotBook::from_bytes(&by_score.to_bytes()).unwrap();
+        assert_eq!(written.entries(), by_score.entries());
+    }
+}
@@ -17,6 +17,8 @@
 
 use serde::{Deserialize, Serialize};
 
+pub mod book;
+pub mod r#move;
 pub mod position;
 pub mod utils;
 
@@ -73,6 +75,9 @@ pub struct MetaAnalysisConfig {
     pub move_thresholds: MoveCategoryThresholds,
     /// Maximum time to wait for engine analysis per position (seconds)
     pub max_analysis_timeout: u64,
+    /// Polyglot opening book used to find book moves
+    #[serde(default)]
+    pub book_path: Option<String>,
 }
 
 /// Move categorization thresholds in centipawns
@@ -125,6 +130,7 @@ impl Default for MetaAnalysisConfig {
         Self {
             move_thresholds: MoveCategoryThresholds::default(),
             max_analysis_timeout: 30,
+            book_path: None,
         }
     }
 }
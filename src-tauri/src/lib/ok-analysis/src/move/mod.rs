@@ -1,6 +1,12 @@
+//! Categorize moves from their context and engine evaluation
+
+use crate::{MoveCategory, MoveCategoryThresholds};
+
 /// Context for move categorization (extensible for future enhancements)
 #[derive(Debug, Clone)]
 pub struct MoveContext {
+    /// The move is in the opening book
+    pub is_book: bool,
     pub is_capture: bool,
     pub is_check: bool,
     pub is_castling: bool,
@@ -9,12 +15,16 @@ pub struct MoveContext {
 }
 
 /// Categorize a move based on evaluation difference
-fn categorize_move(
+///
+/// Book moves are categorized as such whatever their evaluation.
+pub fn categorize_move(
     evaluation_difference: f32,
     thresholds: &MoveCategoryThresholds,
-    _move_context: &MoveContext, // For future enhancement
+    move_context: &MoveContext,
 ) -> MoveCategory {
-    if evaluation_difference >= thresholds.brilliant as f32 {
+    if move_context.is_book {
+        MoveCategory::Book
+    } else if evaluation_difference >= thresholds.brilliant as f32 {
         MoveCategory::Brilliant
     } else if evaluation_difference >= thresholds.excellent as f32 {
         if evaluation_difference >= -5.0 {
@@ -4,6 +4,7 @@ use log::error;
 use shakmaty::{Move, Position};
 use tactics::analyze_tactics;
 
+use crate::utils::parse_fen;
 use crate::GameAnalysisSummary;
 
 pub mod tactics;
@@ -80,3 +81,36 @@ pub fn analyze_position<T: Position>(
         analysis_summary: GameAnalysisSummary::default(),
     }
 }
+
+/// Check if the side to move is expected to recapture
+///
+/// True when the move leading from `previous_fen` to `fen` captured a piece
+/// and the side to move can capture on the same square, as recapturing is
+/// usually the only sensible reply.
+pub fn is_forced_recapture(
+    previous_fen: &str,
+    fen: &str,
+) -> bool {
+    let (Some(previous), Some(current)) =
+        (parse_fen(previous_fen), parse_fen(fen))
+    else {
+        return false;
+    };
+
+    // The square the last move captured on is now held by the side that
+    // moved but was held by the side to move before
+    let mover = !current.turn();
+    let capture_square = current
+        .board()
+        .by_color(mover)
+        .into_iter()
+        .find(|sq| previous.board().color_at(*sq) == Some(current.turn()));
+
+    match capture_square {
+        Some(square) => current
+            .legal_moves()
+            .iter()
+            .any(|m| m.is_capture() && m.to() == square),
+        None => false,
+    }
+}
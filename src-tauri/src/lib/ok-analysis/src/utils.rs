@@ -1,7 +1,11 @@
 //! Utility functions and common types
 
 use serde::Serialize;
-use shakmaty::{Bitboard, ByColor, ByRole, Color, File, Rank, Role, Square};
+use shakmaty::fen::Fen;
+use shakmaty::{
+    Bitboard, ByColor, ByRole, CastlingMode, Chess, Color, File, Rank, Role,
+    Square,
+};
 
 /// Error types for analysis
 #[derive(Debug, Clone, Serialize, thiserror::Error)]
@@ -11,6 +15,17 @@ pub enum AnalysisError {
     InvalidInput,
 }
 
+/// Parse a standard chess position from a FEN
+///
+/// Chess960 castling rules are used as they accept the castling rights of
+/// both standard and Chess960 positions.
+pub fn parse_fen(fen: &str) -> Option<Chess> {
+    Fen::from_ascii(fen.as_bytes())
+        .ok()?
+        .into_position(CastlingMode::Chess960)
+        .ok()
+}
+
 /// Convert a Bitboard into a string representation
 pub fn bitboard_to_string(bitboard: Bitboard) -> String {
     let mut result = String::new();
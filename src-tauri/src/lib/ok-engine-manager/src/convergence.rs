@@ -0,0 +1,258 @@
+//! Convergence detection for "until stable" analysis
+//!
+//! Watches the `AnalysisUpdate`s of a search and decides when the result
+//! has settled enough that searching deeper is unlikely to change it.
+
+use ok_parse::uci::{InfoParams, Score};
+use ok_utils::ts_export;
+
+/// Settings for stopping a search once its result stops changing
+#[ts_export]
+pub struct ConvergenceConfig {
+    /// Number of consecutive depths the best move and score must agree for
+    pub stable_depths: u32,
+    /// Largest score change (in centipawns) between depths still considered stable
+    pub score_tolerance: i32,
+    /// Updates below this depth are too noisy to be considered stable
+    pub min_depth: u32,
+    /// Number of consecutive depths a mate score must be reported for
+    pub mate_confirmation_depths: u32,
+    /// Depth at which the engine stops if the result never settles
+    pub max_depth: Option<u32>,
+    /// Time (in milliseconds) after which the engine stops if the result never settles
+    pub max_time_ms: Option<u32>,
+}
+
+impl Default for ConvergenceConfig {
+    fn default() -> Self {
+        Self {
+            stable_depths: 4,
+            score_tolerance: 20,
+            min_depth: 12,
+            mate_confirmation_depths: 2,
+            max_depth: Some(40),
+            max_time_ms: Some(10_000),
+        }
+    }
+}
+
+/// Why an analysis stopped
+#[ts_export]
+pub enum AnalysisStopReason {
+    /// The best move and score stayed within tolerance for enough depths
+    Converged { depth: u32 },
+    /// The same forced mate was reported for enough depths
+    MateFound { depth: u32, mate_in: i32 },
+    /// The engine reached its depth or time limit before the result settled
+    LimitReached,
+}
+
+/// The result of an analysis run until stable
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct StableAnalysis {
+    /// The engine's best move
+    pub best_move: String,
+    /// The engine's ponder move
+    pub ponder: Option<String>,
+    /// The last complete analysis update for the main line
+    pub last_update: Option<InfoParams>,
+    /// Why the analysis stopped
+    pub stop_reason: AnalysisStopReason,
+}
+
+/// The main line's result at one depth
+#[derive(Debug, Clone)]
+struct DepthSample {
+    depth: u32,
+    best_move: String,
+    score: Score,
+}
+
+/// Tracks analysis updates until the search converges
+#[derive(Debug, Clone)]
+pub struct ConvergenceTracker {
+    config: ConvergenceConfig,
+    samples: Vec<DepthSample>,
+    last_update: Option<InfoParams>,
+}
+
+impl ConvergenceTracker {
+    pub fn new(config: ConvergenceConfig) -> Self {
+        Self {
+            config,
+            samples: Vec::new(),
+            last_update: None,
+        }
+    }
+
+    /// The last complete analysis update for the main line
+    pub fn last_update(&self) -> Option<&InfoParams> {
+        self.last_update.as_ref()
+    }
+
+    /// Record an analysis update
+    ///
+    /// Returns the reason to stop once the search has converged.
+    /// Updates for secondary lines, bound scores and updates without a
+    /// principal variation are ignored.
+    pub fn update(
+        &mut self,
+        info: &InfoParams,
+    ) -> Option<AnalysisStopReason> {
+        if info.multipv.is_some_and(|multipv| multipv != 1) {
+            return None;
+        }
+
+        let (Some(depth), Some(score), Some(best_move)) = (
+            info.depth,
+            info.score.clone(),
+            info.pv.as_ref().and_then(|pv| pv.first()).cloned(),
+        ) else {
+            return None;
+        };
+        if let Score::Centipawns { bound: Some(_), .. } = score {
+            return None;
+        }
+
+        let sample = DepthSample {
+            depth,
+            best_move,
+            score,
+        };
+
+        // A later update for the same depth replaces the earlier one
+        match self.samples.last_mut() {
+            Some(last) if last.depth == depth => *last = sample,
+            Some(last) if last.depth > depth => return None,
+            _ => self.samples.push(sample),
+        }
+        self.last_update = Some(info.clone());
+
+        self.stop_reason()
+    }
+
+    /// Check the recorded samples for a reason to stop
+    fn stop_reason(&self) -> Option<AnalysisStopReason> {
+        let latest = self.samples.last()?;
+        let streak = self.stable_streak() as u32;
+
+        if let Score::Mate(mate_in) = latest.score {
+            if streak >= self.config.mate_confirmation_depths.max(1) {
+                return Some(AnalysisStopReason::MateFound {
+                    depth: latest.depth,
+                    mate_in,
+                });
+            }
+            return None;
+        }
+
+        if latest.depth >= self.config.min_depth
+            && streak >= self.config.stable_depths.max(1)
+        {
+            return Some(AnalysisStopReason::Converged {
+                depth: latest.depth,
+            });
+        }
+
+        None
+    }
+
+    /// Count the trailing samples that agree with their predecessor
+    fn stable_streak(&self) -> usize {
+        let agreeing = self
+            .samples
+            .windows(2)
+            .rev()
+            .take_while(|pair| self.agrees(&pair[0], &pair[1]))
+            .count();
+        // The first sample of the streak has no predecessor to agree with
+        agreeing + 1
+    }
+
+    /// Check if two consecutive samples agree on the move and score
+    fn agrees(
+        &self,
+        previous: &DepthSample,
+        current: &DepthSample,
+    ) -> bool {
+        if previous.best_move != current.best_move
+            || current.depth != previous.depth + 1
+        {
+            return false;
+        }
+
+        match (&previous.score, &current.score) {
+            (
+                Score::Centipawns { value: a, .. },
+                Score::Centipawns { value: b, .. },
+            ) => (a - b).abs() <= self.config.score_tolerance,
+            (Score::Mate(a), Score::Mate(b)) => a.signum() == b.signum(),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(
+        depth: u32,
+        best_move: &str,
+        score: Score,
+    ) -> InfoParams {
+        InfoParams {
+            depth: Some(depth),
+            pv: Some(vec![best_move.to_string()]),
+            score: Some(score),
+            ..Default::default()
+        }
+    }
+
+    fn cp(value: i32) -> Score {
+        Score::Centipawns { value, bound: None }
+    }
+
+    fn config() -> ConvergenceConfig {
+        ConvergenceConfig {
+            stable_depths: 3,
+            score_tolerance: 10,
+            min_depth: 5,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn stops_once_move_and_score_are_stable() {
+        let mut tracker = ConvergenceTracker::new(config());
+        assert!(tracker.update(&info(4, "e2e4", cp(30))).is_none());
+        assert!(tracker.update(&info(5, "d2d4", cp(25))).is_none());
+        assert!(tracker.update(&info(6, "d2d4", cp(32))).is_none());
+        assert!(matches!(
+            tracker.update(&info(7, "d2d4", cp(28))),
+            Some(AnalysisStopReason::Converged { depth: 7 })
+        ));
+    }
+
+    #[test]
+    fn score_swings_reset_the_streak() {
+        let mut tracker = ConvergenceTracker::new(config());
+        assert!(tracker.update(&info(5, "e2e4", cp(30))).is_none());
+        assert!(tracker.update(&info(6, "e2e4", cp(35))).is_none());
+        assert!(tracker.update(&info(7, "e2e4", cp(90))).is_none());
+        assert!(tracker.update(&info(8, "e2e4", cp(95))).is_none());
+    }
+
+    #[test]
+    fn confirmed_mate_stops_early() {
+        let mut tracker = ConvergenceTracker::new(config());
+        assert!(tracker.update(&info(1, "h5f7", Score::Mate(1))).is_none());
+        assert!(matches!(
+            tracker.update(&info(2, "h5f7", Score::Mate(1))),
+            Some(AnalysisStopReason::MateFound {
+                depth: 2,
+                mate_in: 1
+            })
+        ));
+    }
+}
@@ -1,6 +1,7 @@
 use std::any::{Any, TypeId};
 use std::collections::HashMap;
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 
 use serde::Serialize;
 use tokio::sync::mpsc;
@@ -50,9 +51,18 @@ pub enum EngineStateInfoEvent {
     /// Update of the engine's analysis
     AnalysisUpdate(ok_parse::uci::InfoParams),
     /// Update of the engine's best move (Best Move, Ponder Move)
+    ///
+    /// This also moves the engine from `Analyzing` back to `Ready`
     BestMove(String, Option<String>),
-    /// Update of the engine's ready state
+    /// The engine answered `isready`
+    ///
+    /// This moves the engine from `Initialized` or `Busy` to `Ready`. An
+    /// answer while analyzing leaves the engine analyzing.
+    ReadyOk,
+    /// Request to change the engine's ready state
     ReadyStateChanged(super::state::engine_state::EngineReadyState),
+    /// The engine's ready state changed
+    ReadyStateTransition(super::state::engine_state::ReadyStateTransition),
     /// Update of the engine's current position
     CurrentPositionChanged(String),
     /// Lifecycle event (start, stop, pause, resume)
@@ -61,11 +71,96 @@ pub enum EngineStateInfoEvent {
     Error(EngineError),
 }
 
+impl BusEvent for EngineStateInfoEvent {
+    fn is_critical(&self) -> bool {
+        matches!(
+            self,
+            EngineStateInfoEvent::BestMove(..)
+                | EngineStateInfoEvent::Error(_)
+                | EngineStateInfoEvent::LifecycleEvent(_)
+                | EngineStateInfoEvent::ReadyStateChanged(_)
+                | EngineStateInfoEvent::ReadyStateTransition(_)
+        )
+    }
+}
+
+/// An event that can be published on the `EventBus`
+pub trait BusEvent: Clone + Send + 'static {
+    /// Check if the event must reach every subscriber
+    ///
+    /// Critical events are delivered even to subscribers that have fallen
+    /// behind, all other events are dropped for them until they catch up.
+    fn is_critical(&self) -> bool {
+        false
+    }
+}
+
+/// Errors returned when receiving from a `Subscription`
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum RecvError {
+    /// The subscriber fell behind and the given number of events were dropped
+    #[error("Subscriber lagged behind, {0} events were dropped")]
+    Lagged(u64),
+    /// The event bus has been dropped
+    #[error("The event bus is closed")]
+    Closed,
+}
+
+/// A message sent to a subscriber
+enum BusMessage<T> {
+    /// A published event
+    Event(T),
+    /// Notification that events were dropped at this point in the stream
+    Lagged(u64),
+}
+
+/// Delivery statistics shared by a subscriber and its sender
+#[derive(Default)]
+struct SubscriberStats {
+    /// Events sent but not yet received
+    queued: AtomicUsize,
+    /// Events dropped since the last lag notification
+    lagged: AtomicU64,
+    /// Events dropped over the subscription's lifetime
+    dropped: AtomicU64,
+}
+
+/// A subscription to events of a specific type
+pub struct Subscription<T> {
+    receiver: mpsc::UnboundedReceiver<BusMessage<T>>,
+    stats: Arc<SubscriberStats>,
+}
+
+impl<T> Subscription<T> {
+    /// Receive the next event
+    ///
+    /// Returns `RecvError::Lagged` in place of any events that were dropped
+    /// because the subscriber fell behind, receiving can continue after it.
+    pub async fn recv(&mut self) -> Result<T, RecvError> {
+        match self.receiver.recv().await {
+            Some(BusMessage::Event(event)) => {
+                self.stats.queued.fetch_sub(1, Ordering::Relaxed);
+                Ok(event)
+            }
+            Some(BusMessage::Lagged(count)) => Err(RecvError::Lagged(count)),
+            None => Err(RecvError::Closed),
+        }
+    }
+
+    /// Get the number of events dropped for this subscriber
+    pub fn dropped_count(&self) -> u64 {
+        self.stats.dropped.load(Ordering::Relaxed)
+    }
+}
+
 /// Trait to handle the sending of typed events
 trait TypedSender: Send + Sync {
     /// Check if the sender's channel is closed
     fn is_closed(&self) -> bool;
 
+    /// Get the number of events dropped for this subscriber
+    fn dropped_count(&self) -> u64;
+
     /// Send a boxed event to the channel
     fn send_boxed(
         &self,
@@ -74,31 +169,58 @@ trait TypedSender: Send + Sync {
 }
 
 /// A typed sender for a specific event type
-struct EventSender<T: Clone + Send + 'static> {
-    sender: mpsc::Sender<T>,
+struct EventSender<T: BusEvent> {
+    sender: mpsc::UnboundedSender<BusMessage<T>>,
+    /// Number of queued events after which non-critical events are dropped
+    buffer_size: usize,
+    stats: Arc<SubscriberStats>,
 }
 
-impl<T: Clone + Send + 'static> TypedSender for EventSender<T> {
+impl<T: BusEvent> TypedSender for EventSender<T> {
     fn is_closed(&self) -> bool {
         self.sender.is_closed()
     }
 
+    fn dropped_count(&self) -> u64 {
+        self.stats.dropped.load(Ordering::Relaxed)
+    }
+
     fn send_boxed(
         &self,
         event: Box<dyn Any + Send>,
     ) -> bool {
         // Try to downcast the boxed event to the expected type
-        if let Ok(typed_event) = event.downcast::<T>() {
-            // Use try_send to avoid blocking
-            let _ = self.sender.try_send(*typed_event);
-            true
-        } else {
-            false
+        let Ok(typed_event) = event.downcast::<T>() else {
+            return false;
+        };
+
+        // Drop the event if the subscriber is too far behind,
+        // the subscriber is told how many were dropped with the next event
+        let queued = self.stats.queued.load(Ordering::Relaxed);
+        if queued >= self.buffer_size && !typed_event.is_critical() {
+            self.stats.lagged.fetch_add(1, Ordering::Relaxed);
+            self.stats.dropped.fetch_add(1, Ordering::Relaxed);
+            return true;
         }
+
+        let lagged = self.stats.lagged.swap(0, Ordering::Relaxed);
+        if lagged > 0 {
+            let _ = self.sender.send(BusMessage::Lagged(lagged));
+        }
+
+        self.stats.queued.fetch_add(1, Ordering::Relaxed);
+        if self.sender.send(BusMessage::Event(*typed_event)).is_err() {
+            self.stats.queued.fetch_sub(1, Ordering::Relaxed);
+        }
+        true
     }
 }
 
 /// Event bus for publishing and subscribing to typed events
+///
+/// Each subscriber buffers a limited number of events. Once a subscriber
+/// falls that far behind, further events are dropped for it (except for
+/// critical events) and it receives a `RecvError::Lagged` notification.
 pub struct EventBus {
     subscribers:
         Mutex<HashMap<TypeId, Vec<Box<dyn TypedSender + Send + Sync>>>>,
@@ -119,7 +241,7 @@ impl EventBus {
     }
 
     /// Broadcast an event to all subscribers of that event type
-    pub fn publish<T: Clone + Send + 'static>(
+    pub fn publish<T: BusEvent>(
         &self,
         event: T,
     ) {
@@ -140,34 +262,25 @@ impl EventBus {
     }
 
     /// Subscribe to events of a specific type
-    pub fn subscribe<T: Clone + Send + 'static>(&self) -> mpsc::Receiver<T> {
-        // Create a channel with a buffer size of 100
-        let (sender, receiver) = mpsc::channel::<T>(100);
-
-        // Create a typed sender
-        let event_sender = EventSender { sender };
-        let boxed_sender: Box<dyn TypedSender + Send + Sync> =
-            Box::new(event_sender);
-
-        // Add the sender to the subscribers map
-        let type_id = TypeId::of::<T>();
-        let mut subscribers = self.subscribers.lock().unwrap();
-
-        subscribers.entry(type_id).or_default().push(boxed_sender);
-
-        receiver
+    pub fn subscribe<T: BusEvent>(&self) -> Subscription<T> {
+        // Buffer up to 100 events per subscriber
+        self.subscribe_with_buffer(100)
     }
 
     /// Subscribe with a custom buffer size
-    pub fn subscribe_with_buffer<T: Clone + Send + 'static>(
+    pub fn subscribe_with_buffer<T: BusEvent>(
         &self,
         buffer_size: usize,
-    ) -> mpsc::Receiver<T> {
-        // Create a channel with the specified buffer size
-        let (sender, receiver) = mpsc::channel::<T>(buffer_size);
+    ) -> Subscription<T> {
+        let (sender, receiver) = mpsc::unbounded_channel::<BusMessage<T>>();
+        let stats = Arc::new(SubscriberStats::default());
 
         // Create a typed sender
-        let event_sender = EventSender { sender };
+        let event_sender = EventSender {
+            sender,
+            buffer_size,
+            stats: stats.clone(),
+        };
         let boxed_sender: Box<dyn TypedSender + Send + Sync> =
             Box::new(event_sender);
 
@@ -177,7 +290,7 @@ impl EventBus {
 
         subscribers.entry(type_id).or_default().push(boxed_sender);
 
-        receiver
+        Subscription { receiver, stats }
     }
 
     /// Check if there are any subscribers for a specific event type
@@ -205,4 +318,120 @@ impl EventBus {
             0
         }
     }
+
+    /// Get the number of dropped events for each subscriber of an event type
+    pub fn dropped_counts<T: Send + 'static>(&self) -> Vec<u64> {
+        let type_id = TypeId::of::<T>();
+        let subscribers = self.subscribers.lock().unwrap();
+
+        subscribers
+            .get(&type_id)
+            .map(|senders| {
+                senders
+                    .iter()
+                    .filter(|sender| !sender.is_closed())
+                    .map(|sender| sender.dropped_count())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum TestEvent {
+        Update(u32),
+        Done,
+    }
+
+    impl BusEvent for TestEvent {
+        fn is_critical(&self) -> bool {
+            matches!(self, TestEvent::Done)
+        }
+    }
+
+    #[tokio::test]
+    async fn full_subscribers_are_told_how_many_events_they_missed() {
+        let bus = EventBus::new();
+        let mut subscription = bus.subscribe_with_buffer::<TestEvent>(2);
+
+        for i in 0..5 {
+            bus.publish(TestEvent::Update(i));
+        }
+        bus.publish(TestEvent::Done);
+
+        assert_eq!(subscription.recv().await, Ok(TestEvent::Update(0)));
+        assert_eq!(subscription.recv().await, Ok(TestEvent::Update(1)));
+        assert_eq!(subscription.recv().await, Err(RecvError::Lagged(3)));
+        assert_eq!(subscription.recv().await, Ok(TestEvent::Done));
+        assert_eq!(subscription.dropped_count(), 3);
+        assert_eq!(bus.dropped_counts::<TestEvent>(), vec![3]);
+    }
+
+    #[tokio::test]
+    async fn slow_consumers_never_miss_critical_events() {
+        let bus = Arc::new(EventBus::new());
+        let mut slow = bus.subscribe_with_buffer::<TestEvent>(4);
+        let mut fast = bus.subscribe_with_buffer::<TestEvent>(1000);
+
+        let publisher = tokio::spawn({
+            let bus = bus.clone();
+            async move {
+                for round in 0..3 {
+                    for i in 0..50 {
+                        bus.publish(TestEvent::Update(round * 50 + i));
+                    }
+                    bus.publish(TestEvent::Done);
+                    tokio::task::yield_now().await;
+                }
+            }
+        });
+
+        let mut slow_done = 0;
+        let mut lagged = 0;
+        while slow_done < 3 {
+            match slow.recv().await {
+                Ok(TestEvent::Done) => slow_done += 1,
+                Ok(TestEvent::Update(_)) => {
+                    tokio::time::sleep(Duration::from_millis(1)).await
+                }
+                Err(RecvError::Lagged(count)) => lagged += count,
+                Err(RecvError::Closed) => break,
+            }
+        }
+        publisher.await.unwrap();
+
+        assert_eq!(slow_done, 3);
+        assert!(lagged > 0);
+        assert_eq!(slow.dropped_count(), lagged);
+
+        let mut fast_events = 0;
+        while let Ok(Ok(_)) =
+            tokio::time::timeout(Duration::from_millis(10), fast.recv()).await
+        {
+            fast_events += 1;
+        }
+        assert_eq!(fast_events, 153);
+        assert_eq!(fast.dropped_count(), 0);
+    }
+
+    #[test]
+    fn engine_terminal_events_are_critical() {
+        assert!(
+            EngineStateInfoEvent::BestMove("e2e4".into(), None).is_critical()
+        );
+        assert!(EngineStateInfoEvent::Error(EngineError::EngineNotRunning(
+            "stopped".into()
+        ))
+        .is_critical());
+        assert!(!EngineStateInfoEvent::CurrentPositionChanged(
+            "startpos".into()
+        )
+        .is_critical());
+    }
 }
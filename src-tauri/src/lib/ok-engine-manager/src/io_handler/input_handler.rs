@@ -1,39 +1,27 @@
+use std::collections::VecDeque;
 use std::sync::Arc;
 
+use log::{debug, error};
 use tokio::io::AsyncWriteExt;
 use tokio::process::ChildStdin;
-use tokio::sync::RwLock;
+use tokio::sync::{Mutex, RwLock};
+use tokio::task::JoinHandle;
 
-use crate::events::EngineStateInfoEvent;
+use crate::events::{EngineStateInfoEvent, EventBus, RecvError};
 use crate::protocol::{EngineCommand, OptionValue, ProtocolComposer};
 use crate::state::engine_state::EngineReadyState;
 use crate::state::EngineState;
 use crate::utils::EngineError;
 
-/// Handles sending commands to the engine
-///
-/// Used to send commands to stdin using the protocol composer.
-/// It abstracts the protocol details from the user and provides
-/// a clean interface for sending various types of commands.
-pub struct InputHandler<S: EngineState> {
+/// The engine's stdin along with the commands waiting to be sent
+struct CommandWriter {
     input_stream: ChildStdin,
-    protocol_composer: Box<dyn ProtocolComposer>,
-    state: Arc<RwLock<S>>,
+    protocol_composer: Arc<dyn ProtocolComposer>,
+    /// Commands issued while the engine could not accept them
+    pending: VecDeque<EngineCommand>,
 }
 
-impl<S: EngineState> InputHandler<S> {
-    pub fn new(
-        input_stream: ChildStdin,
-        protocol_composer: Box<dyn ProtocolComposer>,
-        state: Arc<RwLock<S>>,
-    ) -> Self {
-        Self {
-            input_stream,
-            protocol_composer,
-            state,
-        }
-    }
-
+impl CommandWriter {
     /// Send a raw string to the engine
     ///
     /// This is an internal function for sending the formatted command
@@ -59,13 +47,113 @@ impl<S: EngineState> InputHandler<S> {
         Ok(())
     }
 
+    /// Format a command with the protocol composer and send it
+    async fn write_command(
+        &mut self,
+        command: &EngineCommand,
+    ) -> Result<(), EngineError> {
+        let formatted = self.protocol_composer.compose(command.clone())?;
+        self.send_raw(&formatted).await
+    }
+}
+
+/// Handles sending commands to the engine
+///
+/// Used to send commands to stdin using the protocol composer.
+/// It abstracts the protocol details from the user and provides
+/// a clean interface for sending various types of commands.
+///
+/// Commands are only sent while the engine's ready state accepts them,
+/// otherwise they are queued and sent in order once the engine is ready.
+/// Commands that keep the engine busy (`ucinewgame`, `setoption`) are
+/// followed by an `isready` so the engine reports when it's ready again.
+pub struct InputHandler<S: EngineState> {
+    writer: Arc<Mutex<CommandWriter>>,
+    protocol_composer: Arc<dyn ProtocolComposer>,
+    state: Arc<RwLock<S>>,
+    event_bus: Arc<EventBus>,
+    /// Task sending queued commands as the engine state changes
+    queue_task: JoinHandle<()>,
+}
+
+impl<S: EngineState> InputHandler<S> {
+    pub fn new(
+        input_stream: ChildStdin,
+        protocol_composer: Box<dyn ProtocolComposer>,
+        state: Arc<RwLock<S>>,
+        event_bus: Arc<EventBus>,
+    ) -> Self {
+        let protocol_composer: Arc<dyn ProtocolComposer> =
+            Arc::from(protocol_composer);
+        let writer = Arc::new(Mutex::new(CommandWriter {
+            input_stream,
+            protocol_composer: protocol_composer.clone(),
+            pending: VecDeque::new(),
+        }));
+
+        // Every state change is published on the event bus,
+        // so use it as the signal to retry any queued commands
+        let mut events = event_bus.subscribe::<S::Event>();
+        let queue_task = tokio::spawn({
+            let writer = writer.clone();
+            let state = state.clone();
+            let event_bus = event_bus.clone();
+            async move {
+                while !matches!(events.recv().await, Err(RecvError::Closed)) {
+                    let mut writer = writer.lock().await;
+                    if let Err(e) =
+                        flush_pending(&mut writer, &state, &event_bus).await
+                    {
+                        error!("Failed to send queued command: {:?}", e);
+                    }
+                }
+            }
+        });
+
+        Self {
+            writer,
+            protocol_composer,
+            state,
+            event_bus,
+            queue_task,
+        }
+    }
+
     /// Send a command to the engine
+    ///
+    /// The command is queued if the engine can't accept it yet
     pub async fn send_command(
         &mut self,
         command: EngineCommand,
     ) -> Result<(), EngineError> {
-        let formatted = self.protocol_composer.compose(command)?;
-        self.send_raw(&formatted).await
+        let mut writer = self.writer.lock().await;
+
+        if command.bypasses_queue() {
+            // Stopping cancels any analysis that hasn't started yet
+            if matches!(command, EngineCommand::StopAnalysis) {
+                writer.pending.retain(|queued| {
+                    !matches!(queued, EngineCommand::StartAnalysis { .. })
+                });
+            }
+            return send_now(
+                &mut writer,
+                &self.state,
+                &self.event_bus,
+                command,
+            )
+            .await;
+        }
+
+        writer.pending.push_back(command);
+        flush_pending(&mut writer, &self.state, &self.event_bus).await?;
+
+        if !writer.pending.is_empty() {
+            debug!(
+                "{} command(s) queued until the engine is ready",
+                writer.pending.len()
+            );
+        }
+        Ok(())
     }
 
     /// Send a raw command string to the engine
@@ -75,10 +163,24 @@ impl<S: EngineState> InputHandler<S> {
         &mut self,
         command: &str,
     ) -> Result<(), EngineError> {
-        let formatted = self
-            .protocol_composer
-            .compose(EngineCommand::Raw(command.to_string()))?;
-        self.send_raw(&formatted).await
+        self.send_command(EngineCommand::Raw(command.to_string()))
+            .await
+    }
+
+    /// Move the engine to another ready state
+    ///
+    /// Used for transitions that aren't caused by a command or engine
+    /// output, like the process being started.
+    pub async fn set_ready_state(
+        &mut self,
+        ready_state: EngineReadyState,
+    ) -> Result<(), EngineError> {
+        transition(&self.state, &self.event_bus, ready_state).await
+    }
+
+    /// Get the number of commands waiting for the engine to be ready
+    pub async fn pending_commands(&self) -> usize {
+        self.writer.lock().await.pending.len()
     }
 
     /// Check if the engine is ready
@@ -128,32 +230,15 @@ impl<S: EngineState> InputHandler<S> {
         depth: Option<u32>,
         movetime: Option<u32>,
         multipv: Option<u32>,
-    ) -> Result<(), EngineError>
-    where
-        S: EngineState<Update = EngineStateInfoEvent>,
-    {
-        let cmd_res = self
-            .send_command(EngineCommand::StartAnalysis {
-                depth,
-                movetime,
-                nodes: None,
-                multipv,
-                searchmoves: None,
-            })
-            .await;
-
-        match cmd_res {
-            Ok(_) => {
-                // Update the state
-                let state_update =
-                    <S as EngineState>::Update::ReadyStateChanged(
-                        EngineReadyState::Analyzing,
-                    );
-                self.state.write().await.apply_update(state_update)?;
-                Ok(())
-            }
-            Err(e) => Err(e),
-        }
+    ) -> Result<(), EngineError> {
+        self.send_command(EngineCommand::StartAnalysis {
+            depth,
+            movetime,
+            nodes: None,
+            multipv,
+            searchmoves: None,
+        })
+        .await
     }
 
     /// Start advanced analysis with additional parameters
@@ -164,26 +249,18 @@ impl<S: EngineState> InputHandler<S> {
         nodes: Option<u64>,
         multipv: Option<u32>,
         searchmoves: Option<&[&str]>,
-    ) -> Result<(), EngineError>
-    where
-        S: EngineState<Update = EngineStateInfoEvent>,
-    {
+    ) -> Result<(), EngineError> {
         let searchmoves_owned =
             searchmoves.map(|m| m.iter().map(|&s| s.to_string()).collect());
 
-        let cmd_res = self
-            .send_command(EngineCommand::StartAnalysis {
-                depth,
-                movetime,
-                nodes,
-                multipv,
-                searchmoves: searchmoves_owned,
-            })
-            .await;
-
-        cmd_res?;
-
-        Ok(())
+        self.send_command(EngineCommand::StartAnalysis {
+            depth,
+            movetime,
+            nodes,
+            multipv,
+            searchmoves: searchmoves_owned,
+        })
+        .await
     }
 
     /// Stop ongoing analysis
@@ -222,3 +299,63 @@ impl<S: EngineState> InputHandler<S> {
         self.protocol_composer.supports_feature(feature)
     }
 }
+
+impl<S: EngineState> Drop for InputHandler<S> {
+    fn drop(&mut self) {
+        self.queue_task.abort();
+    }
+}
+
+/// Apply a ready state transition and publish the resulting event
+async fn transition<S: EngineState>(
+    state: &RwLock<S>,
+    event_bus: &EventBus,
+    ready_state: EngineReadyState,
+) -> Result<(), EngineError> {
+    let event = state
+        .write()
+        .await
+        .apply_update(S::ready_state_update(ready_state))?;
+    event_bus.publish(event);
+    Ok(())
+}
+
+/// Send a command and apply the state transition it causes
+async fn send_now<S: EngineState>(
+    writer: &mut CommandWriter,
+    state: &RwLock<S>,
+    event_bus: &EventBus,
+    command: EngineCommand,
+) -> Result<(), EngineError> {
+    writer.write_command(&command).await?;
+
+    if let Some(ready_state) = command.resulting_state() {
+        transition(state, event_bus, ready_state).await?;
+    }
+
+    // The engine answers `isready` once it has finished the command
+    if command.requires_sync() {
+        writer.write_command(&EngineCommand::IsReady).await?;
+    }
+
+    Ok(())
+}
+
+/// Send queued commands, in order, for as long as the engine accepts them
+async fn flush_pending<S: EngineState>(
+    writer: &mut CommandWriter,
+    state: &RwLock<S>,
+    event_bus: &EventBus,
+) -> Result<(), EngineError> {
+    loop {
+        let ready_state = state.read().await.ready_state();
+        match writer.pending.front() {
+            Some(command) if command.allowed_in(&ready_state) => {}
+            _ => return Ok(()),
+        }
+
+        if let Some(command) = writer.pending.pop_front() {
+            send_now(writer, state, event_bus, command).await?;
+        }
+    }
+}
@@ -1,8 +1,11 @@
 pub mod builder;
+pub mod convergence;
 pub mod events;
 pub mod io_handler;
 pub mod manager;
 pub mod process;
 pub mod protocol;
 pub mod state;
+pub mod test_suite;
+pub mod time_management;
 pub mod utils;
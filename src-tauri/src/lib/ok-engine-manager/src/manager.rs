@@ -1,15 +1,24 @@
-use log::{error, info};
+use log::{error, info, warn};
 use ok_utils::ts_export;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::process::Command;
 
-use ok_parse::uci::OptionDefinition;
+use ok_parse::epd::EpdRecord;
+use ok_parse::uci::{InfoParams, OptionDefinition};
 
+use crate::convergence::{
+    AnalysisStopReason, ConvergenceConfig, ConvergenceTracker, StableAnalysis,
+};
 use crate::events::EventEmitter;
+use crate::io_handler::input_handler::InputHandler;
+use crate::test_suite::SuitePositionResult;
+use crate::time_management::SearchLimits;
+use crate::utils::{validate_option_value, EngineManagerError};
 
-use super::events::{EngineStateInfoEvent, EventBus};
+use super::events::{EngineStateInfoEvent, EventBus, RecvError, Subscription};
 use super::{
     process::EngineProcess,
     protocol::{
@@ -19,6 +28,21 @@ use super::{
     state::engine_state::{EngineReadyState, EngineStateInfo},
 };
 
+/// How long a newly started engine has to finish initializing
+const ENGINE_STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long an engine that timed out has to answer `stop` before it's restarted
+const STOP_GRACE_PERIOD: Duration = Duration::from_secs(2);
+
+/// The UCI option engines provide when they can play Chess960
+const CHESS960_OPTION: &str = "UCI_Chess960";
+
+/// The UCI option engines provide when they can play other variants
+const VARIANT_OPTION: &str = "UCI_Variant";
+
+/// The UCI name of standard chess, which every engine plays
+const STANDARD_VARIANT: &str = "chess";
+
 /// Time management strategies for analysis
 ///
 /// For a single position analysis, total and fixed result in the same behavior.
@@ -36,6 +60,21 @@ pub enum TimeStrategy {
     FixedDepth {
         depth: u32,
     },
+    /// Search each move until the best move and score stop changing
+    UntilStable {
+        convergence: ConvergenceConfig,
+    },
+    /// Fixed node count per move, for results reproducible across machines
+    FixedNodes {
+        nodes: u64,
+    },
+    /// Total time budget, spending more time where the game is critical
+    ///
+    /// Positions after an evaluation swing or PV change get more time,
+    /// book moves and forced recaptures get less.
+    Adaptive {
+        total_seconds: u64,
+    },
 }
 
 impl Default for TimeStrategy {
@@ -77,6 +116,10 @@ where
 {
     engines: HashMap<String, EngineProcess<EngineStateInfo>>,
     engine_names: Vec<String>,
+    /// Executable paths, used to restart engines
+    engine_paths: HashMap<String, String>,
+    /// Options set on each engine, applied again after a restart
+    engine_options: HashMap<String, Vec<(String, OptionValue)>>,
     event_emitter: Option<Arc<Emitter>>,
 }
 
@@ -97,6 +140,8 @@ where
         Self {
             engines: HashMap::new(),
             engine_names: Vec::new(),
+            engine_paths: HashMap::new(),
+            engine_options: HashMap::new(),
             event_emitter: None,
         }
     }
@@ -106,6 +151,8 @@ where
         Self {
             engines: HashMap::new(),
             engine_names: Vec::new(),
+            engine_paths: HashMap::new(),
+            engine_options: HashMap::new(),
             event_emitter: Some(event_emitter),
         }
     }
@@ -117,7 +164,7 @@ where
         &mut self,
         name: &str,
         path: &str,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    ) -> Result<(), EngineManagerError> {
         // TODO: Improve check if the exact engine is already loaded
         // If it is, return an error
         if self.engines.contains_key(name) {
@@ -139,10 +186,20 @@ where
 
         engine
             .spawn(Box::new(UciProtocolParser), Box::new(UciProtocolComposer))
-            .await?;
+            .await
+            .map_err(|e| EngineManagerError::IoError {
+                engine_name: name.to_string(),
+                message: e.to_string(),
+            })?;
 
         // Wait for the engine to initialize
-        match engine.wait_until_ready(EngineReadyState::Initialized).await {
+        match engine
+            .wait_until_ready_within(
+                EngineReadyState::Initialized,
+                ENGINE_STARTUP_TIMEOUT,
+            )
+            .await
+        {
             Ok(_) => {
                 info!("Engine has been initialized");
 
@@ -160,7 +217,10 @@ where
             }
             Err(e) => {
                 error!("Engine initialization failed: {:?}", e);
-                return Err(Box::new(e));
+                if let Err(kill_error) = engine.kill(None).await {
+                    error!("Failed to kill engine: {:?}", kill_error);
+                }
+                return Err(EngineManagerError::from_engine_error(name, e));
             }
         }
 
@@ -180,6 +240,7 @@ where
 
         self.engines.insert(name.to_string(), engine);
         self.engine_names.push(name.to_string());
+        self.engine_paths.insert(name.to_string(), path.to_string());
 
         Ok(())
     }
@@ -211,13 +272,21 @@ where
                 // }
 
                 // For now just emit every event
-                let event = rx.recv().await;
-                if let Some(event) = event {
-                    if let Ok(payload) =
-                        serde_json::to_string(&(engine_name.clone(), event))
-                    {
-                        event_emitter.emit_event("engine-output", payload);
+                let event = match rx.recv().await {
+                    Ok(event) => event,
+                    Err(RecvError::Lagged(count)) => {
+                        warn!(
+                            "Event emitter for engine {} dropped {} events",
+                            engine_name, count
+                        );
+                        continue;
                     }
+                    Err(RecvError::Closed) => break,
+                };
+                if let Ok(payload) =
+                    serde_json::to_string(&(engine_name.clone(), event))
+                {
+                    event_emitter.emit_event("engine-output", payload);
                 }
             }
         });
@@ -226,25 +295,22 @@ where
     pub async fn remove_engine(
         &mut self,
         name: &str,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    ) -> Result<(), EngineManagerError> {
         let engine = self.engines.remove(name);
+        // Clean up the engine state
+        self.engine_names.retain(|x| x != name);
+        self.engine_paths.remove(name);
+        self.engine_options.remove(name);
+
         if let Some(mut engine) = engine {
             let kill_result = engine.kill(None).await;
             match kill_result {
                 Ok(_) => {
                     info!("Engine killed: {}", name);
-                    // Clean up the engine state
-                    let _ = self.engines.remove(name);
-                    let _ = self.engine_names.remove(
-                        self.engine_names
-                            .iter()
-                            .position(|x| x == name)
-                            .unwrap(),
-                    );
                 }
                 Err(e) => {
                     error!("Failed to kill engine: {:?}", e);
-                    return Err(Box::new(e));
+                    return Err(EngineManagerError::from_engine_error(name, e));
                 }
             }
         }
@@ -293,28 +359,140 @@ where
     }
 
     /// Set an option or capability for a specific engine
+    ///
+    /// The option must be one the engine advertised, and the value is
+    /// checked against the option's type, range and choices before sending.
     pub async fn set_engine_option(
         &mut self,
         name: &str,
         option: &str,
         value: OptionValue,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let engine = self.engines.get_mut(name).unwrap();
-        let set_option_result = match engine.input_handler() {
-            Ok(handler) => handler.set_option(option, value).await,
-            Err(e) => {
-                error!("Failed to get input handler: {:?}", e);
-                return Err(Box::new(e));
-            }
-        };
+    ) -> Result<(), EngineManagerError> {
+        let engine = self.engine(name)?;
+        let definition = engine
+            .query_state(|state| {
+                state
+                    .capabilities
+                    .values()
+                    .find(|cap| cap.name.eq_ignore_ascii_case(option))
+                    .cloned()
+            })
+            .await
+            .ok_or_else(|| EngineManagerError::InvalidOption {
+                engine_name: name.to_string(),
+                option: option.to_string(),
+                reason: "The engine does not provide this option".to_string(),
+            })?;
+
+        let value =
+            validate_option_value(&definition, value).map_err(|reason| {
+                EngineManagerError::InvalidOption {
+                    engine_name: name.to_string(),
+                    option: option.to_string(),
+                    reason,
+                }
+            })?;
 
-        match set_option_result {
-            Ok(_) => Ok(()),
-            Err(e) => {
+        self.input_handler(name)?
+            .set_option(&definition.name, value.clone())
+            .await
+            .map_err(|e| {
                 error!("Failed to set option: {:?}", e);
-                Err(Box::new(e))
+                EngineManagerError::from_engine_error(name, e)
+            })?;
+
+        // Remember the option so it can be applied again after a restart
+        let options = self.engine_options.entry(name.to_string()).or_default();
+        options.retain(|(option, _)| option != &definition.name);
+        options.push((definition.name, value));
+
+        Ok(())
+    }
+
+    /// Turn Chess960 castling on or off for a specific engine
+    ///
+    /// While it's on the engine reads and writes castling as the king
+    /// capturing its own rook. Engines that don't provide the
+    /// `UCI_Chess960` option can't play Chess960, so turning it on for
+    /// them fails and turning it off does nothing.
+    pub async fn set_engine_chess960(
+        &mut self,
+        name: &str,
+        enabled: bool,
+    ) -> Result<(), EngineManagerError> {
+        if !self.has_option(name, CHESS960_OPTION).await? {
+            if !enabled {
+                return Ok(());
+            }
+            return Err(EngineManagerError::InvalidOption {
+                engine_name: name.to_string(),
+                option: CHESS960_OPTION.to_string(),
+                reason: "The engine does not support Chess960".to_string(),
+            });
+        }
+
+        self.set_engine_option(
+            name,
+            CHESS960_OPTION,
+            OptionValue::Boolean(enabled),
+        )
+        .await
+    }
+
+    /// Set the variant a specific engine plays, by its UCI name like
+    /// "crazyhouse" or "3check"
+    ///
+    /// Variant engines like Fairy-Stockfish provide the `UCI_Variant`
+    /// option. Engines without it only play standard chess ("chess"), so
+    /// setting any other variant for them fails.
+    pub async fn set_engine_variant(
+        &mut self,
+        name: &str,
+        variant: &str,
+    ) -> Result<(), EngineManagerError> {
+        if !self.has_option(name, VARIANT_OPTION).await? {
+            if variant == STANDARD_VARIANT {
+                return Ok(());
             }
+            return Err(EngineManagerError::InvalidOption {
+                engine_name: name.to_string(),
+                option: VARIANT_OPTION.to_string(),
+                reason: format!("The engine does not support {}", variant),
+            });
         }
+
+        self.set_engine_option(
+            name,
+            VARIANT_OPTION,
+            OptionValue::String(variant.to_string()),
+        )
+        .await
+    }
+
+    /// Turn Chess960 castling on or off for all engines
+    pub async fn set_chess960(
+        &mut self,
+        enabled: bool,
+    ) -> Result<(), EngineManagerError> {
+        let engine_names: Vec<_> = self.engine_names.clone();
+        for engine_name in engine_names.iter() {
+            self.set_engine_chess960(engine_name, enabled).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Set the variant all engines play, by its UCI name
+    pub async fn set_variant(
+        &mut self,
+        variant: &str,
+    ) -> Result<(), EngineManagerError> {
+        let engine_names: Vec<_> = self.engine_names.clone();
+        for engine_name in engine_names.iter() {
+            self.set_engine_variant(engine_name, variant).await?;
+        }
+
+        Ok(())
     }
 
     /// Set the position for all engines
@@ -322,15 +500,11 @@ where
         &mut self,
         fen: Option<&str>,
         moves: Option<&[&str]>,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    ) -> Result<(), EngineManagerError> {
         info!("Setting position for engines");
         let engine_names: Vec<_> = self.engine_names.clone();
         for engine_name in engine_names.iter() {
-            let result =
-                self.set_engine_position(engine_name, fen, moves).await;
-            if result.is_err() {
-                return Err(result.unwrap_err());
-            }
+            self.set_engine_position(engine_name, fen, moves).await?;
         }
 
         Ok(())
@@ -342,16 +516,17 @@ where
         depth: Option<u32>,
         time_ms: Option<u32>,
         multipv: Option<u32>,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    ) -> Result<(), EngineManagerError> {
         info!("Starting analysis for engines");
         let engine_names: Vec<_> = self.engine_names.clone();
+        let limits = SearchLimits {
+            depth,
+            movetime_ms: time_ms,
+            nodes: None,
+        };
         for engine_name in engine_names.iter() {
-            let result = self
-                .start_engine_analysis(engine_name, depth, time_ms, multipv)
-                .await;
-            if result.is_err() {
-                return Err(result.unwrap_err());
-            }
+            self.start_engine_analysis(engine_name, limits, multipv)
+                .await?;
         }
 
         Ok(())
@@ -362,35 +537,169 @@ where
         &mut self,
         engine_name: &str,
         position_fen: &str,
-        depth: Option<u32>,
-        time_ms: Option<u32>,
+        limits: SearchLimits,
         multipv: Option<u32>,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    ) -> Result<(), EngineManagerError> {
         // Ensure the engine exists
-        let engine = self.engines.get_mut(engine_name);
-        if engine.is_none() {
-            return Err("Engine not found".into());
-        }
+        self.engine(engine_name)?;
 
         // Set the position
         self.set_engine_position(engine_name, Some(position_fen), None)
             .await?;
 
         // Start analysis
-        self.start_engine_analysis(engine_name, depth, time_ms, multipv)
+        self.start_engine_analysis(engine_name, limits, multipv)
             .await
     }
 
+    /// Analyze a position and wait for the engine's result
+    ///
+    /// Returns the last main line analysis update before the best move.
+    /// If the engine hasn't finished within `timeout` it's recovered
+    /// (stopped, or restarted if it doesn't respond) and a timeout error
+    /// is returned.
+    pub async fn analyze_position_for(
+        &mut self,
+        engine_name: &str,
+        position_fen: &str,
+        limits: SearchLimits,
+        timeout: Duration,
+    ) -> Result<Option<InfoParams>, EngineManagerError> {
+        let mut last_update = None;
+        self.run_search(engine_name, position_fen, limits, timeout, |info| {
+            if info.score.is_some() && info.multipv.unwrap_or(1) == 1 {
+                last_update = Some(info.clone());
+            }
+            false
+        })
+        .await?;
+
+        Ok(last_update)
+    }
+
+    /// Run an engine on one position of a test suite, searching it for a
+    /// fixed time
+    ///
+    /// A position the engine fails to search, like one it times out on,
+    /// counts as unsolved with the error kept on the result.
+    pub async fn run_test_position(
+        &mut self,
+        engine_name: &str,
+        record: &EpdRecord,
+        movetime_ms: u32,
+        timeout: Duration,
+    ) -> Result<SuitePositionResult, EngineManagerError> {
+        // Ensure the engine exists
+        self.engine(engine_name)?;
+
+        let limits = SearchLimits {
+            depth: None,
+            movetime_ms: Some(movetime_ms),
+            nodes: None,
+        };
+        let mut last_update = None;
+        let result = self
+            .run_search(engine_name, &record.fen(), limits, timeout, |info| {
+                if info.score.is_some() && info.multipv.unwrap_or(1) == 1 {
+                    last_update = Some(info.clone());
+                }
+                false
+            })
+            .await;
+
+        Ok(match result {
+            Ok((best_move, _)) => SuitePositionResult::check(
+                record,
+                &best_move,
+                last_update.as_ref(),
+            ),
+            Err(e) => {
+                SuitePositionResult::unchecked(record, Some(e.to_string()))
+            }
+        })
+    }
+
+    /// Analyze a position until the engine's result settles
+    ///
+    /// The search is stopped once the best move and score have been stable
+    /// for the configured number of depths, or a mate has been confirmed.
+    /// Otherwise the engine searches until the configured limits are reached.
+    /// The engine is recovered if it hasn't finished within `timeout`.
+    pub async fn analyze_until_stable(
+        &mut self,
+        engine_name: &str,
+        position_fen: &str,
+        config: &ConvergenceConfig,
+        timeout: Duration,
+    ) -> Result<StableAnalysis, EngineManagerError> {
+        let limits = SearchLimits {
+            depth: config.max_depth,
+            movetime_ms: config.max_time_ms,
+            nodes: None,
+        };
+        let mut tracker = ConvergenceTracker::new(config.clone());
+        let mut stop_reason = None;
+
+        let (best_move, ponder) = self
+            .run_search(engine_name, position_fen, limits, timeout, |info| {
+                if stop_reason.is_some() {
+                    return false;
+                }
+                stop_reason = tracker.update(info);
+                if let Some(reason) = &stop_reason {
+                    info!(
+                        "Analysis for engine {} converged: {:?}",
+                        engine_name, reason
+                    );
+                }
+                stop_reason.is_some()
+            })
+            .await?;
+
+        Ok(StableAnalysis {
+            best_move,
+            ponder,
+            last_update: tracker.last_update().cloned(),
+            stop_reason: stop_reason
+                .unwrap_or(AnalysisStopReason::LimitReached),
+        })
+    }
+
+    /// Restart an engine, for example after it stopped responding
+    ///
+    /// The engine is started again from the same path and any options
+    /// set through the manager are applied again.
+    pub async fn restart_engine(
+        &mut self,
+        name: &str,
+    ) -> Result<(), EngineManagerError> {
+        let path = self
+            .engine_paths
+            .get(name)
+            .cloned()
+            .ok_or_else(|| EngineManagerError::unknown_engine(name))?;
+        let options =
+            self.engine_options.get(name).cloned().unwrap_or_default();
+
+        warn!("Restarting engine: {}", name);
+        if let Err(e) = self.remove_engine(name).await {
+            // The process is dropped anyway, which kills it
+            error!("Failed to stop engine before restart: {:?}", e);
+        }
+        self.add_uci_engine(name, &path).await?;
+
+        for (option, value) in options {
+            self.set_engine_option(name, &option, value).await?;
+        }
+
+        Ok(())
+    }
+
     /// Stop analysis for all engines
-    pub async fn stop_analysis(
-        &mut self
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    pub async fn stop_analysis(&mut self) -> Result<(), EngineManagerError> {
         let engine_names: Vec<_> = self.engine_names.clone();
         for engine_name in engine_names.iter() {
-            let result = self.stop_engine_analysis(engine_name).await;
-            if result.is_err() {
-                return Err(result.unwrap_err());
-            }
+            self.stop_engine_analysis(engine_name).await?;
         }
 
         Ok(())
@@ -402,86 +711,263 @@ impl<Emitter> EngineManager<Emitter>
 where
     Emitter: EventEmitter + Send + Sync,
 {
+    /// Look up an engine by name
+    fn engine(
+        &self,
+        name: &str,
+    ) -> Result<&EngineProcess<EngineStateInfo>, EngineManagerError> {
+        self.engines
+            .get(name)
+            .ok_or_else(|| EngineManagerError::unknown_engine(name))
+    }
+
+    /// Whether an engine provides an option
+    async fn has_option(
+        &self,
+        name: &str,
+        option: &str,
+    ) -> Result<bool, EngineManagerError> {
+        Ok(self
+            .engine(name)?
+            .query_state(|state| {
+                state
+                    .capabilities
+                    .values()
+                    .any(|cap| cap.name.eq_ignore_ascii_case(option))
+            })
+            .await)
+    }
+
+    /// Look up an engine's input handler by engine name
+    fn input_handler(
+        &mut self,
+        name: &str,
+    ) -> Result<&mut InputHandler<EngineStateInfo>, EngineManagerError> {
+        let engine = self
+            .engines
+            .get_mut(name)
+            .ok_or_else(|| EngineManagerError::unknown_engine(name))?;
+        engine.input_handler().map_err(|e| {
+            error!("Failed to get input handler: {:?}", e);
+            EngineManagerError::from_engine_error(name, e)
+        })
+    }
+
     /// Set the position for a specific engine
     async fn set_engine_position(
         &mut self,
         name: &str,
         fen: Option<&str>,
         moves: Option<&[&str]>,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let engine = self.engines.get_mut(name).unwrap();
-        // Set the position to a FEN string
-        let set_pos_result = match engine.input_handler() {
-            Ok(handler) => handler.set_position(fen, moves).await,
-            Err(e) => {
-                error!("Failed to get input handler: {:?}", e);
-                return Err(Box::new(e));
-            }
-        };
+    ) -> Result<(), EngineManagerError> {
+        // A new position makes any running search obsolete, stop it so
+        // the position isn't queued behind it
+        let analyzing = self
+            .engine(name)?
+            .query_state(|state| {
+                state.ready_state == EngineReadyState::Analyzing
+            })
+            .await;
+        if analyzing {
+            self.stop_engine_analysis(name).await?;
+        }
 
-        match set_pos_result {
-            Ok(_) => Ok(()),
-            Err(e) => {
+        // Set the position to a FEN string
+        self.input_handler(name)?
+            .set_position(fen, moves)
+            .await
+            .map_err(|e| {
                 error!("Failed to set position: {:?}", e);
-                Err(Box::new(e))
-            }
-        }
+                EngineManagerError::from_engine_error(name, e)
+            })
     }
 
     /// Start analysis for a specific engine
     async fn start_engine_analysis(
         &mut self,
         name: &str,
-        depth: Option<u32>,
-        time_ms: Option<u32>,
+        limits: SearchLimits,
         multipv: Option<u32>,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let engine = self.engines.get_mut(name).unwrap();
-        let start_analysis_result = match engine.input_handler() {
-            Ok(handler) => {
-                handler.start_analysis(depth, time_ms, multipv).await
-            }
-            Err(e) => {
-                error!("Failed to get input handler: {:?}", e);
-                return Err(Box::new(e));
+    ) -> Result<(), EngineManagerError> {
+        self.input_handler(name)?
+            .start_advanced_analysis(
+                limits.depth,
+                limits.movetime_ms,
+                limits.nodes,
+                multipv,
+                None,
+            )
+            .await
+            .map_err(|e| {
+                error!("Failed to start analysis: {:?}", e);
+                EngineManagerError::from_engine_error(name, e)
+            })?;
+
+        info!("Started analysis for engine: {}", name);
+        Ok(())
+    }
+
+    /// Start a search and wait for its best move
+    ///
+    /// `on_update` is called with every analysis update of the search and
+    /// can return `true` to stop the search early. If the search hasn't
+    /// finished within `timeout` the engine is recovered and a timeout
+    /// error is returned.
+    async fn run_search<F>(
+        &mut self,
+        engine_name: &str,
+        position_fen: &str,
+        limits: SearchLimits,
+        timeout: Duration,
+        mut on_update: F,
+    ) -> Result<(String, Option<String>), EngineManagerError>
+    where
+        F: FnMut(&InfoParams) -> bool,
+    {
+        // Subscribe before starting so no updates are missed
+        let mut events = self.subscribe(engine_name)?;
+
+        let search = async {
+            self.quick_start_position_analysis_for(
+                engine_name,
+                position_fen,
+                limits,
+                None,
+            )
+            .await?;
+
+            // Output from a search that was running before ours is ignored
+            let mut started = false;
+            let mut stopping = false;
+
+            loop {
+                let event = match events.recv().await {
+                    Ok(event) => event,
+                    // Updates may be dropped, but the best move never is
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                };
+                match event {
+                    EngineStateInfoEvent::ReadyStateTransition(transition)
+                        if transition.to == EngineReadyState::Analyzing =>
+                    {
+                        started = true;
+                    }
+                    EngineStateInfoEvent::AnalysisUpdate(_)
+                    | EngineStateInfoEvent::BestMove(..)
+                        if !started => {}
+                    EngineStateInfoEvent::AnalysisUpdate(info)
+                        if on_update(&info) && !stopping =>
+                    {
+                        stopping = true;
+                        self.stop_engine_analysis(engine_name).await?;
+                    }
+                    EngineStateInfoEvent::BestMove(best_move, ponder) => {
+                        return Ok((best_move, ponder));
+                    }
+                    EngineStateInfoEvent::Error(e) => {
+                        return Err(EngineManagerError::from_engine_error(
+                            engine_name,
+                            e,
+                        ));
+                    }
+                    _ => {}
+                }
             }
+
+            Err(EngineManagerError::IoError {
+                engine_name: engine_name.to_string(),
+                message: "Engine events ended before a best move was received"
+                    .to_string(),
+            })
         };
 
-        match start_analysis_result {
-            Ok(_) => {
-                info!("Started analysis for engine: {}", name);
-                Ok(())
-            }
-            Err(e) => {
-                error!("Failed to start analysis: {:?}", e);
-                Err(Box::new(e))
+        match tokio::time::timeout(timeout, search).await {
+            Ok(result) => result,
+            Err(_) => {
+                warn!(
+                    "Engine {} did not finish its search within {}ms",
+                    engine_name,
+                    timeout.as_millis()
+                );
+                if let Err(e) = self.recover_engine(engine_name).await {
+                    error!("Failed to recover engine {}: {}", engine_name, e);
+                }
+                Err(EngineManagerError::Timeout {
+                    engine_name: engine_name.to_string(),
+                    message: format!(
+                        "No best move within {}ms",
+                        timeout.as_millis()
+                    ),
+                })
             }
         }
     }
 
-    /// Stop analysis for a specific engine
-    async fn stop_engine_analysis(
+    /// Bring an engine that stopped responding back to a usable state
+    ///
+    /// The engine is sent `stop` and given a short grace period to answer
+    /// with a best move, otherwise it is restarted.
+    async fn recover_engine(
         &mut self,
         name: &str,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let engine = self.engines.get_mut(name).unwrap();
-        let stop_analysis_result = match engine.input_handler() {
-            Ok(handler) => handler.stop_analysis().await,
-            Err(e) => {
-                error!("Failed to get input handler: {:?}", e);
-                return Err(Box::new(e));
+    ) -> Result<(), EngineManagerError> {
+        let mut events = self.subscribe(name)?;
+
+        let stopped = match self.stop_engine_analysis(name).await {
+            Ok(()) => {
+                let best_move = async {
+                    loop {
+                        match events.recv().await {
+                            Ok(EngineStateInfoEvent::BestMove(..)) => {
+                                return true
+                            }
+                            Err(RecvError::Closed) => return false,
+                            _ => {}
+                        }
+                    }
+                };
+                tokio::time::timeout(STOP_GRACE_PERIOD, best_move)
+                    .await
+                    .unwrap_or(false)
             }
+            Err(_) => false,
         };
 
-        match stop_analysis_result {
-            Ok(_) => {
-                info!("Stopped analysis for engine: {}", name);
-                Ok(())
-            }
-            Err(e) => {
-                error!("Failed to stop analysis: {:?}", e);
-                Err(Box::new(e))
-            }
+        if stopped {
+            info!("Engine {} stopped after timing out", name);
+            Ok(())
+        } else {
+            self.restart_engine(name).await
         }
     }
+
+    /// Subscribe to an engine's events
+    fn subscribe(
+        &self,
+        name: &str,
+    ) -> Result<Subscription<EngineStateInfoEvent>, EngineManagerError> {
+        Ok(self
+            .engine(name)?
+            .event_bus()
+            .map_err(|e| EngineManagerError::from_engine_error(name, e))?
+            .subscribe::<EngineStateInfoEvent>())
+    }
+
+    /// Stop analysis for a specific engine
+    async fn stop_engine_analysis(
+        &mut self,
+        name: &str,
+    ) -> Result<(), EngineManagerError> {
+        self.input_handler(name)?
+            .stop_analysis()
+            .await
+            .map_err(|e| {
+                error!("Failed to stop analysis: {:?}", e);
+                EngineManagerError::from_engine_error(name, e)
+            })?;
+
+        info!("Stopped analysis for engine: {}", name);
+        Ok(())
+    }
 }
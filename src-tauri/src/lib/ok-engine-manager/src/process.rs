@@ -1,14 +1,15 @@
 use std::process::Stdio;
 use std::sync::Arc;
+use std::time::Duration;
 
-use log::{debug, error};
+use log::{debug, error, warn};
 use tokio::io::BufReader;
 use tokio::process::{Child, Command};
 use tokio::signal::unix::Signal;
 use tokio::sync::{broadcast, RwLock, RwLockReadGuard};
 
 use super::builder::EngineProcessBuilder;
-use super::events::{EngineStateInfoEvent, EventBus};
+use super::events::{EngineStateInfoEvent, EventBus, RecvError};
 use super::io_handler::input_handler::InputHandler;
 use super::io_handler::output_handler::OutputHandler;
 use super::protocol::{ProtocolComposerType, ProtocolParserType};
@@ -107,6 +108,8 @@ where
                 self.state.clone(),
                 self.shutdown_tx.subscribe(),
             );
+        let event_bus = output_handler.event_bus().clone();
+
         // Start the output handler
         match output_handler.start().await {
             Ok(()) => {
@@ -124,8 +127,19 @@ where
         // Get the initial command before moving the protocol to the input handler
         let initial_command = protocol_composer.initial_command();
 
-        let mut input_handler =
-            InputHandler::new(stdin, protocol_composer, self.state.clone());
+        let mut input_handler = InputHandler::new(
+            stdin,
+            protocol_composer,
+            self.state.clone(),
+            event_bus,
+        );
+
+        if let Err(e) = input_handler
+            .set_ready_state(EngineReadyState::Starting)
+            .await
+        {
+            error!("Failed to mark engine as starting: {:?}", e);
+        }
 
         // Send the initial command to the engine
         if let Ok(initial_command) = initial_command {
@@ -154,6 +168,17 @@ where
                 return Err(EngineError::ProcessFailedToKill(e.to_string()));
             }
         }
+
+        let event =
+            self.state
+                .write()
+                .await
+                .apply_update(S::ready_state_update(
+                    EngineReadyState::NotRunning,
+                ))?;
+        if let Ok(event_bus) = self.event_bus() {
+            event_bus.publish(event);
+        }
         Ok(())
     }
 }
@@ -233,20 +258,42 @@ where
         let mut event_stream = event_bus.subscribe::<EngineStateInfoEvent>();
 
         loop {
-            let event = event_stream.recv().await;
-            if let Some(event) = event {
-                match event {
-                    EngineStateInfoEvent::Error(error) => return Err(error),
-                    _ => {
-                        if !callback(event) {
-                            return Ok(());
-                        }
+            match event_stream.recv().await {
+                Ok(EngineStateInfoEvent::Error(error)) => return Err(error),
+                Ok(event) => {
+                    if !callback(event) {
+                        return Ok(());
                     }
                 }
+                Err(RecvError::Lagged(count)) => {
+                    warn!("Event monitor dropped {} events", count);
+                }
+                Err(RecvError::Closed) => {
+                    return Err(EngineError::EngineNotRunning(
+                        "Engine events closed".to_string(),
+                    ));
+                }
             }
         }
     }
 
+    /// Wait until the engine is in the specified ready state, giving up
+    /// after `timeout`
+    pub async fn wait_until_ready_within(
+        &self,
+        target_state: EngineReadyState,
+        timeout: Duration,
+    ) -> Result<(), EngineError> {
+        let wait = self.wait_until_ready(target_state.clone());
+        tokio::time::timeout(timeout, wait).await.map_err(|_| {
+            EngineError::Timeout(format!(
+                "Not {:?} within {}ms",
+                target_state,
+                timeout.as_millis()
+            ))
+        })?
+    }
+
     /// Wait until the engine is in the specified ready state
     pub async fn wait_until_ready(
         &self,
@@ -255,19 +302,27 @@ where
         let event_bus = self.event_bus()?;
         let mut event_stream = event_bus.subscribe::<EngineStateInfoEvent>();
 
+        // The engine might have reached the state before we subscribed
+        if self.state.read().await.ready_state() == target_state {
+            return Ok(());
+        }
+
         loop {
-            let event = event_stream.recv().await;
-            if let Some(event) = event {
-                match event {
-                    EngineStateInfoEvent::ReadyStateChanged(state) => {
-                        if state == target_state {
-                            return Ok(());
-                        }
-                    }
-                    EngineStateInfoEvent::Error(error) => {
-                        return Err(error);
+            match event_stream.recv().await {
+                Ok(EngineStateInfoEvent::ReadyStateTransition(transition)) => {
+                    if transition.to == target_state {
+                        return Ok(());
                     }
-                    _ => {}
+                }
+                Ok(EngineStateInfoEvent::Error(error)) => {
+                    return Err(error);
+                }
+                // Ready state changes are never dropped
+                Ok(_) | Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => {
+                    return Err(EngineError::EngineNotRunning(
+                        "Engine events closed".to_string(),
+                    ));
                 }
             }
         }
@@ -2,6 +2,7 @@ use crate::utils::EngineError;
 use std::fmt;
 
 use super::events::LifecycleEvent;
+use super::state::engine_state::EngineReadyState;
 use super::state::EngineState;
 
 pub mod uci_protocol;
@@ -65,6 +66,62 @@ pub enum EngineCommand {
     Quit,
 }
 
+impl EngineCommand {
+    /// Check if the command may be sent while the engine is in `state`
+    ///
+    /// Commands that are not allowed are queued until the engine reaches a
+    /// state that accepts them.
+    pub fn allowed_in(
+        &self,
+        state: &EngineReadyState,
+    ) -> bool {
+        match self {
+            EngineCommand::NewGame
+            | EngineCommand::SetPosition { .. }
+            | EngineCommand::StartAnalysis { .. }
+            | EngineCommand::SetOption { .. } => matches!(
+                state,
+                EngineReadyState::Initialized | EngineReadyState::Ready
+            ),
+            EngineCommand::Raw(_)
+            | EngineCommand::IsReady
+            | EngineCommand::StopAnalysis
+            | EngineCommand::Quit => true,
+        }
+    }
+
+    /// Check if the command is sent right away, ahead of any queued commands
+    pub fn bypasses_queue(&self) -> bool {
+        matches!(
+            self,
+            EngineCommand::IsReady
+                | EngineCommand::StopAnalysis
+                | EngineCommand::Quit
+        )
+    }
+
+    /// The state the engine enters once the command has been sent
+    pub fn resulting_state(&self) -> Option<EngineReadyState> {
+        match self {
+            EngineCommand::NewGame | EngineCommand::SetOption { .. } => {
+                Some(EngineReadyState::Busy)
+            }
+            EngineCommand::StartAnalysis { .. } => {
+                Some(EngineReadyState::Analyzing)
+            }
+            _ => None,
+        }
+    }
+
+    /// Check if the engine must be synchronised with `isready` afterwards
+    pub fn requires_sync(&self) -> bool {
+        matches!(
+            self,
+            EngineCommand::NewGame | EngineCommand::SetOption { .. }
+        )
+    }
+}
+
 /// Possible option value types for engine configuration
 #[derive(Debug, Clone)]
 pub enum OptionValue {
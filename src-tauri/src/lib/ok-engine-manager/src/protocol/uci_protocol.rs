@@ -23,11 +23,9 @@ impl ProtocolParser for UciProtocolParser {
                         EngineReadyState::Initialized,
                     ),
                 )),
-                EngineResponse::ReadyOk => Ok(ParserOutput::StateUpdate(
-                    EngineStateInfoEvent::ReadyStateChanged(
-                        EngineReadyState::Ready,
-                    ),
-                )),
+                EngineResponse::ReadyOk => {
+                    Ok(ParserOutput::StateUpdate(EngineStateInfoEvent::ReadyOk))
+                }
                 EngineResponse::Id(id) => Ok(ParserOutput::StateUpdate(
                     EngineStateInfoEvent::InfoUpdate(id),
                 )),
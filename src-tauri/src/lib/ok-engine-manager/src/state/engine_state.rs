@@ -26,6 +26,41 @@ pub enum EngineReadyState {
     // TODO: Add more states as needed
 }
 
+impl EngineReadyState {
+    /// Check if the engine may move from this state to `next`
+    ///
+    /// Staying in the same state is always allowed, as is stopping the engine.
+    pub fn can_transition_to(
+        &self,
+        next: &EngineReadyState,
+    ) -> bool {
+        use EngineReadyState::*;
+
+        if self == next || *next == NotRunning {
+            return true;
+        }
+
+        matches!(
+            (self, next),
+            (NotRunning, Starting)
+                | (Starting, Initialized)
+                | (Initialized, Ready | Busy | Analyzing)
+                | (Ready, Busy | Analyzing)
+                | (Busy, Ready)
+                | (Analyzing, Ready)
+        )
+    }
+}
+
+/// A change of the engine's readiness state
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct ReadyStateTransition {
+    /// The state the engine left
+    pub from: EngineReadyState,
+    /// The state the engine entered
+    pub to: EngineReadyState,
+}
+
 /// Generic engine information
 #[derive(Debug, Serialize, Default, Clone)]
 pub struct EngineMetadata {
@@ -87,6 +122,22 @@ impl EngineStateInfo {
         self.ready_state = ready_state;
     }
 
+    /// Move to a new readiness state if the transition is allowed
+    pub fn transition(
+        &mut self,
+        to: EngineReadyState,
+    ) -> Result<ReadyStateTransition, EngineError> {
+        if !self.ready_state.can_transition_to(&to) {
+            return Err(EngineError::InvalidStateTransition(format!(
+                "{:?} -> {:?}",
+                self.ready_state, to
+            )));
+        }
+
+        let from = std::mem::replace(&mut self.ready_state, to.clone());
+        Ok(ReadyStateTransition { from, to })
+    }
+
     /// Add a capability to the engine
     pub fn add_capability(
         &mut self,
@@ -208,13 +259,27 @@ impl super::EngineState for EngineStateInfo {
                 self.best_move = Some((best_move.clone(), ponder.clone()));
 
                 // Analysis is complete, update the ready state
-                self.ready_state = EngineReadyState::Ready;
+                self.transition(EngineReadyState::Ready)?;
 
                 Ok(EngineStateInfoEvent::BestMove(best_move, ponder))
             }
+            EngineStateInfoEvent::ReadyOk => match self.ready_state {
+                EngineReadyState::Initialized | EngineReadyState::Busy => {
+                    Ok(EngineStateInfoEvent::ReadyStateTransition(
+                        self.transition(EngineReadyState::Ready)?,
+                    ))
+                }
+                _ => Ok(EngineStateInfoEvent::ReadyOk),
+            },
             EngineStateInfoEvent::ReadyStateChanged(ready_state) => {
-                self.ready_state = ready_state.clone();
-                Ok(EngineStateInfoEvent::ReadyStateChanged(ready_state))
+                Ok(EngineStateInfoEvent::ReadyStateTransition(
+                    self.transition(ready_state)?,
+                ))
+            }
+            EngineStateInfoEvent::ReadyStateTransition(transition) => {
+                Ok(EngineStateInfoEvent::ReadyStateTransition(
+                    self.transition(transition.to)?,
+                ))
             }
             EngineStateInfoEvent::CurrentPositionChanged(position) => {
                 self.current_position = Some(position.clone());
@@ -230,4 +295,86 @@ impl super::EngineState for EngineStateInfo {
             EngineStateInfoEvent::Error(error) => Err(error),
         }
     }
+
+    fn ready_state(&self) -> EngineReadyState {
+        self.ready_state.clone()
+    }
+
+    fn ready_state_update(ready_state: EngineReadyState) -> Self::Update {
+        EngineStateInfoEvent::ReadyStateChanged(ready_state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::EngineState;
+
+    #[test]
+    fn transitions_follow_the_engine_lifecycle() {
+        use EngineReadyState::*;
+
+        assert!(NotRunning.can_transition_to(&Starting));
+        assert!(Starting.can_transition_to(&Initialized));
+        assert!(Ready.can_transition_to(&Busy));
+        assert!(Busy.can_transition_to(&Ready));
+        assert!(Analyzing.can_transition_to(&NotRunning));
+
+        assert!(!NotRunning.can_transition_to(&Ready));
+        assert!(!Busy.can_transition_to(&Analyzing));
+        assert!(!Analyzing.can_transition_to(&Busy));
+    }
+
+    #[test]
+    fn invalid_transitions_leave_the_state_untouched() {
+        let mut state = EngineStateInfo::default();
+        let result = state.apply_update(
+            EngineStateInfoEvent::ReadyStateChanged(EngineReadyState::Ready),
+        );
+        assert!(matches!(
+            result,
+            Err(EngineError::InvalidStateTransition(_))
+        ));
+        assert_eq!(state.ready_state, EngineReadyState::NotRunning);
+
+        state.ready_state = EngineReadyState::Starting;
+        let result =
+            state.apply_update(EngineStateInfoEvent::ReadyStateChanged(
+                EngineReadyState::Initialized,
+            ));
+        assert!(matches!(
+            result,
+            Ok(EngineStateInfoEvent::ReadyStateTransition(
+                ReadyStateTransition {
+                    from: EngineReadyState::Starting,
+                    to: EngineReadyState::Initialized,
+                }
+            ))
+        ));
+    }
+
+    #[test]
+    fn readyok_only_readies_a_waiting_engine() {
+        let mut state = EngineStateInfo {
+            ready_state: EngineReadyState::Busy,
+            ..Default::default()
+        };
+        let result = state.apply_update(EngineStateInfoEvent::ReadyOk);
+        assert!(matches!(
+            result,
+            Ok(EngineStateInfoEvent::ReadyStateTransition(
+                ReadyStateTransition {
+                    from: EngineReadyState::Busy,
+                    to: EngineReadyState::Ready,
+                }
+            ))
+        ));
+        assert_eq!(state.ready_state, EngineReadyState::Ready);
+
+        // The engine answers `isready` while searching, too
+        state.ready_state = EngineReadyState::Analyzing;
+        let result = state.apply_update(EngineStateInfoEvent::ReadyOk);
+        assert!(matches!(result, Ok(EngineStateInfoEvent::ReadyOk)));
+        assert_eq!(state.ready_state, EngineReadyState::Analyzing);
+    }
 }
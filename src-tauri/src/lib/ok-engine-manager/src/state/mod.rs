@@ -1,15 +1,23 @@
+use super::events::BusEvent;
 use super::utils::EngineError;
+use engine_state::EngineReadyState;
 
 pub mod engine_state;
 
 /// Common trait for all engine states regardless of protocol
 pub trait EngineState: Send + Sync + 'static + Clone {
     type Update: Send + Sync + Clone + std::fmt::Debug + 'static;
-    type Event: Send + Sync + Clone + std::fmt::Debug;
+    type Event: Send + Sync + Clone + std::fmt::Debug + BusEvent;
 
     /// Apply an update to the engine state
     fn apply_update(
         &mut self,
         update: Self::Update,
     ) -> Result<Self::Event, EngineError>;
+
+    /// The engine's current readiness state
+    fn ready_state(&self) -> EngineReadyState;
+
+    /// Build the update that moves the engine to another readiness state
+    fn ready_state_update(ready_state: EngineReadyState) -> Self::Update;
 }
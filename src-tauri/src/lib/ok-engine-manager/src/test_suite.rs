@@ -0,0 +1,215 @@
+//! Engine test suites
+//!
+//! A test suite is a set of EPD positions with the moves an engine should
+//! find (`bm`) or avoid (`am`), like WAC or Bratko-Kopec. The engine
+//! searches each position and solves it if its best move is one of the
+//! best moves and none of the moves to avoid.
+
+use ok_parse::epd::EpdRecord;
+use ok_parse::uci::{InfoParams, Score};
+use ok_utils::ts_export;
+use shakmaty::fen::Fen;
+use shakmaty::san::San;
+use shakmaty::uci::UciMove;
+use shakmaty::{CastlingMode, Chess};
+
+/// How an engine did on one position of a test suite
+#[ts_export]
+pub struct SuitePositionResult {
+    /// The position's `id`, like "WAC.001"
+    pub id: Option<String>,
+    /// The position as a full FEN
+    pub fen: String,
+    /// The moves that solve the position, in SAN
+    pub best_moves: Vec<String>,
+    /// The moves that fail the position, in SAN
+    pub avoid_moves: Vec<String>,
+    /// The engine's best move in SAN, if it found a legal one
+    pub engine_move: Option<String>,
+    /// Whether the engine's move solved the position
+    pub solved: bool,
+    /// Depth of the engine's last main line update
+    pub depth: Option<u32>,
+    /// The engine's score in centipawns, from the side to move's view
+    pub score_cp: Option<i32>,
+    /// Moves to mate, negative when the side to move is being mated
+    pub mate: Option<i32>,
+    /// Why the position couldn't be checked, like an engine timeout
+    pub error: Option<String>,
+}
+
+/// How an engine did on a test suite
+#[ts_export]
+pub struct SuiteResult {
+    pub engine_name: String,
+    /// Number of positions solved
+    pub solved: usize,
+    /// Number of positions in the suite
+    pub total: usize,
+    /// Results of each position, in suite order
+    pub positions: Vec<SuitePositionResult>,
+}
+
+impl SuitePositionResult {
+    /// Check an engine's best move, given in UCI, against a position's
+    /// best moves and moves to avoid
+    ///
+    /// `last_update` is the engine's last main line update, which gives
+    /// the depth and score.
+    pub fn check(
+        record: &EpdRecord,
+        engine_move: &str,
+        last_update: Option<&InfoParams>,
+    ) -> Self {
+        let mut result = Self::unchecked(record, None);
+        if let Some(info) = last_update {
+            result.depth = info.depth;
+            match info.score {
+                Some(Score::Centipawns { value, .. }) => {
+                    result.score_cp = Some(value)
+                }
+                Some(Score::Mate(moves)) => result.mate = Some(moves),
+                None => {}
+            }
+        }
+
+        let Some(position) = parse_position(&result.fen) else {
+            result.error = Some(format!("Invalid position '{}'", result.fen));
+            return result;
+        };
+        let Some(played) = UciMove::from_ascii(engine_move.as_bytes())
+            .ok()
+            .and_then(|uci| uci.to_move(&position).ok())
+        else {
+            result.error =
+                Some(format!("Illegal engine move '{}'", engine_move));
+            return result;
+        };
+
+        let is_one_of = |moves: &[String]| {
+            moves.iter().any(|san| {
+                san.parse::<San>()
+                    .ok()
+                    .and_then(|san| san.to_move(&position).ok())
+                    .is_some_and(|listed| listed == played)
+            })
+        };
+        let finds_best_move =
+            record.best_moves().is_empty() || is_one_of(record.best_moves());
+        let avoids_moves = !is_one_of(record.avoid_moves());
+        let has_solution = !(record.best_moves().is_empty()
+            && record.avoid_moves().is_empty());
+
+        result.solved = has_solution && finds_best_move && avoids_moves;
+        result.engine_move =
+            Some(San::from_move(&position, &played).to_string());
+        result
+    }
+
+    /// A position that wasn't checked, with the reason if there is one
+    pub fn unchecked(
+        record: &EpdRecord,
+        error: Option<String>,
+    ) -> Self {
+        Self {
+            id: record.id().map(str::to_string),
+            fen: record.fen(),
+            best_moves: record.best_moves().to_vec(),
+            avoid_moves: record.avoid_moves().to_vec(),
+            engine_move: None,
+            solved: false,
+            depth: None,
+            score_cp: None,
+            mate: None,
+            error,
+        }
+    }
+
+    /// Add the engine's result to a record as the standard EPD opcodes:
+    /// predicted move (`pm`), centipawn evaluation (`ce`), direct mate
+    /// (`dm`) and analysis depth (`acd`)
+    pub fn annotate(
+        &self,
+        record: &mut EpdRecord,
+    ) {
+        if let Some(ref engine_move) = self.engine_move {
+            record.set_operation("pm", vec![engine_move.clone()]);
+        }
+        if let Some(score) = self.score_cp {
+            record.set_operation("ce", vec![score.to_string()]);
+        }
+        if let Some(mate) = self.mate {
+            record.set_operation("dm", vec![mate.to_string()]);
+        }
+        if let Some(depth) = self.depth {
+            record.set_operation("acd", vec![depth.to_string()]);
+        }
+    }
+}
+
+/// Parse a position, with Chess960 castling if the castling rights need it
+fn parse_position(fen: &str) -> Option<Chess> {
+    let fen: Fen = fen.parse().ok()?;
+    fen.clone()
+        .into_position(CastlingMode::Standard)
+        .ok()
+        .or_else(|| fen.into_position(CastlingMode::Chess960).ok())
+}
+
+impl SuiteResult {
+    pub fn new(
+        engine_name: &str,
+        positions: Vec<SuitePositionResult>,
+    ) -> Self {
+        Self {
+            engine_name: engine_name.to_string(),
+            solved: positions.iter().filter(|result| result.solved).count(),
+            total: positions.len(),
+            positions,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ok_parse::epd::parse_epd_line;
+
+    #[test]
+    fn engine_moves_are_checked_against_best_and_avoid_moves() {
+        let wac = parse_epd_line(
+            "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - \
+             bm Qg6; id \"WAC.001\";",
+            1,
+        )
+        .unwrap();
+        let info = InfoParams {
+            depth: Some(12),
+            score: Some(Score::Mate(3)),
+            ..Default::default()
+        };
+
+        let solved = SuitePositionResult::check(&wac, "g3g6", Some(&info));
+        assert!(solved.solved);
+        assert_eq!(solved.engine_move.as_deref(), Some("Qg6"));
+        assert_eq!((solved.depth, solved.mate), (Some(12), Some(3)));
+
+        let missed = SuitePositionResult::check(&wac, "e5f7", None);
+        assert!(!missed.solved && missed.error.is_none());
+
+        let avoid = parse_epd_line(
+            "r1b1k2r/ppppnppp/2n2q2/2b5/3NP3/2P1B3/PP3PPP/RN1QKB1R w KQkq - \
+             am Nxc6;",
+            1,
+        )
+        .unwrap();
+        assert!(!SuitePositionResult::check(&avoid, "d4c6", None).solved);
+        assert!(SuitePositionResult::check(&avoid, "d4b5", None).solved);
+
+        let illegal = SuitePositionResult::check(&avoid, "e1e3", None);
+        assert!(!illegal.solved && illegal.error.is_some());
+
+        let result = SuiteResult::new("engine", vec![solved, missed]);
+        assert_eq!((result.solved, result.total), (1, 2));
+    }
+}
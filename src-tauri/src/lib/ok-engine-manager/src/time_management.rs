@@ -0,0 +1,209 @@
+//! Search limits and time allocation for analyzing a series of positions
+
+use ok_parse::uci::{InfoParams, Score};
+
+/// The shortest time (in milliseconds) any position is analyzed for
+const MIN_MOVETIME_MS: u64 = 100;
+
+/// Evaluation change (in centipawns) between positions considered a swing
+const SWING_THRESHOLD_CP: i32 = 50;
+
+/// Centipawn value used in place of a mate score when comparing evaluations
+const MATE_SCORE_CP: i32 = 10_000;
+
+/// Share of the base time given to book moves
+const BOOK_FACTOR: f64 = 0.25;
+
+/// Share of the base time given to forced recaptures
+const RECAPTURE_FACTOR: f64 = 0.5;
+
+/// Share of the base time given to positions following a swing or PV change
+const VOLATILE_FACTOR: f64 = 2.0;
+
+/// Limits for a single engine search
+///
+/// Any combination of limits can be set, the engine stops at whichever
+/// is reached first. No limits means an infinite search.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SearchLimits {
+    pub depth: Option<u32>,
+    pub movetime_ms: Option<u32>,
+    pub nodes: Option<u64>,
+}
+
+/// What is known about a position before analyzing it
+#[derive(Debug, Clone, Default)]
+pub struct PositionHints {
+    /// The position is still in the opening book
+    pub is_book: bool,
+    /// The side to move is expected to recapture
+    pub is_forced_recapture: bool,
+}
+
+/// Distributes a total time budget across positions in game order
+///
+/// Positions following an evaluation swing or a change of the principal
+/// variation get more time, book moves and forced recaptures get less.
+/// The time actually used is tracked so the total budget is respected.
+#[derive(Debug, Clone)]
+pub struct AdaptiveTimeBudget {
+    remaining_ms: u64,
+    remaining_positions: usize,
+    /// Score and expected reply from the previous position's analysis
+    previous: Option<(i32, Option<String>)>,
+    /// The previous analysis showed a swing or PV change
+    volatile: bool,
+}
+
+impl AdaptiveTimeBudget {
+    pub fn new(
+        total_seconds: u64,
+        total_positions: usize,
+    ) -> Self {
+        Self {
+            remaining_ms: total_seconds * 1000,
+            remaining_positions: total_positions,
+            previous: None,
+            volatile: false,
+        }
+    }
+
+    /// The budget left for the remaining positions
+    pub fn remaining_ms(&self) -> u64 {
+        self.remaining_ms
+    }
+
+    /// Get the search limits for the next position
+    pub fn next_limits(
+        &self,
+        hints: &PositionHints,
+    ) -> SearchLimits {
+        let positions = self.remaining_positions.max(1) as u64;
+        let base = self.remaining_ms / positions;
+
+        let factor = if hints.is_book {
+            BOOK_FACTOR
+        } else if hints.is_forced_recapture {
+            RECAPTURE_FACTOR
+        } else if self.volatile {
+            VOLATILE_FACTOR
+        } else {
+            1.0
+        };
+
+        // Leave enough for every later position to get the minimum
+        let reserved = (positions - 1) * MIN_MOVETIME_MS;
+        let max = self
+            .remaining_ms
+            .saturating_sub(reserved)
+            .max(MIN_MOVETIME_MS);
+        let movetime =
+            ((base as f64 * factor) as u64).clamp(MIN_MOVETIME_MS, max);
+
+        SearchLimits {
+            depth: None,
+            movetime_ms: Some(movetime as u32),
+            nodes: None,
+        }
+    }
+
+    /// Record the time used for a position and the resulting analysis
+    pub fn record(
+        &mut self,
+        used_ms: u64,
+        analysis: Option<&InfoParams>,
+    ) {
+        self.remaining_ms = self.remaining_ms.saturating_sub(used_ms);
+        self.remaining_positions = self.remaining_positions.saturating_sub(1);
+
+        let Some(score) = analysis
+            .and_then(|info| info.score.as_ref())
+            .map(score_to_centipawns)
+        else {
+            self.previous = None;
+            self.volatile = false;
+            return;
+        };
+        let pv = analysis.and_then(|info| info.pv.as_ref());
+
+        self.volatile = match &self.previous {
+            Some((previous_score, expected_reply)) => {
+                // Scores are from the side to move, which alternates
+                let swung =
+                    (score + previous_score).abs() >= SWING_THRESHOLD_CP;
+                let pv_changed = expected_reply.is_some()
+                    && pv.and_then(|pv| pv.first()) != expected_reply.as_ref();
+                swung || pv_changed
+            }
+            None => false,
+        };
+
+        let expected_reply = pv.and_then(|pv| pv.get(1)).cloned();
+        self.previous = Some((score, expected_reply));
+    }
+}
+
+/// Convert a score to centipawns, treating mates as a large advantage
+pub fn score_to_centipawns(score: &Score) -> i32 {
+    match score {
+        Score::Centipawns { value, .. } => *value,
+        Score::Mate(moves) => moves.signum() * MATE_SCORE_CP,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn analysis(
+        score: i32,
+        pv: &[&str],
+    ) -> InfoParams {
+        InfoParams {
+            score: Some(Score::Centipawns {
+                value: score,
+                bound: None,
+            }),
+            pv: Some(pv.iter().map(|m| m.to_string()).collect()),
+            ..Default::default()
+        }
+    }
+
+    fn movetime(limits: SearchLimits) -> u64 {
+        limits.movetime_ms.unwrap_or_default() as u64
+    }
+
+    #[test]
+    fn quiet_moves_get_less_time_than_swings() {
+        let mut budget = AdaptiveTimeBudget::new(10, 10);
+        let normal = movetime(budget.next_limits(&PositionHints::default()));
+        assert_eq!(normal, 1000);
+
+        let recapture = PositionHints {
+            is_forced_recapture: true,
+            ..Default::default()
+        };
+        assert!(movetime(budget.next_limits(&recapture)) < normal);
+
+        budget.record(1000, Some(&analysis(20, &["e2e4", "e7e5"])));
+        budget.record(1000, Some(&analysis(150, &["d7d5"])));
+        assert!(movetime(budget.next_limits(&PositionHints::default())) > 1000);
+    }
+
+    #[test]
+    fn total_budget_is_respected() {
+        let mut budget = AdaptiveTimeBudget::new(1, 5);
+        budget.volatile = true;
+        let first = movetime(budget.next_limits(&PositionHints::default()));
+        assert!(first <= 1000 - 4 * MIN_MOVETIME_MS);
+
+        budget.record(first, None);
+        let mut total = first;
+        for _ in 0..4 {
+            let next = movetime(budget.next_limits(&PositionHints::default()));
+            budget.record(next, None);
+            total += next;
+        }
+        assert!(total <= 1000);
+    }
+}
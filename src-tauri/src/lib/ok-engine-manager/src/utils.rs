@@ -1,8 +1,11 @@
 //! Utility functions and common types
 
+use ok_parse::uci::{OptionDefinition, OptionType};
 use serde::Serialize;
 
 use super::manager::TimeStrategy;
+use super::protocol::OptionValue;
+use super::time_management::SearchLimits;
 
 /// Comprehensive error types
 #[derive(Debug, Clone, Serialize, thiserror::Error)]
@@ -36,6 +39,9 @@ pub enum EngineError {
     /// The engine state failed to update
     #[error("The engine state failed to update: {0}")]
     StateFailedToUpdate(String),
+    /// The engine cannot move between the two ready states
+    #[error("Invalid engine state transition: {0}")]
+    InvalidStateTransition(String),
 
     ////// IO Errors //////
     /// The engine IO failed to read a line
@@ -59,25 +65,281 @@ pub enum EngineError {
     /// Output handler already started
     #[error("Output handler already started: {0}")]
     OutputHandlerAlreadyStarted(String),
+
+    ////// Timeout Errors //////
+    /// The engine did not respond in time
+    #[error("The engine did not respond in time: {0}")]
+    Timeout(String),
+}
+
+/// Errors returned by the `EngineManager`
+///
+/// Every variant names the engine it relates to and serializes with an
+/// `error_type` tag so the frontend can branch on the kind of failure.
+#[derive(Debug, Clone, Serialize, thiserror::Error)]
+#[serde(tag = "error_type")]
+pub enum EngineManagerError {
+    /// No engine has been loaded under the given name
+    #[error("Unknown engine: {engine_name}")]
+    UnknownEngine { engine_name: String },
+    /// The engine exists but cannot accept the command yet
+    #[error("Engine '{engine_name}' is not ready: {reason}")]
+    EngineNotReady { engine_name: String, reason: String },
+    /// The engine protocol could not compose or parse a message
+    #[error("Protocol error for engine '{engine_name}': {message}")]
+    ProtocolError {
+        engine_name: String,
+        message: String,
+    },
+    /// Communicating with or managing the engine process failed
+    #[error("IO error for engine '{engine_name}': {message}")]
+    IoError {
+        engine_name: String,
+        message: String,
+    },
+    /// An option was rejected before being sent to the engine
+    #[error("Invalid option '{option}' for engine '{engine_name}': {reason}")]
+    InvalidOption {
+        engine_name: String,
+        option: String,
+        reason: String,
+    },
+    /// The engine did not respond in time
+    #[error("Engine '{engine_name}' timed out: {message}")]
+    Timeout {
+        engine_name: String,
+        message: String,
+    },
 }
 
-/// Calculate analysis time per position based on strategy
+impl EngineManagerError {
+    /// Build an `UnknownEngine` error
+    pub fn unknown_engine(engine_name: &str) -> Self {
+        Self::UnknownEngine {
+            engine_name: engine_name.to_string(),
+        }
+    }
+
+    /// Categorize a lower level `EngineError` for the given engine
+    pub fn from_engine_error(
+        engine_name: &str,
+        error: EngineError,
+    ) -> Self {
+        let engine_name = engine_name.to_string();
+        match error {
+            EngineError::ProtocolFailedToParseLine(_)
+            | EngineError::InvalidProtocolType(_) => Self::ProtocolError {
+                engine_name,
+                message: error.to_string(),
+            },
+            EngineError::EngineAlreadyRunning(_)
+            | EngineError::EngineNotRunning(_)
+            | EngineError::StateFailedToUpdate(_)
+            | EngineError::InvalidStateTransition(_)
+            | EngineError::InvalidState(_)
+            | EngineError::OutputHandlerAlreadyStarted(_) => {
+                Self::EngineNotReady {
+                    engine_name,
+                    reason: error.to_string(),
+                }
+            }
+            EngineError::ProcessFailedToStart(_)
+            | EngineError::ProcessFailedToExecuteCommand(_)
+            | EngineError::ProcessFailedToKill(_)
+            | EngineError::IoFailedToReadLine(_)
+            | EngineError::IoFailedToWriteLine(_)
+            | EngineError::IoFailedToFlush(_)
+            | EngineError::IoFailedToClose(_)
+            | EngineError::IoFailedToJoin(_) => Self::IoError {
+                engine_name,
+                message: error.to_string(),
+            },
+            EngineError::Timeout(message) => Self::Timeout {
+                engine_name,
+                message,
+            },
+        }
+    }
+}
+
+/// Check a value against the option definition the engine advertised
+///
+/// Values arrive from the frontend as strings, so they are converted to the
+/// type the option expects. Returns the value to send or the rejection reason.
+pub fn validate_option_value(
+    definition: &OptionDefinition,
+    value: OptionValue,
+) -> Result<OptionValue, String> {
+    match definition.option_type {
+        OptionType::Check => match value {
+            OptionValue::Boolean(b) => Ok(OptionValue::Boolean(b)),
+            OptionValue::String(s) => match s.trim() {
+                "true" => Ok(OptionValue::Boolean(true)),
+                "false" => Ok(OptionValue::Boolean(false)),
+                other => {
+                    Err(format!("expected true or false, got '{}'", other))
+                }
+            },
+            other => Err(format!("expected a boolean, got '{}'", other)),
+        },
+        OptionType::Spin => {
+            let number = match value {
+                OptionValue::Integer(i) => i,
+                OptionValue::String(ref s) => s
+                    .trim()
+                    .parse::<i64>()
+                    .map_err(|_| format!("expected an integer, got '{}'", s))?,
+                other => {
+                    return Err(format!("expected an integer, got '{}'", other))
+                }
+            };
+            if let Some(min) = definition.min {
+                if number < min as i64 {
+                    return Err(format!(
+                        "{} is below the minimum {}",
+                        number, min
+                    ));
+                }
+            }
+            if let Some(max) = definition.max {
+                if number > max as i64 {
+                    return Err(format!(
+                        "{} is above the maximum {}",
+                        number, max
+                    ));
+                }
+            }
+            Ok(OptionValue::Integer(number))
+        }
+        OptionType::Combo => {
+            let choice = value.to_string();
+            definition
+                .var
+                .iter()
+                .find(|var| var.eq_ignore_ascii_case(choice.trim()))
+                .map(|var| OptionValue::String(var.clone()))
+                .ok_or_else(|| {
+                    format!(
+                        "'{}' is not one of: {}",
+                        choice,
+                        definition.var.join(", ")
+                    )
+                })
+        }
+        OptionType::String | OptionType::Button => {
+            Ok(OptionValue::String(value.to_string()))
+        }
+    }
+}
+
+/// Calculate the search limits per position based on strategy
+///
+/// The adaptive strategy starts from an even split, use
+/// `AdaptiveTimeBudget` to adjust it as positions are analyzed.
 pub fn calculate_analysis_time(
     time_strategy: &TimeStrategy,
     total_positions: usize,
-) -> (Option<u32>, Option<u32>) {
+) -> SearchLimits {
     match time_strategy {
-        TimeStrategy::TotalBudget { total_seconds } => {
+        TimeStrategy::TotalBudget { total_seconds }
+        | TimeStrategy::Adaptive { total_seconds } => {
             let time_per_position = if total_positions > 0 {
                 total_seconds / total_positions as u64
             } else {
                 10 // Default fallback
             };
-            (None, Some(time_per_position as u32 * 1000)) // Convert to milliseconds
+            SearchLimits {
+                movetime_ms: Some(time_per_position as u32 * 1000), // Convert to milliseconds
+                ..Default::default()
+            }
         }
-        TimeStrategy::FixedPerMove { seconds_per_move } => {
-            (None, Some(*seconds_per_move as u32 * 1000))
+        TimeStrategy::FixedPerMove { seconds_per_move } => SearchLimits {
+            movetime_ms: Some(*seconds_per_move as u32 * 1000),
+            ..Default::default()
+        },
+        TimeStrategy::FixedDepth { depth } => SearchLimits {
+            depth: Some(*depth),
+            ..Default::default()
+        },
+        TimeStrategy::UntilStable { convergence } => SearchLimits {
+            depth: convergence.max_depth,
+            movetime_ms: convergence.max_time_ms,
+            nodes: None,
+        },
+        TimeStrategy::FixedNodes { nodes } => SearchLimits {
+            nodes: Some(*nodes),
+            ..Default::default()
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spin_option() -> OptionDefinition {
+        OptionDefinition {
+            name: "Hash".to_string(),
+            option_type: OptionType::Spin,
+            default: Some("16".to_string()),
+            min: Some(1),
+            max: Some(1024),
+            var: Vec::new(),
         }
-        TimeStrategy::FixedDepth { depth } => (Some(*depth), None),
+    }
+
+    #[test]
+    fn spin_values_are_parsed_and_bounded() {
+        let option = spin_option();
+        assert!(matches!(
+            validate_option_value(&option, OptionValue::String("64".into())),
+            Ok(OptionValue::Integer(64))
+        ));
+        assert!(validate_option_value(
+            &option,
+            OptionValue::String("4096".into())
+        )
+        .is_err());
+        assert!(validate_option_value(
+            &option,
+            OptionValue::String("lots".into())
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn combo_values_must_be_listed() {
+        let option = OptionDefinition {
+            name: "Style".to_string(),
+            option_type: OptionType::Combo,
+            default: Some("Normal".to_string()),
+            min: None,
+            max: None,
+            var: vec!["Solid".to_string(), "Normal".to_string()],
+        };
+        assert!(matches!(
+            validate_option_value(&option, OptionValue::String("solid".into())),
+            Ok(OptionValue::String(ref s)) if s == "Solid"
+        ));
+        assert!(validate_option_value(
+            &option,
+            OptionValue::String("Risky".into())
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn engine_errors_are_categorized() {
+        let error = EngineManagerError::from_engine_error(
+            "stockfish",
+            EngineError::IoFailedToWriteLine("broken pipe".into()),
+        );
+        assert!(matches!(error, EngineManagerError::IoError { .. }));
+
+        let error = EngineManagerError::from_engine_error(
+            "stockfish",
+            EngineError::InvalidState("Input handler not initialized".into()),
+        );
+        assert!(matches!(error, EngineManagerError::EngineNotReady { .. }));
     }
 }
@@ -0,0 +1,277 @@
+//! Text encodings of game files
+//!
+//! PGN files are meant to be Latin-1, but most recent ones are UTF-8 and
+//! older databases are often Windows-1252. Sources are read as bytes and
+//! decoded a line at a time: a byte order mark decides the encoding, and
+//! otherwise lines are UTF-8 until one isn't valid UTF-8, from which point
+//! the source is read as Windows-1252. Windows-1252 only differs from
+//! Latin-1 in bytes 0x80 to 0x9F, which are control characters in Latin-1
+//! that text never uses, so Latin-1 sources decode the same.
+//!
+//! Decoded text is normalised to NFC, so names with accents are the same
+//! whether their source composed the accents or not.
+
+use std::borrow::Cow;
+use std::io::{self, BufRead};
+
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, WINDOWS_1252};
+use serde::{Deserialize, Serialize};
+use unicode_normalization::{is_nfc_quick, IsNormalized, UnicodeNormalization};
+
+/// The encoding a source was read as
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize,
+)]
+pub enum TextEncoding {
+    #[default]
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    /// Latin-1, which is Windows-1252 without the characters in bytes 0x80
+    /// to 0x9F
+    Latin1,
+    Windows1252,
+}
+
+impl TextEncoding {
+    /// The encoding's usual name, like "UTF-8" or "Windows-1252"
+    pub fn name(self) -> &'static str {
+        match self {
+            TextEncoding::Utf8 => "UTF-8",
+            TextEncoding::Utf16Le => "UTF-16LE",
+            TextEncoding::Utf16Be => "UTF-16BE",
+            TextEncoding::Latin1 => "ISO-8859-1",
+            TextEncoding::Windows1252 => "Windows-1252",
+        }
+    }
+
+    fn is_utf16(self) -> bool {
+        matches!(self, TextEncoding::Utf16Le | TextEncoding::Utf16Be)
+    }
+}
+
+impl std::fmt::Display for TextEncoding {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Reads lines of a source and decodes them, working out the source's
+/// encoding as it goes
+#[derive(Debug, Clone, Default)]
+pub struct LineDecoder {
+    encoding: TextEncoding,
+    /// The source started with a byte order mark
+    has_bom: bool,
+    /// The first line, and any byte order mark, has been read
+    started: bool,
+}
+
+impl LineDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The encoding of the source, as far as it has been read
+    pub fn encoding(&self) -> TextEncoding {
+        self.encoding
+    }
+
+    /// Read the bytes of the next line, up to and including its line
+    /// feed, into `line`
+    ///
+    /// A byte order mark at the start of the source is skipped, but is
+    /// counted in the number of bytes read that is returned.
+    pub fn read_line<R: BufRead>(
+        &mut self,
+        reader: &mut R,
+        line: &mut Vec<u8>,
+    ) -> io::Result<usize> {
+        let mut read = 0;
+        if !self.started {
+            self.started = true;
+            read += self.read_bom(reader)?;
+        }
+        if !self.encoding.is_utf16() {
+            return Ok(read + reader.read_until(b'\n', line)?);
+        }
+
+        // A line feed byte may be half of another character in UTF-16, so
+        // keep reading until it's a whole line feed
+        loop {
+            let chunk = reader.read_until(b'\n', line)?;
+            read += chunk;
+            if chunk == 0 || line.last() != Some(&b'\n') {
+                return Ok(read);
+            }
+            if self.encoding == TextEncoding::Utf16Le {
+                if line.len() % 2 == 1 {
+                    let mut high = [0];
+                    let n = reader.read(&mut high)?;
+                    line.extend_from_slice(&high[..n]);
+                    read += n;
+                    if n == 0 || high[0] == 0 {
+                        return Ok(read);
+                    }
+                }
+            } else if line.len().is_multiple_of(2) && line[line.len() - 2] == 0
+            {
+                return Ok(read);
+            }
+        }
+    }
+
+    /// Skip a byte order mark, returning its length
+    fn read_bom<R: BufRead>(
+        &mut self,
+        reader: &mut R,
+    ) -> io::Result<usize> {
+        let start = reader.fill_buf()?;
+        let Some((encoding, length)) = Encoding::for_bom(start) else {
+            return Ok(0);
+        };
+        self.has_bom = true;
+        self.encoding = if encoding == UTF_16LE {
+            TextEncoding::Utf16Le
+        } else if encoding == UTF_16BE {
+            TextEncoding::Utf16Be
+        } else {
+            TextEncoding::Utf8
+        };
+        reader.consume(length);
+        Ok(length)
+    }
+
+    /// Decode the bytes of a line and normalise them to NFC
+    pub fn decode(
+        &mut self,
+        line: &[u8],
+    ) -> String {
+        let text = match self.encoding {
+            TextEncoding::Utf8 => match std::str::from_utf8(line) {
+                Ok(text) => Cow::Borrowed(text),
+                // A source marked as UTF-8 stays UTF-8, with invalid bytes
+                // replaced
+                Err(_) if self.has_bom => String::from_utf8_lossy(line),
+                Err(_) => {
+                    self.encoding = TextEncoding::Latin1;
+                    self.decode_legacy(line)
+                }
+            },
+            TextEncoding::Utf16Le => {
+                UTF_16LE.decode_without_bom_handling(line).0
+            }
+            TextEncoding::Utf16Be => {
+                UTF_16BE.decode_without_bom_handling(line).0
+            }
+            TextEncoding::Latin1 | TextEncoding::Windows1252 => {
+                self.decode_legacy(line)
+            }
+        };
+        normalize(&text)
+    }
+
+    /// Decode a Latin-1 or Windows-1252 line, noting when it uses the
+    /// characters only Windows-1252 has
+    fn decode_legacy<'a>(
+        &mut self,
+        line: &'a [u8],
+    ) -> Cow<'a, str> {
+        if line.iter().any(|byte| (0x80..0xA0).contains(byte)) {
+            self.encoding = TextEncoding::Windows1252;
+        }
+        WINDOWS_1252.decode_without_bom_handling(line).0
+    }
+}
+
+/// Decode text that isn't marked with an encoding, like the names in a
+/// SCID database: UTF-8 if it's valid UTF-8, otherwise Windows-1252
+pub fn decode_text(bytes: &[u8]) -> String {
+    let text = match std::str::from_utf8(bytes) {
+        Ok(text) => Cow::Borrowed(text),
+        Err(_) => WINDOWS_1252.decode_without_bom_handling(bytes).0,
+    };
+    normalize(&text)
+}
+
+/// Normalise text to NFC
+pub fn normalize(text: &str) -> String {
+    if text.is_ascii() || is_nfc_quick(text.chars()) == IsNormalized::Yes {
+        text.to_string()
+    } else {
+        text.nfc().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decode every line of a source
+    fn decode_all(source: &[u8]) -> (Vec<String>, TextEncoding) {
+        let mut reader = source;
+        let mut decoder = LineDecoder::new();
+        let mut lines = Vec::new();
+        let mut line = Vec::new();
+        let mut read = 0;
+        loop {
+            line.clear();
+            let n = decoder.read_line(&mut reader, &mut line).unwrap();
+            if n == 0 {
+                break;
+            }
+            read += n;
+            lines.push(decoder.decode(&line));
+        }
+        assert_eq!(read, source.len());
+        (lines, decoder.encoding())
+    }
+
+    #[test]
+    fn detects_encodings() {
+        let (lines, encoding) = decode_all("[White \"Réti\"]\n".as_bytes());
+        assert_eq!(lines, ["[White \"Réti\"]\n"]);
+        assert_eq!(encoding, TextEncoding::Utf8);
+
+        // Latin-1 after an ASCII line
+        let (lines, encoding) = decode_all(b"1. e4\n[White \"R\xe9ti\"]\n");
+        assert_eq!(lines[1], "[White \"Réti\"]\n");
+        assert_eq!(encoding, TextEncoding::Latin1);
+
+        // Windows-1252 has curly quotes where Latin-1 has control codes
+        let (lines, encoding) = decode_all(b"{\x93Best\x94 by R\xe9ti}\n");
+        assert_eq!(lines, ["{\u{201c}Best\u{201d} by Réti}\n"]);
+        assert_eq!(encoding, TextEncoding::Windows1252);
+
+        let (lines, encoding) = decode_all(b"\xef\xbb\xbf1. e4\n");
+        assert_eq!(lines, ["1. e4\n"]);
+        assert_eq!(encoding, TextEncoding::Utf8);
+
+        // A line feed byte in the middle of "Ċ" doesn't end the line
+        let mut utf16 = vec![0xFF, 0xFE];
+        for unit in "\u{0a0a}\u{10a}\n1. e4\n".encode_utf16() {
+            utf16.extend_from_slice(&unit.to_le_bytes());
+        }
+        let (lines, encoding) = decode_all(&utf16);
+        assert_eq!(lines, ["\u{0a0a}\u{10a}\n", "1. e4\n"]);
+        assert_eq!(encoding, TextEncoding::Utf16Le);
+
+        let mut utf16 = vec![0xFE, 0xFF];
+        for unit in "\u{10a}\u{0a0a}\n1. e4".encode_utf16() {
+            utf16.extend_from_slice(&unit.to_be_bytes());
+        }
+        let (lines, encoding) = decode_all(&utf16);
+        assert_eq!(lines, ["\u{10a}\u{0a0a}\n", "1. e4"]);
+        assert_eq!(encoding, TextEncoding::Utf16Be);
+    }
+
+    #[test]
+    fn normalizes_to_nfc() {
+        // "e" followed by a combining acute accent
+        assert_eq!(decode_text("Re\u{301}ti".as_bytes()), "Réti");
+        assert_eq!(decode_text(b"R\xe9ti"), "Réti");
+    }
+}
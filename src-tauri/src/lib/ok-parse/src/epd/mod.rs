@@ -0,0 +1,299 @@
+//! Extended Position Description (EPD)
+//!
+//! Each line of an EPD file is a position: the first four fields of a FEN
+//! (piece placement, side to move, castling and en passant) followed by
+//! operations like `bm Qd1+;` or `id "WAC.001";`. Test suites use them to
+//! give the best moves (`bm`), the moves to avoid (`am`), an ID (`id`) and
+//! comments (`c0` to `c9`) of each position.
+
+use chumsky::prelude::*;
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+pub mod writer;
+
+pub use writer::{write_epd, write_epd_record};
+
+/// A position from an EPD file and its operations
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EpdRecord {
+    /// The first four FEN fields: placement, side to move, castling rights
+    /// and en passant square
+    pub position: String,
+    /// The operations, in the order they were given
+    pub operations: Vec<EpdOperation>,
+}
+
+/// An EPD operation, an opcode with its operands like `bm Nf3 Nc3;`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EpdOperation {
+    pub opcode: String,
+    /// Operands without the quotes around strings
+    pub operands: Vec<String>,
+}
+
+impl EpdOperation {
+    pub fn new(
+        opcode: &str,
+        operands: Vec<String>,
+    ) -> Self {
+        Self {
+            opcode: opcode.to_string(),
+            operands,
+        }
+    }
+}
+
+impl EpdRecord {
+    pub fn new(position: &str) -> Self {
+        Self {
+            position: position.to_string(),
+            operations: Vec::new(),
+        }
+    }
+
+    /// The operands of the first operation with an opcode
+    pub fn operands(
+        &self,
+        opcode: &str,
+    ) -> Option<&[String]> {
+        self.operations
+            .iter()
+            .find(|operation| operation.opcode == opcode)
+            .map(|operation| operation.operands.as_slice())
+    }
+
+    /// Replace the operation with an opcode, or add it if there isn't one
+    pub fn set_operation(
+        &mut self,
+        opcode: &str,
+        operands: Vec<String>,
+    ) {
+        match self
+            .operations
+            .iter_mut()
+            .find(|operation| operation.opcode == opcode)
+        {
+            Some(operation) => operation.operands = operands,
+            None => self.operations.push(EpdOperation::new(opcode, operands)),
+        }
+    }
+
+    /// The best moves (`bm`), in SAN
+    pub fn best_moves(&self) -> &[String] {
+        self.operands("bm").unwrap_or_default()
+    }
+
+    /// The moves to avoid (`am`), in SAN
+    pub fn avoid_moves(&self) -> &[String] {
+        self.operands("am").unwrap_or_default()
+    }
+
+    /// The position's ID (`id`), like "WAC.001"
+    pub fn id(&self) -> Option<&str> {
+        self.operands("id")
+            .and_then(|operands| operands.first())
+            .map(String::as_str)
+    }
+
+    /// The primary comment (`c0`)
+    pub fn comment(&self) -> Option<&str> {
+        self.operands("c0")
+            .and_then(|operands| operands.first())
+            .map(String::as_str)
+    }
+
+    /// The position as a full FEN, with the move counters from the `hmvc`
+    /// and `fmvn` operations or "0 1" without them
+    pub fn fen(&self) -> String {
+        let counter = |opcode: &str, default: &'static str| {
+            self.operands(opcode)
+                .and_then(|operands| operands.first())
+                .map_or(default.to_string(), |value| value.clone())
+        };
+        format!(
+            "{} {} {}",
+            self.position,
+            counter("hmvc", "0"),
+            counter("fmvn", "1")
+        )
+    }
+}
+
+/// An EPD line that failed to parse
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error, Serialize)]
+pub enum EpdParseError {
+    /// The line isn't a valid EPD record
+    #[error("Parse failure on line {line}:\n\tInput: '{input}'\n\tMessage: {message}")]
+    ParseFailure {
+        /// Line number, starting at 1
+        line: usize,
+        input: String,
+        message: String,
+    },
+}
+
+/// The records parsed from an EPD source along with the lines that failed
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EpdParseResult {
+    /// Successfully parsed records, in source order
+    pub records: Vec<EpdRecord>,
+    /// Lines that failed to parse, in source order
+    pub errors: Vec<EpdParseError>,
+}
+
+/// Parser for a field or unquoted operand, anything up to whitespace or
+/// the `;` ending an operation
+fn word_parser() -> impl Parser<char, String, Error = Simple<char>> + Clone {
+    filter(|c: &char| !c.is_whitespace() && *c != ';')
+        .repeated()
+        .at_least(1)
+        .collect::<String>()
+}
+
+/// Parser for an operand in double quotes, returning it without the quotes
+///
+/// Quotes and backslashes inside it are escaped with a backslash, as in
+/// PGN strings. Any other backslash is kept as it is.
+fn string_operand_parser(
+) -> impl Parser<char, String, Error = Simple<char>> + Clone {
+    choice((
+        just('\\').ignore_then(one_of("\"\\")),
+        filter(|c: &char| *c != '"'),
+    ))
+    .repeated()
+    .delimited_by(just('"'), just('"'))
+    .collect::<String>()
+}
+
+/// Parser for an operation, its opcode and operands ending with `;`
+fn operation_parser() -> impl Parser<char, EpdOperation, Error = Simple<char>> {
+    let opcode = filter(|c: &char| c.is_ascii_alphanumeric() || *c == '_')
+        .repeated()
+        .at_least(1)
+        .collect::<String>()
+        .labelled("opcode");
+    let operand = string_operand_parser().or(word_parser());
+
+    opcode
+        .then(
+            text::whitespace()
+                .at_least(1)
+                .ignore_then(operand)
+                .repeated(),
+        )
+        .then_ignore(text::whitespace())
+        .then_ignore(just(';'))
+        .map(|(opcode, operands)| EpdOperation { opcode, operands })
+        .labelled("operation")
+}
+
+/// Top-level parser for an EPD line
+fn epd_parser() -> impl Parser<char, EpdRecord, Error = Simple<char>> {
+    let separator = || text::whitespace().at_least(1);
+    let side_to_move = one_of("wb").map(String::from).labelled("side to move");
+
+    word_parser()
+        .labelled("piece placement")
+        .then_ignore(separator())
+        .then(side_to_move)
+        .then_ignore(separator())
+        .then(word_parser().labelled("castling rights"))
+        .then_ignore(separator())
+        .then(word_parser().labelled("en passant square"))
+        .map(|(((placement, side), castling), en_passant)| {
+            [placement, side, castling, en_passant].join(" ")
+        })
+        .then(operation_parser().padded().repeated())
+        .padded()
+        .then_ignore(end())
+        .map(|(position, operations)| EpdRecord {
+            position,
+            operations,
+        })
+}
+
+/// Parse one EPD line
+///
+/// # Arguments
+/// * `line` - The line to parse
+/// * `line_number` - The line's number in its source, starting at 1, for
+///   error messages
+pub fn parse_epd_line(
+    line: &str,
+    line_number: usize,
+) -> Result<EpdRecord, EpdParseError> {
+    epd_parser()
+        .parse(line)
+        .map_err(|errors| EpdParseError::ParseFailure {
+            line: line_number,
+            input: line.to_string(),
+            message: errors
+                .iter()
+                .map(|e| e.to_string())
+                .collect::<Vec<String>>()
+                .join(", "),
+        })
+}
+
+/// Parse every record of an EPD source, skipping blank lines
+///
+/// A line that fails to parse is reported and parsing resumes at the next
+/// line.
+pub fn parse_epd(epd: &str) -> EpdParseResult {
+    let mut result = EpdParseResult::default();
+    for (index, line) in epd.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match parse_epd_line(line, index + 1) {
+            Ok(record) => result.records.push(record),
+            Err(e) => result.errors.push(e),
+        }
+    }
+
+    debug!(
+        "Parsed {} EPD records, {} failed",
+        result.records.len(),
+        result.errors.len()
+    );
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_epd_records_and_operations() {
+        let epd = "\
+2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\";
+r1b1k2r/ppppnppp/2n2q2/2b5/3NP3/2P1B3/PP3PPP/RN1QKB1R w KQkq - am Nxc6 Bxc5; bm Nb5 Be2; c0 \"Avoid the exchanges\"; hmvc 4; fmvn 7;
+
+not an epd line
+";
+        let result = parse_epd(epd);
+
+        assert_eq!(result.records.len(), 2);
+        assert!(matches!(
+            result.errors.as_slice(),
+            [EpdParseError::ParseFailure { line: 4, .. }]
+        ));
+
+        let wac = &result.records[0];
+        assert_eq!(
+            wac.position,
+            "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - -"
+        );
+        assert_eq!(wac.best_moves(), ["Qg6"]);
+        assert_eq!(wac.id(), Some("WAC.001"));
+        assert!(wac.avoid_moves().is_empty());
+        assert!(wac.fen().ends_with(" w - - 0 1"));
+
+        let second = &result.records[1];
+        assert_eq!(second.avoid_moves(), ["Nxc6", "Bxc5"]);
+        assert_eq!(second.best_moves(), ["Nb5", "Be2"]);
+        assert_eq!(second.comment(), Some("Avoid the exchanges"));
+        assert!(second.fen().ends_with(" KQkq - 4 7"));
+    }
+}
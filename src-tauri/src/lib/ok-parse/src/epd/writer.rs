@@ -0,0 +1,98 @@
+//! Writes positions as EPD
+//!
+//! The operands of `id` and the comment opcodes `c0` to `c9` are always
+//! strings, so they're written in double quotes. Other operands are only
+//! quoted when they couldn't be read back without them. Quotes and
+//! backslashes inside quoted operands are escaped with a backslash.
+
+use super::EpdRecord;
+
+/// Whether an opcode's operands are strings
+fn has_string_operands(opcode: &str) -> bool {
+    opcode == "id"
+        || (opcode.len() == 2
+            && opcode.starts_with('c')
+            && opcode[1..].chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Write a record as one EPD line, without a line ending
+pub fn write_epd_record(record: &EpdRecord) -> String {
+    let mut line = record.position.clone();
+    for operation in &record.operations {
+        line.push(' ');
+        line.push_str(&operation.opcode);
+        for operand in &operation.operands {
+            line.push(' ');
+            if has_string_operands(&operation.opcode)
+                || operand.is_empty()
+                || operand.contains(|c: char| {
+                    c.is_whitespace() || c == ';' || c == '"'
+                })
+            {
+                line.push('"');
+                line.push_str(
+                    &operand.replace('\\', "\\\\").replace('"', "\\\""),
+                );
+                line.push('"');
+            } else {
+                line.push_str(operand);
+            }
+        }
+        line.push(';');
+    }
+    line
+}
+
+/// Write records as EPD, one line each
+pub fn write_epd(records: &[EpdRecord]) -> String {
+    records
+        .iter()
+        .map(|record| write_epd_record(record) + "\n")
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::epd::parse_epd_line;
+
+    #[test]
+    fn written_records_parse_back_the_same() {
+        let mut record = EpdRecord::new(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -",
+        );
+        record.set_operation("bm", vec!["e4".to_string(), "d4".to_string()]);
+        record.set_operation("id", vec!["start".to_string()]);
+        record.set_operation("c0", vec!["Best by test".to_string()]);
+        record.set_operation("bm", vec!["e4".to_string()]);
+
+        let line = write_epd_record(&record);
+        assert_eq!(
+            line,
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - \
+             bm e4; id \"start\"; c0 \"Best by test\";"
+        );
+        assert_eq!(parse_epd_line(&line, 1).unwrap(), record);
+    }
+
+    #[test]
+    fn quotes_in_operands_are_escaped() {
+        let mut record = EpdRecord::new("8/8/8/8/8/8/8/K1k5 w - -");
+        record.set_operation("id", vec!["\"Sicilian\" \\ 1".to_string()]);
+        record.set_operation("c0", vec!["say \"hi\";".to_string()]);
+        record.set_operation("pv", vec!["a\"b".to_string()]);
+
+        let line = write_epd_record(&record);
+        assert_eq!(
+            line,
+            "8/8/8/8/8/8/8/K1k5 w - - id \"\\\"Sicilian\\\" \\\\ 1\"; \
+             c0 \"say \\\"hi\\\";\"; pv \"a\\\"b\";"
+        );
+        assert_eq!(parse_epd_line(&line, 1).unwrap(), record);
+
+        // A backslash that doesn't escape anything is read as it is
+        let record =
+            parse_epd_line(r#"8/8/8/8/8/8/8/K1k5 w - - c0 "C:\tests";"#, 1);
+        assert_eq!(record.unwrap().comment(), Some("C:\\tests"));
+    }
+}
@@ -0,0 +1,138 @@
+//! chess.com game archives
+//!
+//! chess.com archives a player's games by month as a JSON object like
+//! `{"games": [{"url": ..., "pgn": ..., "white": {...}, ...}]}`. Each game
+//! includes its PGN, with clocks as `[%clk]` comments, along with the
+//! players' ratings and accuracies.
+
+use serde::{Deserialize, Serialize};
+
+use super::{JsonParseError, JsonParseResult};
+
+/// A game from a chess.com archive
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChessComGame {
+    pub url: Option<String>,
+    /// The game as PGN
+    pub pgn: Option<String>,
+    /// The time control in seconds, like "180+2" or "1/86400" for daily
+    /// games
+    pub time_control: Option<String>,
+    /// When the game ended, in seconds since the Unix epoch
+    pub end_time: Option<i64>,
+    pub rated: Option<bool>,
+    pub accuracies: Option<ChessComAccuracies>,
+    /// The speed, like "blitz" or "daily"
+    pub time_class: Option<String>,
+    /// The variant, like "chess", "chess960" or "kingofthehill"
+    pub rules: Option<String>,
+    pub white: ChessComPlayer,
+    pub black: ChessComPlayer,
+    /// URL of the opening's page, like
+    /// `https://www.chess.com/openings/Sicilian-Defense-Closed`
+    pub eco: Option<String>,
+    /// The starting position
+    pub initial_setup: Option<String>,
+}
+
+/// A player of a chess.com game
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChessComPlayer {
+    pub username: String,
+    /// Rating after the game
+    pub rating: Option<i32>,
+    /// How the game ended for the player, like "win", "resigned" or
+    /// "agreed"
+    pub result: Option<String>,
+}
+
+/// Each player's accuracy as a percentage
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChessComAccuracies {
+    pub white: f64,
+    pub black: f64,
+}
+
+impl ChessComGame {
+    /// The opening's name from the URL of its page, like "Sicilian
+    /// Defense Closed"
+    pub fn opening_name(&self) -> Option<String> {
+        let url = self.eco.as_deref()?;
+        let name = url.trim_end_matches('/').rsplit('/').next()?;
+        (!name.is_empty()).then(|| name.replace('-', " "))
+    }
+}
+
+/// The ways a chess.com archive can be saved: the object returned for a
+/// month, or just its list of games
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ChessComArchive {
+    Month { games: Vec<serde_json::Value> },
+    Games(Vec<serde_json::Value>),
+}
+
+/// Parse the games of a chess.com archive
+///
+/// A game that fails to parse is reported, with its index in the archive,
+/// and parsing resumes at the next game. Only an archive that
+/// isn't a list of games fails as a whole.
+pub fn parse_chesscom_archive(
+    json: &str
+) -> Result<JsonParseResult<ChessComGame>, JsonParseError> {
+    let archive: ChessComArchive = serde_json::from_str(json)
+        .map_err(|e| JsonParseError::InvalidArchive(e.to_string()))?;
+    let (ChessComArchive::Month { games } | ChessComArchive::Games(games)) =
+        archive;
+
+    let mut result = JsonParseResult::default();
+    for (index, game) in games.into_iter().enumerate() {
+        match serde_json::from_value(game) {
+            Ok(game) => result.games.push((index, game)),
+            Err(e) => result.errors.push(JsonParseError::InvalidArchiveGame {
+                index,
+                message: e.to_string(),
+            }),
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_chesscom_archive_games() {
+        let json = r#"{"games": [
+            {"url": "https://www.chess.com/game/live/1", "pgn": "[Event \"Live Chess\"]\n\n1. e4 {[%clk 0:02:59.9]} 1-0", "time_control": "180", "end_time": 1700000000, "rated": true, "accuracies": {"white": 83.5, "black": 77.1}, "time_class": "blitz", "rules": "chess", "white": {"rating": 1500, "result": "win", "username": "alice"}, "black": {"rating": 1480, "result": "resigned", "username": "bob"}, "eco": "https://www.chess.com/openings/Kings-Pawn-Opening"},
+            {"url": 7}
+        ]}"#;
+        let result = parse_chesscom_archive(json).unwrap();
+        assert_eq!(result.games.len(), 1);
+        assert!(matches!(
+            result.errors.as_slice(),
+            [JsonParseError::InvalidArchiveGame { index: 1, .. }]
+        ));
+
+        let (index, game) = &result.games[0];
+        assert_eq!(*index, 0);
+        assert_eq!(game.white.username, "alice");
+        assert_eq!(game.black.rating, Some(1480));
+        assert_eq!(game.accuracies.as_ref().unwrap().white, 83.5);
+        assert_eq!(game.opening_name().as_deref(), Some("Kings Pawn Opening"));
+
+        let games_only = parse_chesscom_archive(r#"[{"rules": "chess960"}]"#);
+        assert_eq!(
+            games_only.unwrap().games[0].1.rules.as_deref(),
+            Some("chess960")
+        );
+        assert!(matches!(
+            parse_chesscom_archive("{}"),
+            Err(JsonParseError::InvalidArchive(_))
+        ));
+    }
+}
@@ -0,0 +1,260 @@
+//! lichess game exports
+//!
+//! lichess exports games as NDJSON, each line a JSON object like
+//! `{"id": "q7ZvsdUF", "players": {...}, "moves": "d4 d5 c4 c6", ...}`.
+//! Clocks, evaluations and the opening are only included when asked for
+//! when exporting.
+
+use serde::{Deserialize, Serialize};
+
+use super::{JsonParseError, JsonParseResult};
+
+/// A game exported from lichess
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LichessGame {
+    /// The game's ID, its URL is `https://lichess.org/<id>`
+    pub id: String,
+    pub rated: bool,
+    /// The variant's key, like "standard", "chess960" or "kingOfTheHill"
+    pub variant: Option<String>,
+    /// The speed, like "blitz" or "correspondence"
+    pub speed: Option<String>,
+    /// When the game started, in milliseconds since the Unix epoch
+    pub created_at: Option<i64>,
+    /// How the game ended, like "mate", "resign" or "outoftime"
+    pub status: Option<String>,
+    pub players: LichessPlayers,
+    /// The winning side, "white" or "black", unless the game was drawn or
+    /// didn't end
+    pub winner: Option<String>,
+    pub opening: Option<LichessOpening>,
+    /// The moves in SAN, separated by spaces
+    pub moves: String,
+    /// Each side's clock after each move, in centiseconds
+    pub clocks: Vec<u32>,
+    /// The server's analysis of the position after each move
+    pub analysis: Vec<LichessEval>,
+    pub clock: Option<LichessClock>,
+    /// The starting position of games that don't start from the standard
+    /// one
+    pub initial_fen: Option<String>,
+    /// ID of the arena tournament the game was played in
+    pub tournament: Option<String>,
+    /// ID of the swiss tournament the game was played in
+    pub swiss: Option<String>,
+}
+
+/// The players of a lichess game
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LichessPlayers {
+    pub white: LichessPlayer,
+    pub black: LichessPlayer,
+}
+
+/// A player of a lichess game
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LichessPlayer {
+    /// The account playing, missing for the lichess AI and anonymous
+    /// players
+    pub user: Option<LichessUser>,
+    /// Rating before the game
+    pub rating: Option<i32>,
+    /// How much the game changed the rating
+    pub rating_diff: Option<i32>,
+    /// Level of the lichess AI, if it was the player
+    pub ai_level: Option<u8>,
+    pub analysis: Option<LichessPlayerAnalysis>,
+}
+
+/// A lichess account
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LichessUser {
+    pub name: String,
+    /// FIDE or lichess title, like "GM" or "BOT"
+    pub title: Option<String>,
+}
+
+/// How well a player played according to the server's analysis
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LichessPlayerAnalysis {
+    pub inaccuracy: u32,
+    pub mistake: u32,
+    pub blunder: u32,
+    /// Average centipawn loss
+    pub acpl: Option<u32>,
+    /// Accuracy as a percentage
+    pub accuracy: Option<u32>,
+}
+
+/// The opening of a lichess game
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LichessOpening {
+    pub eco: Option<String>,
+    /// The opening's name, like "Semi-Slav Defense: Marshall Gambit"
+    pub name: Option<String>,
+    /// Number of plies the opening lasted
+    pub ply: Option<u32>,
+}
+
+/// The server's evaluation of a position, from White's point of view
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LichessEval {
+    /// Score in centipawns
+    pub eval: Option<i32>,
+    /// Moves to mate, negative when Black mates
+    pub mate: Option<i32>,
+    /// The best move in UCI, given when the move played was a mistake
+    pub best: Option<String>,
+    /// The line that should have been played, in SAN
+    pub variation: Option<String>,
+    pub judgment: Option<LichessJudgment>,
+}
+
+/// How bad a move was, like "Mistake"
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LichessJudgment {
+    pub name: String,
+    /// Explanation, like "Mistake. Nf3 was best."
+    pub comment: Option<String>,
+}
+
+/// A lichess game's time control
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LichessClock {
+    /// Starting time in seconds
+    pub initial: u32,
+    /// Seconds added after each move
+    pub increment: u32,
+}
+
+impl LichessGame {
+    /// The game's PGN result: "1-0", "0-1", "1/2-1/2" or "*"
+    pub fn result(&self) -> &'static str {
+        match (self.winner.as_deref(), self.status.as_deref()) {
+            (Some("white"), _) => "1-0",
+            (Some("black"), _) => "0-1",
+            (
+                None,
+                None
+                | Some("created" | "started" | "aborted" | "noStart")
+                | Some("unknownFinish"),
+            ) => "*",
+            _ => "1/2-1/2",
+        }
+    }
+
+    /// The time control the way PGN writes it, like "300+3", or "-" for
+    /// games without a clock
+    pub fn time_control(&self) -> String {
+        match &self.clock {
+            Some(clock) => format!("{}+{}", clock.initial, clock.increment),
+            None => "-".to_string(),
+        }
+    }
+
+    /// The PGN `Termination` of the game
+    pub fn termination(&self) -> &'static str {
+        match self.status.as_deref() {
+            Some("outoftime" | "timeout") => "Time forfeit",
+            Some("cheat") => "Rules infraction",
+            Some("aborted" | "noStart") => "Abandoned",
+            Some("created" | "started") => "Unterminated",
+            _ => "Normal",
+        }
+    }
+}
+
+impl LichessPlayer {
+    /// The player's name, or the lichess AI's name with its level
+    pub fn name(&self) -> String {
+        match (&self.user, self.ai_level) {
+            (Some(user), _) => user.name.clone(),
+            (None, Some(level)) => format!("lichess AI level {}", level),
+            (None, None) => "Anonymous".to_string(),
+        }
+    }
+}
+
+/// Parse one line of a lichess NDJSON export
+///
+/// # Arguments
+/// * `line` - The line to parse
+/// * `line_number` - The line's number in the export, starting at 1, for
+///   error messages
+pub fn parse_lichess_game(
+    line: &str,
+    line_number: usize,
+) -> Result<LichessGame, JsonParseError> {
+    serde_json::from_str(line).map_err(|e| JsonParseError::InvalidGame {
+        line: line_number,
+        message: e.to_string(),
+    })
+}
+
+/// Parse every game of a lichess NDJSON export, skipping blank lines
+///
+/// A line that fails to parse is reported and parsing resumes at the next
+/// line.
+pub fn parse_lichess_ndjson(ndjson: &str) -> JsonParseResult<LichessGame> {
+    let mut result = JsonParseResult::default();
+    for (index, line) in ndjson.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match parse_lichess_game(line, index + 1) {
+            Ok(game) => result.games.push((index, game)),
+            Err(e) => result.errors.push(e),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pgn::parse_movetext;
+
+    #[test]
+    fn parse_lichess_export() {
+        let ndjson = r#"{"id":"q7ZvsdUF","rated":true,"variant":"standard","speed":"blitz","perf":"blitz","createdAt":1514505150384,"lastMoveAt":1514505592843,"status":"resign","players":{"white":{"user":{"name":"Lance5500","title":"LM","patron":true,"id":"lance5500"},"rating":2389,"ratingDiff":4,"analysis":{"inaccuracy":2,"mistake":1,"blunder":0,"acpl":25,"accuracy":91}},"black":{"aiLevel":5}},"winner":"white","opening":{"eco":"D31","name":"Semi-Slav Defense: Marshall Gambit","ply":7},"moves":"d4 d5 c4 c6","clocks":[30003,30003,29803,29503],"analysis":[{"eval":18},{"eval":-4},{"mate":3},{"eval":-101,"best":"g8f6","variation":"Nf6 Nc3","judgment":{"name":"Mistake","comment":"Mistake. Nf6 was best."}}],"clock":{"initial":300,"increment":3,"totalTime":420}}
+
+not json
+{"id":"abc","moves":"","status":"aborted","players":{"white":{},"black":{}}}
+"#;
+        let JsonParseResult { games, errors } = parse_lichess_ndjson(ndjson);
+        assert_eq!(games.len(), 2);
+        assert!(matches!(
+            errors.as_slice(),
+            [JsonParseError::InvalidGame { line: 3, .. }]
+        ));
+
+        assert_eq!(games[1].0, 3);
+        let (_, game) = &games[0];
+        let white = &game.players.white;
+        assert_eq!(white.name(), "Lance5500");
+        assert_eq!(white.user.as_ref().unwrap().title.as_deref(), Some("LM"));
+        assert_eq!(white.rating, Some(2389));
+        assert_eq!(white.analysis.as_ref().unwrap().accuracy, Some(91));
+        assert_eq!(game.players.black.name(), "lichess AI level 5");
+        assert_eq!(game.result(), "1-0");
+        assert_eq!(game.time_control(), "300+3");
+        assert_eq!(game.termination(), "Normal");
+        assert_eq!(game.clocks.len(), 4);
+        let moves = parse_movetext(&game.moves).unwrap().moves;
+        assert_eq!(moves.len(), 4);
+        assert_eq!(game.analysis[2].mate, Some(3));
+        assert_eq!(game.analysis[3].judgment.as_ref().unwrap().name, "Mistake");
+
+        assert_eq!(games[1].1.result(), "*");
+        assert_eq!(games[1].1.termination(), "Abandoned");
+    }
+}
@@ -0,0 +1,54 @@
+//! Game exports in JSON
+//!
+//! Online servers export games as JSON along with data PGN has no tags
+//! for, like each player's accuracy. lichess exports NDJSON, one game per
+//! line, and chess.com monthly archives of a player's games.
+
+use serde::Serialize;
+
+pub mod chesscom;
+pub mod lichess;
+
+pub use chesscom::{parse_chesscom_archive, ChessComGame};
+pub use lichess::{parse_lichess_game, parse_lichess_ndjson, LichessGame};
+
+/// A JSON game export that failed to parse
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error, Serialize)]
+pub enum JsonParseError {
+    /// A line of an NDJSON export isn't a valid game
+    #[error("Invalid game on line {line}: {message}")]
+    InvalidGame {
+        /// Line number, starting at 1
+        line: usize,
+        message: String,
+    },
+    /// A game of a JSON archive isn't a valid game
+    #[error("Invalid game at index {index} of the archive: {message}")]
+    InvalidArchiveGame {
+        /// Index of the game in the archive, starting at 0
+        index: usize,
+        message: String,
+    },
+    /// A JSON archive isn't a valid list of games
+    #[error("Invalid game archive: {0}")]
+    InvalidArchive(String),
+}
+
+/// The games parsed from a JSON export along with those that failed
+#[derive(Debug, Clone, Serialize)]
+pub struct JsonParseResult<T> {
+    /// Successfully parsed games, in source order, each with its index in
+    /// the source starting at 0
+    pub games: Vec<(usize, T)>,
+    /// Games that failed to parse, in source order
+    pub errors: Vec<JsonParseError>,
+}
+
+impl<T> Default for JsonParseResult<T> {
+    fn default() -> Self {
+        Self {
+            games: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+}
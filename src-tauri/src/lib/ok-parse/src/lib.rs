@@ -1,4 +1,8 @@
+pub mod encoding;
+pub mod epd;
+pub mod json;
 pub mod pgn;
+pub mod scid;
 pub mod uci;
 
 pub const DEBUG: bool = true;
@@ -10,4 +14,10 @@ pub enum ParseError {
     PgnParseError(#[from] pgn::PgnParseError),
     #[error("UCI parse error: {0}")]
     UciParseError(#[from] uci::UciParseError),
+    #[error("EPD parse error: {0}")]
+    EpdParseError(#[from] epd::EpdParseError),
+    #[error("JSON parse error: {0}")]
+    JsonParseError(#[from] json::JsonParseError),
+    #[error("SCID parse error: {0}")]
+    ScidError(#[from] scid::ScidError),
 }
@@ -0,0 +1,401 @@
+//! Commands embedded in PGN comments, like `{[%clk 0:05:00] Good move}`
+//!
+//! Supported commands:
+//! - `[%clk H:MM:SS]` clock time left after the move
+//! - `[%emt H:MM:SS]` time spent on the move
+//! - `[%eval 0.17]`, `[%eval #-3]` or `[%eval 0.17,20]` with a search depth
+//! - `[%cal Ge2e4,Rd1d8]` coloured arrows
+//! - `[%csl Gd4,Re5]` coloured square highlights
+//!
+//! Unknown or malformed commands are left in the comment text.
+
+use chumsky::prelude::*;
+use serde::Serialize;
+
+/// An engine evaluation from an `[%eval]` command
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum EvalScore {
+    /// Score in centipawns from white's point of view
+    Centipawns(i32),
+    /// Mate in this many moves, negative when black mates
+    Mate(i32),
+}
+
+/// An `[%eval]` command
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct CommentEval {
+    pub score: EvalScore,
+    pub depth: Option<u32>,
+}
+
+/// A coloured arrow from a `[%cal]` command, like "Ge2e4"
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Arrow {
+    /// Colour letter, usually one of R, G, B or Y
+    pub colour: char,
+    pub from: String,
+    pub to: String,
+}
+
+/// A coloured square from a `[%csl]` command, like "Rd4"
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SquareHighlight {
+    /// Colour letter, usually one of R, G, B or Y
+    pub colour: char,
+    pub square: String,
+}
+
+/// A comment split into its commands and the text around them
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CommentCommands {
+    /// Clock time left after the move in milliseconds (`%clk`)
+    pub clock_ms: Option<u64>,
+    /// Time spent on the move in milliseconds (`%emt`)
+    pub elapsed_ms: Option<u64>,
+    /// Evaluation of the position after the move (`%eval`)
+    pub eval: Option<CommentEval>,
+    /// Arrows drawn on the board (`%cal`)
+    pub arrows: Vec<Arrow>,
+    /// Highlighted squares (`%csl`)
+    pub highlights: Vec<SquareHighlight>,
+    /// The human written part of the comment
+    pub text: String,
+}
+
+impl CommentCommands {
+    /// Whether the comment had any commands
+    pub fn has_commands(&self) -> bool {
+        self.clock_ms.is_some()
+            || self.elapsed_ms.is_some()
+            || self.eval.is_some()
+            || !self.arrows.is_empty()
+            || !self.highlights.is_empty()
+    }
+
+    /// Apply a single command, returning false if it isn't understood
+    fn apply(
+        &mut self,
+        name: &str,
+        args: &str,
+    ) -> bool {
+        let args = args.trim();
+        match name {
+            "clk" => parse_clock(args).map(|ms| self.clock_ms = Some(ms)),
+            "emt" => parse_clock(args).map(|ms| self.elapsed_ms = Some(ms)),
+            "eval" => parse_eval(args).map(|eval| self.eval = Some(eval)),
+            "cal" => parse_list(args).map(|arrows| self.arrows.extend(arrows)),
+            "csl" => parse_list(args)
+                .map(|highlights| self.highlights.extend(highlights)),
+            _ => None,
+        }
+        .is_some()
+    }
+}
+
+impl std::fmt::Display for CommentCommands {
+    /// Writes the comment back out with its commands first
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(ms) = self.clock_ms {
+            parts.push(format!("[%clk {}]", format_clock(ms)));
+        }
+        if let Some(ms) = self.elapsed_ms {
+            parts.push(format!("[%emt {}]", format_clock(ms)));
+        }
+        if let Some(eval) = &self.eval {
+            parts.push(format!("[%eval {}]", eval));
+        }
+        if !self.arrows.is_empty() {
+            parts.push(format!("[%cal {}]", join(&self.arrows)));
+        }
+        if !self.highlights.is_empty() {
+            parts.push(format!("[%csl {}]", join(&self.highlights)));
+        }
+        if !self.text.is_empty() {
+            parts.push(self.text.clone());
+        }
+        write!(f, "{}", parts.join(" "))
+    }
+}
+
+impl std::fmt::Display for CommentEval {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        match self.score {
+            EvalScore::Centipawns(cp) => write!(f, "{:.2}", cp as f64 / 100.0)?,
+            EvalScore::Mate(moves) => write!(f, "#{}", moves)?,
+        }
+        if let Some(depth) = self.depth {
+            write!(f, ",{}", depth)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Arrow {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        write!(f, "{}{}{}", self.colour, self.from, self.to)
+    }
+}
+
+impl std::fmt::Display for SquareHighlight {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        write!(f, "{}{}", self.colour, self.square)
+    }
+}
+
+/// Part of a comment, either a command or a single character of text
+enum Segment {
+    Command { name: String, args: String },
+    Text(char),
+}
+
+/// Parse a `[%name args]` command or a character of text
+fn segment_parser() -> impl Parser<char, Vec<Segment>, Error = Simple<char>> {
+    let command = just("[%")
+        .ignore_then(
+            filter(|c: &char| c.is_ascii_alphanumeric() || *c == '_')
+                .repeated()
+                .at_least(1)
+                .collect::<String>(),
+        )
+        .then(filter(|&c| c != ']').repeated().collect::<String>())
+        .then_ignore(just(']'))
+        .map(|(name, args)| Segment::Command { name, args });
+
+    choice((command, any().map(Segment::Text)))
+        .repeated()
+        .then_ignore(end())
+}
+
+/// Split the text of a comment into its commands and human written text
+pub fn parse_comment_commands(text: &str) -> CommentCommands {
+    let mut commands = CommentCommands::default();
+    let mut remaining = String::new();
+
+    // Every character is accepted as text, so this can't fail
+    let segments = segment_parser().parse(text).unwrap_or_default();
+    for segment in segments {
+        match segment {
+            Segment::Text(c) => remaining.push(c),
+            Segment::Command { name, args } => {
+                if !commands.apply(&name, &args) {
+                    remaining.push_str(&format!("[%{}{}]", name, args));
+                }
+                // Keep the text on either side of the command apart
+                remaining.push(' ');
+            }
+        }
+    }
+
+    commands.text = remaining.split_whitespace().collect::<Vec<_>>().join(" ");
+    commands
+}
+
+/// Parse a clock time like "1:05:30", "5:30" or "0:00:07.5" into milliseconds
+fn parse_clock(time: &str) -> Option<u64> {
+    let parts: Vec<&str> = time.split(':').collect();
+    if parts.len() > 3 {
+        return None;
+    }
+
+    let (seconds, whole) = parts.split_last()?;
+    let mut ms = parse_seconds(seconds)?;
+    // Times too large to hold are malformed
+    for (unit, part) in whole.iter().rev().enumerate() {
+        let value: u64 = part.parse().ok()?;
+        let unit_ms = 60_000 * 60u64.pow(unit as u32);
+        ms = ms.checked_add(value.checked_mul(unit_ms)?)?;
+    }
+    Some(ms)
+}
+
+/// Parse seconds with an optional fraction like "07.5" into milliseconds
+fn parse_seconds(seconds: &str) -> Option<u64> {
+    let (whole, fraction) = seconds.split_once('.').unwrap_or((seconds, ""));
+    if !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let fraction_ms = format!("{:0<3}", &fraction[..fraction.len().min(3)]);
+    whole
+        .parse::<u64>()
+        .ok()?
+        .checked_mul(1000)?
+        .checked_add(fraction_ms.parse().ok()?)
+}
+
+/// Format milliseconds as a clock time like "1:05:30" or "0:00:07.5"
+fn format_clock(ms: u64) -> String {
+    let seconds = ms / 1000;
+    let mut clock = format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    );
+    let fraction = ms % 1000;
+    if fraction != 0 {
+        clock.push_str(format!(".{:03}", fraction).trim_end_matches('0'));
+    }
+    clock
+}
+
+/// Parse an evaluation like "0.17", "-1.5", "#-3" or "0.17,20"
+fn parse_eval(eval: &str) -> Option<CommentEval> {
+    let (score, depth) = match eval.split_once(',') {
+        Some((score, depth)) => {
+            (score.trim(), Some(depth.trim().parse().ok()?))
+        }
+        None => (eval, None),
+    };
+
+    let score = match score.strip_prefix('#') {
+        Some(moves) => {
+            EvalScore::Mate(moves.trim_start_matches('+').parse().ok()?)
+        }
+        None => {
+            let pawns: f64 = score.parse().ok()?;
+            if !pawns.is_finite() {
+                return None;
+            }
+            EvalScore::Centipawns((pawns * 100.0).round() as i32)
+        }
+    };
+
+    Some(CommentEval { score, depth })
+}
+
+/// Parse a comma separated list like "Ge2e4,Rd1d8", failing if any item
+/// is invalid
+pub fn parse_list<T: std::str::FromStr>(list: &str) -> Option<Vec<T>> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| entry.parse().ok())
+        .collect()
+}
+
+impl std::str::FromStr for Arrow {
+    type Err = ();
+
+    /// Parse an arrow like "Ge2e4"
+    fn from_str(arrow: &str) -> Result<Self, Self::Err> {
+        let mut chars = arrow.chars();
+        let colour = chars.next().filter(char::is_ascii_uppercase).ok_or(())?;
+        let squares = chars.as_str();
+        // Squares are ASCII, so this also keeps the split on a character
+        if squares.len() != 4 || !squares.is_ascii() {
+            return Err(());
+        }
+        let (from, to) = squares.split_at(2);
+        if !is_square(from) || !is_square(to) {
+            return Err(());
+        }
+        Ok(Arrow {
+            colour,
+            from: from.to_string(),
+            to: to.to_string(),
+        })
+    }
+}
+
+impl std::str::FromStr for SquareHighlight {
+    type Err = ();
+
+    /// Parse a square highlight like "Rd4"
+    fn from_str(highlight: &str) -> Result<Self, Self::Err> {
+        let mut chars = highlight.chars();
+        let colour = chars.next().filter(char::is_ascii_uppercase).ok_or(())?;
+        let square = chars.as_str();
+        if !is_square(square) {
+            return Err(());
+        }
+        Ok(SquareHighlight {
+            colour,
+            square: square.to_string(),
+        })
+    }
+}
+
+fn is_square(square: &str) -> bool {
+    matches!(square.as_bytes(), [b'a'..=b'h', b'1'..=b'8'])
+}
+
+fn join<T: ToString>(items: &[T]) -> String {
+    items
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_are_separated_from_text() {
+        let commands = parse_comment_commands(
+            " [%clk 1:05:30.5] [%eval -0.35,18] Bad move [%emt 0:00:07] ",
+        );
+
+        assert_eq!(commands.clock_ms, Some(3_930_500));
+        assert_eq!(commands.elapsed_ms, Some(7_000));
+        assert_eq!(
+            commands.eval,
+            Some(CommentEval {
+                score: EvalScore::Centipawns(-35),
+                depth: Some(18),
+            })
+        );
+        assert_eq!(commands.text, "Bad move");
+    }
+
+    #[test]
+    fn arrows_highlights_and_mates_are_parsed() {
+        let commands =
+            parse_comment_commands("[%cal Ge2e4,Rd1d8][%csl Yd4] [%eval #-3]");
+
+        assert_eq!(commands.arrows.len(), 2);
+        assert_eq!(commands.arrows[1].to_string(), "Rd1d8");
+        assert_eq!(commands.highlights[0].square, "d4");
+        assert_eq!(commands.eval.map(|e| e.score), Some(EvalScore::Mate(-3)));
+        assert_eq!(commands.text, "");
+    }
+
+    #[test]
+    fn unknown_and_malformed_commands_stay_in_the_text() {
+        let commands =
+            parse_comment_commands("[%clk soon] [%foo bar] [%cal Gz9e4] ok");
+
+        assert!(!commands.has_commands());
+        assert_eq!(commands.text, "[%clk soon] [%foo bar] [%cal Gz9e4] ok");
+
+        // Neither a square split inside a character nor a clock too large
+        // to hold is parsed
+        let text = "[%cal Gaé4] [%clk 999999999999999999:00:00]";
+        let commands = parse_comment_commands(text);
+        assert!(!commands.has_commands());
+        assert_eq!(commands.text, text);
+    }
+
+    #[test]
+    fn commands_are_written_back_out() {
+        let text = "[%clk 0:00:07.5] [%eval 0.17,20] [%cal Ge2e4] Nice";
+        let commands = parse_comment_commands(text);
+
+        assert_eq!(commands.to_string(), text);
+        assert_eq!(parse_comment_commands(&commands.to_string()), commands);
+    }
+}
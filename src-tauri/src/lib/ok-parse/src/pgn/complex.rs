@@ -1,48 +1,145 @@
 use super::simple::*;
-use super::{PgnGame, PgnParseError, PgnToken};
+use super::{
+    PgnGame, PgnParseError, PgnToken, PgnWarning, SourceExcerpt, SourceSpan,
+};
 use chumsky::prelude::*;
+use serde::{Deserialize, Serialize};
 
 /// Parse variations in parentheses (recursive)
 pub fn variation_parser() -> impl Parser<char, PgnToken, Error = Simple<char>> {
     recursive(|variation| {
         choice((
             move_number_parser(),
+            evaluation_symbol_parser(),
             chess_move_parser(),
             move_suffix_parser(),
             nag_parser(),
             comment_parser(),
+            line_comment_parser(),
             variation,
         ))
         .padded()
         .repeated()
         .delimited_by(just('('), just(')'))
-        .map(|moves| PgnToken::Variation { moves })
+        .map_with_span(|moves, span| PgnToken::Variation {
+            moves,
+            span: SourceSpan::unresolved(span),
+        })
     })
     .labelled("variation")
 }
 
+/// The Seven Tag Roster, in the order it's written, with the value used
+/// when a game doesn't have the tag
+pub(crate) const SEVEN_TAG_ROSTER: [(&str, &str); 7] = [
+    ("Event", "?"),
+    ("Site", "?"),
+    ("Date", "????.??.??"),
+    ("Round", "?"),
+    ("White", "?"),
+    ("Black", "?"),
+    ("Result", "*"),
+];
+
+/// Which tags a game must have to be accepted
+///
+/// Games missing a Seven Tag Roster tag that isn't required are given
+/// the tag's default value, like "?" or "????.??.??", along with a
+/// warning.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", content = "tags", rename_all = "snake_case")]
+pub enum ValidationPolicy {
+    /// Every Seven Tag Roster tag is required
+    #[default]
+    Strict,
+    /// No tags are required
+    Lenient,
+    /// Only the listed tags are required
+    RequiredTags(Vec<String>),
+}
+
+impl ValidationPolicy {
+    /// Whether a game must have the tag
+    pub fn requires(
+        &self,
+        tag_name: &str,
+    ) -> bool {
+        match self {
+            ValidationPolicy::Strict => SEVEN_TAG_ROSTER
+                .iter()
+                .any(|(roster_tag, _)| *roster_tag == tag_name),
+            ValidationPolicy::Lenient => false,
+            ValidationPolicy::RequiredTags(tags) => {
+                tags.iter().any(|tag| tag == tag_name)
+            }
+        }
+    }
+}
+
 /// Validate a PGN game structure
-pub fn validate_game(game: &PgnGame) -> Result<(), PgnParseError> {
-    // Check for required tags
-    let required_tags =
-        ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+///
+/// Missing tags that the policy doesn't require are filled in with their
+/// default value and recorded in the game's warnings.
+pub fn validate_game(
+    game: &mut PgnGame,
+    policy: &ValidationPolicy,
+) -> Result<(), PgnParseError> {
+    if let ValidationPolicy::RequiredTags(tags) = policy {
+        for tag_name in tags {
+            if game.get_tag(tag_name).is_none() {
+                return Err(PgnParseError::MissingRequiredTag {
+                    tag_name: tag_name.clone(),
+                    span: game.span,
+                    excerpt: SourceExcerpt::default(),
+                });
+            }
+        }
+    }
 
-    for tag_name in &required_tags {
-        if game.get_tag(tag_name).is_none() {
+    for (tag_name, default) in SEVEN_TAG_ROSTER {
+        if game.get_tag(tag_name).is_some() {
+            continue;
+        }
+        if policy.requires(tag_name) {
             return Err(PgnParseError::MissingRequiredTag {
                 tag_name: tag_name.to_string(),
+                span: game.span,
+                excerpt: SourceExcerpt::default(),
             });
         }
+
+        // The result can be taken from the end of the movetext
+        let value = match tag_name {
+            "Result" => game.result.as_deref().unwrap_or(default),
+            _ => default,
+        }
+        .to_string();
+        let span =
+            SourceSpan::at(game.span.start, game.span.line, game.span.column);
+        game.warnings.push(PgnWarning::MissingTag {
+            tag_name: tag_name.to_string(),
+            value: value.clone(),
+            span,
+        });
+        game.tags.push(PgnToken::Tag {
+            name: tag_name.to_string(),
+            value,
+            span,
+        });
     }
 
     // Validate result format
-    if let Some(result) = &game.result {
-        match result.as_str() {
-            "1-0" | "0-1" | "1/2-1/2" | "*" => {}
-            _ => {
-                return Err(PgnParseError::InvalidResult {
-                    result: result.clone(),
-                })
+    for token in &game.moves {
+        if let PgnToken::Result { result, span } = token {
+            match result.as_str() {
+                "1-0" | "0-1" | "1/2-1/2" | "*" => {}
+                _ => {
+                    return Err(PgnParseError::InvalidResult {
+                        result: result.clone(),
+                        span: *span,
+                        excerpt: SourceExcerpt::default(),
+                    })
+                }
             }
         }
     }
@@ -1,49 +1,103 @@
 // Module for parsing PGN files
+use chumsky::error::SimpleReason;
 use chumsky::prelude::*;
 use log::debug;
 use serde::Serialize;
 
 use crate::DEBUG;
+use span::SourceMap;
 
+pub mod commands;
 pub mod complex;
+pub mod nag;
+pub mod reader;
+pub mod san;
 pub mod simple;
+pub mod span;
 pub mod util;
+pub mod writer;
+
+pub use commands::{parse_comment_commands, CommentCommands};
+pub use complex::ValidationPolicy;
+pub use reader::PgnReader;
+pub use san::SanMove;
+pub use span::{SourceExcerpt, SourceSpan};
+pub use writer::write_pgn_game;
 
 /// Represents a token in a PGN file
+///
+/// Every token records the part of the source it was parsed from.
 #[derive(Debug, Clone, Serialize)]
 #[serde(tag = "token_type")]
 pub enum PgnToken {
     /// Move numbers like "1."
     #[serde(rename = "move_number")]
-    MoveNumber { number: u32 },
+    MoveNumber { number: u32, span: SourceSpan },
 
     /// Chess moves like "e4" or "Nf3"
     #[serde(rename = "move")]
-    Move { notation: String },
+    Move { san: SanMove, span: SourceSpan },
 
     /// Move suffix notation like "??", "?!", "!?", "!!"
     #[serde(rename = "move_suffix")]
-    MoveSuffixNotation { suffix: String },
+    MoveSuffixNotation { suffix: String, span: SourceSpan },
 
     /// Game result like "1-0", "0-1", "1/2-1/2", "*"
     #[serde(rename = "result")]
-    Result { result: String },
+    Result { result: String, span: SourceSpan },
 
     /// Metadata in square brackets like [Event "World Championship"]
     #[serde(rename = "tag")]
-    Tag { name: String, value: String },
+    Tag {
+        name: String,
+        value: String,
+        span: SourceSpan,
+    },
 
     /// Comments in curly braces like {This is a comment}
     #[serde(rename = "comment")]
-    Comment { text: String },
+    Comment { text: String, span: SourceSpan },
 
     /// Variations in parentheses like (1.e4 e5 2.Nf3)
     #[serde(rename = "variation")]
-    Variation { moves: Vec<PgnToken> },
+    Variation {
+        moves: Vec<PgnToken>,
+        span: SourceSpan,
+    },
 
-    /// Numeric Annotation Glyphs like $1, $2, etc.
+    /// Numeric Annotation Glyphs like $1, $2, etc., also parsed from
+    /// evaluation symbols like "+-"
     #[serde(rename = "nag")]
-    NAG { code: u8 },
+    NAG { code: u8, span: SourceSpan },
+}
+
+impl PgnToken {
+    /// The part of the source this token was parsed from
+    pub fn span(&self) -> &SourceSpan {
+        match self {
+            PgnToken::MoveNumber { span, .. }
+            | PgnToken::Move { span, .. }
+            | PgnToken::MoveSuffixNotation { span, .. }
+            | PgnToken::Result { span, .. }
+            | PgnToken::Tag { span, .. }
+            | PgnToken::Comment { span, .. }
+            | PgnToken::Variation { span, .. }
+            | PgnToken::NAG { span, .. } => span,
+        }
+    }
+
+    fn span_mut(&mut self) -> &mut SourceSpan {
+        match self {
+            PgnToken::MoveNumber { span, .. }
+            | PgnToken::Move { span, .. }
+            | PgnToken::MoveSuffixNotation { span, .. }
+            | PgnToken::Result { span, .. }
+            | PgnToken::Tag { span, .. }
+            | PgnToken::Comment { span, .. }
+            | PgnToken::Variation { span, .. }
+            | PgnToken::NAG { span, .. } => span,
+        }
+    }
 }
 
 impl std::fmt::Display for PgnToken {
@@ -52,20 +106,22 @@ impl std::fmt::Display for PgnToken {
         f: &mut std::fmt::Formatter<'_>,
     ) -> std::fmt::Result {
         match self {
-            PgnToken::MoveNumber { number } => write!(f, "{}.", number),
-            PgnToken::Move { notation } => write!(f, "{} ", notation),
-            PgnToken::Result { result } => write!(f, "{}", result),
-            PgnToken::Tag { name, value } => {
+            PgnToken::MoveNumber { number, .. } => write!(f, "{}.", number),
+            PgnToken::Move { san, .. } => write!(f, "{} ", san),
+            PgnToken::Result { result, .. } => write!(f, "{}", result),
+            PgnToken::Tag { name, value, .. } => {
                 write!(f, "[{} \"{}\"]", name, value)
             }
-            PgnToken::Comment { text } => write!(f, "{{{}}}", text),
-            PgnToken::Variation { moves } => write!(
+            PgnToken::Comment { text, .. } => write!(f, "{{{}}}", text),
+            PgnToken::Variation { moves, .. } => write!(
                 f,
                 "({})",
                 moves.iter().map(|t| t.to_string()).collect::<String>()
             ),
-            PgnToken::NAG { code } => write!(f, "${}", code),
-            PgnToken::MoveSuffixNotation { suffix } => write!(f, "{}", suffix),
+            PgnToken::NAG { code, .. } => write!(f, "${}", code),
+            PgnToken::MoveSuffixNotation { suffix, .. } => {
+                write!(f, "{}", suffix)
+            }
         }
     }
 }
@@ -79,11 +135,25 @@ pub struct PgnGame {
     pub moves: Vec<PgnToken>,
     /// Game result
     pub result: Option<String>,
+    /// The part of the source the game was parsed from
+    pub span: SourceSpan,
+    /// Problems validation let through, like tags filled with defaults
+    pub warnings: Vec<PgnWarning>,
+    /// Index of the game in the source, starting at 0
+    pub game_index: usize,
 }
 
 impl PgnGame {
     /// Create a new PGN game from tokens
     pub fn from_tokens(tokens: Vec<PgnToken>) -> Self {
+        let span = match (tokens.first(), tokens.last()) {
+            (Some(first), Some(last)) => SourceSpan {
+                end: last.span().end,
+                ..*first.span()
+            },
+            _ => SourceSpan::default(),
+        };
+
         let mut tags = Vec::new();
         let mut moves = Vec::new();
         let mut result = None;
@@ -91,7 +161,7 @@ impl PgnGame {
         for token in tokens {
             match &token {
                 PgnToken::Tag { .. } => tags.push(token),
-                PgnToken::Result { result: r } => {
+                PgnToken::Result { result: r, .. } => {
                     result = Some(r.clone());
                     moves.push(token);
                 }
@@ -103,6 +173,9 @@ impl PgnGame {
             tags,
             moves,
             result,
+            span,
+            warnings: Vec::new(),
+            game_index: 0,
         }
     }
 
@@ -112,7 +185,7 @@ impl PgnGame {
         tag_name: &str,
     ) -> Option<&str> {
         self.tags.iter().find_map(|token| {
-            if let PgnToken::Tag { name, value } = token {
+            if let PgnToken::Tag { name, value, .. } = token {
                 if name == tag_name {
                     Some(value.as_str())
                 } else {
@@ -126,36 +199,176 @@ impl PgnGame {
 }
 
 /// Error type for PGN parsing failures
+///
+/// Every error records where in the source it occurred along with a short
+/// excerpt of the offending line.
 #[derive(Debug, Clone, PartialEq, Eq, thiserror::Error, Serialize)]
 #[serde(tag = "error_type")]
 pub enum PgnParseError {
     /// Failed to parse the PGN content
-    #[error("Parse failure:\n\tInput: '{input}'\n\tMessage: {message}")]
-    ParseFailure { input: String, message: String },
+    #[error("Parse failure at {span}:\n\tMessage: {message}{excerpt}")]
+    ParseFailure {
+        message: String,
+        span: SourceSpan,
+        excerpt: SourceExcerpt,
+    },
 
     /// Invalid tag format
-    #[error("Invalid tag format:\n\tTag: '{tag}'\n\tReason: {reason}")]
-    InvalidTag { tag: String, reason: String },
+    #[error(
+        "Invalid tag format at {span}:\n\tTag: '{tag}'\n\tReason: {reason}{excerpt}"
+    )]
+    InvalidTag {
+        tag: String,
+        reason: String,
+        span: SourceSpan,
+        excerpt: SourceExcerpt,
+    },
 
     /// Invalid move format
-    #[error("Invalid move format:\n\tMove: '{move_text}'\n\tReason: {reason}")]
-    InvalidMove { move_text: String, reason: String },
+    #[error(
+        "Invalid move format at {span}:\n\tMove: '{move_text}'\n\tReason: {reason}{excerpt}"
+    )]
+    InvalidMove {
+        move_text: String,
+        reason: String,
+        span: SourceSpan,
+        excerpt: SourceExcerpt,
+    },
 
     /// Missing required tag
-    #[error("Missing required tag:\n\tTag: '{tag_name}'")]
-    MissingRequiredTag { tag_name: String },
+    #[error("Missing required tag at {span}:\n\tTag: '{tag_name}'{excerpt}")]
+    MissingRequiredTag {
+        tag_name: String,
+        span: SourceSpan,
+        excerpt: SourceExcerpt,
+    },
 
     /// Invalid game result
-    #[error("Invalid game result:\n\tResult: '{result}'")]
-    InvalidResult { result: String },
+    #[error("Invalid game result at {span}:\n\tResult: '{result}'{excerpt}")]
+    InvalidResult {
+        result: String,
+        span: SourceSpan,
+        excerpt: SourceExcerpt,
+    },
 
     /// Malformed variation
-    #[error("Malformed variation:\n\tReason: {reason}")]
-    MalformedVariation { reason: String },
+    #[error("Malformed variation at {span}:\n\tReason: {reason}{excerpt}")]
+    MalformedVariation {
+        reason: String,
+        span: SourceSpan,
+        excerpt: SourceExcerpt,
+    },
 
     /// Unknown token
-    #[error("Unknown token:\n\tToken: '{token}'")]
-    UnknownToken { token: String },
+    #[error("Unknown token at {span}:\n\tToken: '{token}'{excerpt}")]
+    UnknownToken {
+        token: String,
+        span: SourceSpan,
+        excerpt: SourceExcerpt,
+    },
+
+    /// Failed to read from the PGN source
+    #[error("Read failure at {span}:\n\tMessage: {message}{excerpt}")]
+    ReadFailure {
+        message: String,
+        span: SourceSpan,
+        excerpt: SourceExcerpt,
+    },
+
+    /// Game text larger than the reader allows
+    #[error("Game too large at {span}:\n\tLimit: {limit} bytes{excerpt}")]
+    GameTooLarge {
+        limit: usize,
+        span: SourceSpan,
+        excerpt: SourceExcerpt,
+    },
+}
+
+impl PgnParseError {
+    /// The part of the source the error occurred at
+    pub fn span(&self) -> &SourceSpan {
+        match self {
+            PgnParseError::ParseFailure { span, .. }
+            | PgnParseError::InvalidTag { span, .. }
+            | PgnParseError::InvalidMove { span, .. }
+            | PgnParseError::MissingRequiredTag { span, .. }
+            | PgnParseError::InvalidResult { span, .. }
+            | PgnParseError::MalformedVariation { span, .. }
+            | PgnParseError::UnknownToken { span, .. }
+            | PgnParseError::ReadFailure { span, .. }
+            | PgnParseError::GameTooLarge { span, .. } => span,
+        }
+    }
+
+    /// The excerpt of the source line the error occurred on
+    pub fn excerpt(&self) -> &SourceExcerpt {
+        match self {
+            PgnParseError::ParseFailure { excerpt, .. }
+            | PgnParseError::InvalidTag { excerpt, .. }
+            | PgnParseError::InvalidMove { excerpt, .. }
+            | PgnParseError::MissingRequiredTag { excerpt, .. }
+            | PgnParseError::InvalidResult { excerpt, .. }
+            | PgnParseError::MalformedVariation { excerpt, .. }
+            | PgnParseError::UnknownToken { excerpt, .. }
+            | PgnParseError::ReadFailure { excerpt, .. }
+            | PgnParseError::GameTooLarge { excerpt, .. } => excerpt,
+        }
+    }
+
+    fn excerpt_mut(&mut self) -> &mut SourceExcerpt {
+        match self {
+            PgnParseError::ParseFailure { excerpt, .. }
+            | PgnParseError::InvalidTag { excerpt, .. }
+            | PgnParseError::InvalidMove { excerpt, .. }
+            | PgnParseError::MissingRequiredTag { excerpt, .. }
+            | PgnParseError::InvalidResult { excerpt, .. }
+            | PgnParseError::MalformedVariation { excerpt, .. }
+            | PgnParseError::UnknownToken { excerpt, .. }
+            | PgnParseError::ReadFailure { excerpt, .. }
+            | PgnParseError::GameTooLarge { excerpt, .. } => excerpt,
+        }
+    }
+}
+
+/// A problem with a game that didn't stop it being parsed
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error, Serialize)]
+#[serde(tag = "warning_type")]
+pub enum PgnWarning {
+    /// A tag the game didn't have was given a default value
+    #[error("Missing tag at {span}:\n\tTag: '{tag_name}'\n\tUsing: '{value}'")]
+    MissingTag {
+        tag_name: String,
+        value: String,
+        span: SourceSpan,
+    },
+}
+
+impl PgnWarning {
+    /// The part of the source the warning is about
+    pub fn span(&self) -> &SourceSpan {
+        match self {
+            PgnWarning::MissingTag { span, .. } => span,
+        }
+    }
+}
+
+/// A game that failed to parse
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error, Serialize)]
+#[error("Game {}: {error}", .game_index + 1)]
+pub struct PgnGameError {
+    /// Index of the game in the source, starting at 0
+    pub game_index: usize,
+    /// What went wrong and where
+    pub error: PgnParseError,
+}
+
+/// The games parsed from a PGN source along with the games that failed
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PgnParseResult {
+    /// Successfully parsed games, in source order
+    pub games: Vec<PgnGame>,
+    /// Games that failed to parse, in source order
+    pub errors: Vec<PgnGameError>,
 }
 
 /// Top-level parser for PGN tokens
@@ -164,120 +377,176 @@ fn pgn_parser() -> impl Parser<char, Vec<PgnToken>, Error = Simple<char>> {
         simple::tag_parser(),
         simple::game_result_parser(),
         simple::move_number_parser(),
+        simple::evaluation_symbol_parser(),
         simple::chess_move_parser(),
+        simple::move_suffix_parser(),
         simple::nag_parser(),
         simple::comment_parser(),
+        simple::line_comment_parser(),
         complex::variation_parser(),
     ))
     .padded()
     .repeated()
+    .then_ignore(end())
 }
 
 /// Parse a PGN string into tokens
 ///
 /// # Arguments
-/// * `pgn` - A PGN string to parse (can contain multiple games)
+/// * `pgn` - A PGN string to parse
+/// * `source` - Maps positions in `pgn` to positions in the source
 ///
 /// # Returns
 /// * `Result<Vec<PgnToken>, PgnParseError>` - A vector of parsed PGN tokens or an error
-fn pgn_string_to_tokens(pgn: &str) -> Result<Vec<PgnToken>, PgnParseError> {
-    // TODO: Improve error recovery and allow for partial parsing
-
-    if DEBUG {
-        let (tokens, errors) = pgn_parser().parse_recovery_verbose(pgn);
-        tokens.ok_or_else(|| PgnParseError::ParseFailure {
-            input: pgn.to_string(),
-            message: errors
-                .iter()
-                .map(|e| e.to_string())
-                .collect::<Vec<String>>()
-                .join(", "),
-        })
+fn pgn_string_to_tokens(
+    pgn: &str,
+    source: &SourceMap,
+) -> Result<Vec<PgnToken>, PgnParseError> {
+    let (tokens, errors) = if DEBUG {
+        pgn_parser().parse_recovery_verbose(pgn)
     } else {
-        pgn_parser()
-            .parse(pgn)
-            .map_err(|e| PgnParseError::ParseFailure {
-                input: pgn.to_string(),
-                message: e
-                    .iter()
-                    .map(|e| e.to_string())
-                    .collect::<Vec<String>>()
-                    .join(", "),
-            })
-    }
-}
+        pgn_parser().parse_recovery(pgn)
+    };
 
-/// Parse a PGN string and group tokens by game
-///
-/// # Arguments
-/// * `tokens` - A vector of PGN tokens to parse (can contain multiple games)
-///
-/// # Returns
-/// * `Result<Vec<PgnGame>, PgnParseError>` - A vector of parsed PGN games or an error
-fn pgn_tokens_to_games(
-    tokens: Vec<PgnToken>
-) -> Result<Vec<PgnGame>, Vec<PgnParseError>> {
-    let mut games = Vec::new();
-    let mut current_game = Vec::new();
-    let mut parse_errors = Vec::new();
-
-    for token in tokens {
-        if let PgnToken::Tag { name, .. } = &token {
-            // Use the "Event" tag to determine the start of a new game
-            // this should always be the first tag in the game (I think)
-            if name == "Event" && !current_game.is_empty() {
-                let game = PgnGame::from_tokens(current_game);
-                match complex::validate_game(&game) {
-                    Ok(_) => games.push(game),
-                    Err(e) => parse_errors.push(e),
+    match (tokens, errors.first()) {
+        (Some(mut tokens), None) => {
+            source.resolve(&mut tokens);
+            Ok(tokens)
+        }
+        (_, first_error) => {
+            let span = first_error.map(|e| e.span()).unwrap_or(0..0);
+            let error = match first_error.map(|e| e.reason()) {
+                // Only the move parser gives custom errors, for moves
+                // that aren't valid SAN
+                Some(SimpleReason::Custom(reason)) => {
+                    PgnParseError::InvalidMove {
+                        move_text: pgn
+                            .chars()
+                            .skip(span.start)
+                            .take(span.len())
+                            .collect(),
+                        reason: reason.clone(),
+                        span: source.span(span),
+                        excerpt: SourceExcerpt::default(),
+                    }
                 }
-                current_game = Vec::new();
-            }
+                _ => PgnParseError::ParseFailure {
+                    message: errors
+                        .iter()
+                        .map(|e| e.to_string())
+                        .collect::<Vec<String>>()
+                        .join(", "),
+                    span: source.span(span),
+                    excerpt: SourceExcerpt::default(),
+                },
+            };
+            Err(source.attach_excerpt(error))
         }
-
-        current_game.push(token);
     }
+}
 
-    if !current_game.is_empty() {
-        let game = PgnGame::from_tokens(current_game);
-        match complex::validate_game(&game) {
-            Ok(_) => games.push(game),
-            Err(e) => parse_errors.push(e),
-        }
+/// Replace escape lines (lines starting with `%`) with spaces
+///
+/// The PGN standard reserves these for other programs, their content is
+/// ignored. They are blanked rather than removed so the positions of the
+/// text after them don't change. `start` tells whether the text starts
+/// at the beginning of a line.
+fn blank_escape_lines(
+    pgn: &str,
+    start: SourceSpan,
+) -> std::borrow::Cow<'_, str> {
+    let is_escape = |(i, line): &(usize, &str)| {
+        line.starts_with('%') && (*i > 0 || start.column == 1)
+    };
+    if !pgn.split_inclusive('\n').enumerate().any(|l| is_escape(&l)) {
+        return pgn.into();
     }
 
-    if !parse_errors.is_empty() {
-        return Err(parse_errors);
-    }
+    pgn.split_inclusive('\n')
+        .enumerate()
+        .map(|(i, line)| {
+            if !is_escape(&(i, line)) {
+                return line.to_string();
+            }
+            let content = line.trim_end_matches(['\r', '\n']);
+            " ".repeat(content.len()) + &line[content.len()..]
+        })
+        .collect::<String>()
+        .into()
+}
 
-    Ok(games)
+/// Parse and validate the text of a single game
+///
+/// `start` is where the text starts in the source, so the spans of the
+/// game's tokens and errors point into the source rather than the text.
+pub(crate) fn parse_game_text(
+    pgn: &str,
+    start: SourceSpan,
+    policy: &ValidationPolicy,
+) -> Result<PgnGame, PgnParseError> {
+    let pgn = &blank_escape_lines(pgn, start);
+    let source = SourceMap::new(pgn, start);
+    let tokens = pgn_string_to_tokens(pgn, &source)?;
+    let mut game = PgnGame::from_tokens(tokens);
+    complex::validate_game(&mut game, policy)
+        .map_err(|e| source.attach_excerpt(e))?;
+    Ok(game)
+}
+
+/// Parse the text of a single PGN game
+///
+/// # Arguments
+/// * `pgn` - A PGN string holding exactly one game
+///
+/// # Returns
+/// * `Result<PgnGame, PgnParseError>` - The parsed and validated game or an error
+pub fn parse_pgn_game(pgn: &str) -> Result<PgnGame, PgnParseError> {
+    parse_game_text(pgn, SourceSpan::at(0, 1, 1), &ValidationPolicy::Strict)
+}
+
+/// Parse movetext without a tag section, like the moves of a game
+/// exported as JSON
+///
+/// # Arguments
+/// * `movetext` - Moves with optional comments, variations and result
+///
+/// # Returns
+/// * `Result<PgnGame, PgnParseError>` - A game with no tags or an error
+pub fn parse_movetext(movetext: &str) -> Result<PgnGame, PgnParseError> {
+    parse_game_text(
+        movetext,
+        SourceSpan::at(0, 1, 1),
+        &ValidationPolicy::Lenient,
+    )
 }
 
 /// Public facade for parsing PGNs
 ///
+/// Games are parsed independently, a game that fails to parse is
+/// reported and parsing resumes at the next game's tag section.
+///
 /// # Arguments
 /// * `pgn` - A PGN string to parse (can contain multiple games)
 ///
 /// # Returns
-/// * `Result<Vec<PgnGame>, Vec<PgnParseError>>` - A vector of parsed PGN games or an error
-pub fn parse_pgn_games(pgn: &str) -> Result<Vec<PgnGame>, Vec<PgnParseError>> {
-    if pgn.trim().is_empty() {
-        return Err(vec![PgnParseError::ParseFailure {
-            input: pgn.to_string(),
-            message: "PGN string is empty".to_string(),
-        }]);
-    }
-
+/// * `PgnParseResult` - The parsed PGN games and the errors of those that failed
+pub fn parse_pgn_games(pgn: &str) -> PgnParseResult {
     debug!("Parsing PGN...");
 
-    let tokens = pgn_string_to_tokens(pgn).map_err(|e| vec![e])?;
-
-    debug!("\tParsed {} tokens", tokens.len());
-
-    let games = pgn_tokens_to_games(tokens)?;
+    let mut result = PgnParseResult::default();
+    for game in PgnReader::new(pgn.as_bytes()) {
+        match game {
+            Ok(game) => result.games.push(game),
+            Err(e) => result.errors.push(e),
+        }
+    }
 
-    debug!("\tParsed {} games", games.len());
+    debug!(
+        "\tParsed {} games, {} failed",
+        result.games.len(),
+        result.errors.len()
+    );
     debug!("Parsing complete");
 
-    Ok(games)
+    result
 }
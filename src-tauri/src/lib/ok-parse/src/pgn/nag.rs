@@ -0,0 +1,95 @@
+//! Numeric Annotation Glyphs (NAGs) and the symbols written in their place
+//!
+//! Games often use move suffixes like `!?` and evaluation symbols like
+//! `+-` instead of NAGs. Both are stored as their NAG code, which is what
+//! the PGN export format writes.
+
+/// Move suffixes and the NAG each stands for
+const MOVE_SUFFIXES: [(&str, u8); 6] = [
+    ("!", 1),
+    ("?", 2),
+    ("!!", 3),
+    ("??", 4),
+    ("!?", 5),
+    ("?!", 6),
+];
+
+/// Evaluation symbols and the NAG each stands for, in ASCII and the
+/// Unicode forms printed in books
+///
+/// Longer symbols come first so they're matched before the symbols
+/// they start with.
+pub const EVALUATION_SYMBOLS: [(&str, u8); 12] = [
+    ("+/-", 16),
+    ("-/+", 17),
+    ("+=", 14),
+    ("=+", 15),
+    ("+-", 18),
+    ("-+", 19),
+    ("=", 10),
+    ("\u{221e}", 13),
+    ("\u{2a72}", 14),
+    ("\u{2a71}", 15),
+    ("\u{b1}", 16),
+    ("\u{2213}", 17),
+];
+
+/// The NAG for a move suffix like "!?" or an evaluation symbol like "+-"
+pub fn symbol_to_nag(symbol: &str) -> Option<u8> {
+    MOVE_SUFFIXES
+        .iter()
+        .chain(EVALUATION_SYMBOLS.iter())
+        .find(|(text, _)| *text == symbol)
+        .map(|(_, code)| *code)
+}
+
+/// The move suffix for a NAG, for $1 to $6
+pub fn nag_to_suffix(code: u8) -> Option<&'static str> {
+    MOVE_SUFFIXES
+        .iter()
+        .find(|(_, nag)| *nag == code)
+        .map(|(text, _)| *text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pgn::{parse_pgn_game, PgnToken};
+
+    #[test]
+    fn suffixes_and_symbols_map_to_nags() {
+        let codes: Vec<Option<u8>> = ["!", "?", "!!", "??", "!?", "?!"]
+            .into_iter()
+            .map(symbol_to_nag)
+            .collect();
+        assert_eq!(codes, (1..=6).map(Some).collect::<Vec<_>>());
+        assert_eq!(symbol_to_nag("+-"), Some(18));
+        assert_eq!(symbol_to_nag("=+"), Some(15));
+        assert_eq!(symbol_to_nag("\u{b1}"), Some(16));
+        assert_eq!(symbol_to_nag("!?!"), None);
+        assert_eq!(nag_to_suffix(5), Some("!?"));
+        assert_eq!(nag_to_suffix(18), None);
+    }
+
+    #[test]
+    fn evaluation_symbols_are_parsed_as_nags() {
+        let pgn = "[Event \"?\"]\n[Site \"?\"]\n[Date \"?\"]\n[Round \"?\"]\n\
+                   [White \"?\"]\n[Black \"?\"]\n[Result \"*\"]\n\n\
+                   1. e4 += e5 = 2. Qh5 -/+ ( 2. Nf3 +/- ) Nc6 -- 3. Bc4 +- *\n";
+        let game = parse_pgn_game(pgn).unwrap();
+
+        let nags: Vec<u8> = game
+            .moves
+            .iter()
+            .filter_map(|token| match token {
+                PgnToken::NAG { code, .. } => Some(*code),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(nags, vec![14, 10, 17, 18]);
+        assert!(game.moves.iter().any(|token| matches!(
+            token,
+            PgnToken::Move { san, .. } if san.is_null()
+        )));
+    }
+}
@@ -0,0 +1,607 @@
+//! Streaming PGN reader
+//!
+//! Reads games one at a time from any `BufRead` source so large PGN files
+//! (database dumps can be several gigabytes) never have to be held in
+//! memory at once. Only the text of the game currently being read is kept.
+//!
+//! Sources are read as bytes and decoded a line at a time, see
+//! `crate::encoding` for how their encoding is worked out. Spans are
+//! offsets into the decoded text, which only match the source's bytes for
+//! UTF-8 sources, so progress through the source is tracked separately by
+//! `PgnReader::bytes_read`.
+
+use std::collections::VecDeque;
+use std::io::BufRead;
+
+use crate::encoding::{LineDecoder, TextEncoding};
+
+use super::{
+    parse_game_text, PgnGame, PgnGameError, PgnParseError, SourceExcerpt,
+    SourceSpan, ValidationPolicy,
+};
+
+/// Games larger than this are skipped instead of growing the buffer forever
+pub const DEFAULT_MAX_GAME_BYTES: usize = 16 * 1024 * 1024;
+
+/// Game termination markers
+const RESULTS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+
+/// Iterator over the games of a PGN source
+///
+/// A game ends when a tag section follows its movetext, or at its game
+/// termination marker (the result at the end of the movetext). Games
+/// don't need any particular tags, so exports without an `Event` tag, with
+/// tags in a different order, or with no tags at all are split correctly.
+///
+/// Each game is tokenised and validated on its own, so memory use is
+/// bounded by the size of the largest game, and a game that fails to
+/// parse doesn't affect the games after it.
+pub struct PgnReader<R: BufRead> {
+    reader: R,
+    /// Raw bytes of the line being read
+    line: Vec<u8>,
+    decoder: LineDecoder,
+    /// Text of the game being read
+    game: String,
+    /// Games finished but not yet returned
+    finished_games: VecDeque<Result<PgnGame, PgnGameError>>,
+    /// Number of bytes consumed from the source
+    bytes_read: u64,
+    /// Length of the text decoded so far, what spans are offsets into
+    text_read: usize,
+    /// Number of lines consumed from the source
+    lines_read: usize,
+    /// Where the current game starts in the source
+    game_start: SourceSpan,
+    /// Number of games finished, including those that failed
+    games_read: usize,
+    max_game_bytes: usize,
+    /// Which tags games must have
+    policy: ValidationPolicy,
+    /// The current game exceeded `max_game_bytes` and is being skipped
+    oversized: bool,
+    /// A `{` comment is open
+    in_comment: bool,
+    /// Number of variations open
+    variation_depth: usize,
+    /// The current game has a tag section
+    has_tags: bool,
+    /// Movetext has been seen since the last tag section
+    in_movetext: bool,
+    finished: bool,
+}
+
+impl<R: BufRead> PgnReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            line: Vec::new(),
+            decoder: LineDecoder::new(),
+            game: String::new(),
+            finished_games: VecDeque::new(),
+            bytes_read: 0,
+            text_read: 0,
+            lines_read: 0,
+            game_start: SourceSpan::at(0, 1, 1),
+            games_read: 0,
+            max_game_bytes: DEFAULT_MAX_GAME_BYTES,
+            policy: ValidationPolicy::default(),
+            oversized: false,
+            in_comment: false,
+            variation_depth: 0,
+            has_tags: false,
+            in_movetext: false,
+            finished: false,
+        }
+    }
+
+    /// Set the largest game (in bytes) that will be parsed
+    pub fn with_max_game_bytes(
+        mut self,
+        max_game_bytes: usize,
+    ) -> Self {
+        self.max_game_bytes = max_game_bytes;
+        self
+    }
+
+    /// Set which tags games must have, all of the Seven Tag Roster
+    /// by default
+    pub fn with_validation_policy(
+        mut self,
+        policy: ValidationPolicy,
+    ) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// The number of bytes consumed from the source so far
+    ///
+    /// Compare against the total size of the source to report progress.
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    /// The number of games read so far, including those that failed
+    ///
+    /// Games a line finishes are all counted once the line is read, so
+    /// this can be ahead of the games returned. Use each game's
+    /// `game_index` to tell where it is in the source.
+    pub fn games_read(&self) -> usize {
+        self.games_read
+    }
+
+    /// The encoding of the source, as far as it has been read
+    pub fn encoding(&self) -> TextEncoding {
+        self.decoder.encoding()
+    }
+
+    /// Finish the current game and queue the result of parsing it
+    ///
+    /// `next_start` is where the following game starts. Nothing is queued
+    /// if the game has no tags or movetext, like the whitespace or comments
+    /// between two games.
+    fn finish_game(
+        &mut self,
+        next_start: SourceSpan,
+    ) {
+        let text = std::mem::take(&mut self.game);
+        let start = std::mem::replace(&mut self.game_start, next_start);
+        let has_content = self.has_tags || self.in_movetext;
+        self.in_comment = false;
+        self.variation_depth = 0;
+        self.has_tags = false;
+        self.in_movetext = false;
+
+        let result = if std::mem::take(&mut self.oversized) {
+            Err(PgnParseError::GameTooLarge {
+                limit: self.max_game_bytes,
+                span: start,
+                excerpt: SourceExcerpt::default(),
+            })
+        } else if !has_content {
+            return;
+        } else {
+            parse_game_text(&text, start, &self.policy)
+        };
+
+        let game_index = self.games_read;
+        self.games_read += 1;
+        self.finished_games.push_back(match result {
+            Ok(game) => Ok(PgnGame { game_index, ..game }),
+            Err(error) => Err(PgnGameError { game_index, error }),
+        });
+    }
+
+    /// Add text to the current game, unless it has grown too large
+    fn push_text(
+        &mut self,
+        text: &str,
+    ) {
+        if self.oversized {
+            return;
+        }
+        if self.game.len() + text.len() > self.max_game_bytes {
+            self.oversized = true;
+            self.game.clear();
+            return;
+        }
+        self.game.push_str(text);
+    }
+
+    /// Split a line between games and add it to the current game
+    ///
+    /// `line_start` is where the line starts in the source.
+    fn read_line(
+        &mut self,
+        line: &str,
+        line_start: SourceSpan,
+    ) {
+        // Escape lines are ignored, whatever they contain
+        if line.starts_with('%') {
+            self.push_text(line);
+            return;
+        }
+
+        if !self.in_comment && line.trim_start().starts_with('[') {
+            // A tag section after movetext starts a new game
+            if self.in_movetext {
+                self.finish_game(line_start);
+            }
+            self.has_tags = true;
+            self.push_text(line);
+            return;
+        }
+
+        // Start of the part of the line not yet added to a game
+        let mut consumed = 0;
+        let mut token_start = None;
+
+        for (i, c) in line
+            .char_indices()
+            .chain(std::iter::once((line.len(), ' ')))
+        {
+            if self.in_comment {
+                self.in_comment = c != '}';
+                continue;
+            }
+            if !(c.is_whitespace() || "{};()".contains(c)) {
+                token_start.get_or_insert(i);
+                self.in_movetext = true;
+                continue;
+            }
+
+            // A termination marker outside of any variation ends the game
+            if let Some(start) = token_start.take() {
+                if self.variation_depth == 0
+                    && RESULTS.contains(&&line[start..i])
+                {
+                    self.push_text(&line[consumed..i]);
+                    consumed = i;
+                    let column = line[..i].chars().count() + 1;
+                    self.finish_game(SourceSpan::at(
+                        line_start.start + i,
+                        line_start.line,
+                        column,
+                    ));
+                }
+            }
+
+            match c {
+                '{' => self.in_comment = true,
+                // The rest of the line is a comment
+                ';' => break,
+                '(' => {
+                    self.variation_depth += 1;
+                    self.in_movetext = true;
+                }
+                ')' => {
+                    self.variation_depth =
+                        self.variation_depth.saturating_sub(1)
+                }
+                _ => {}
+            }
+        }
+
+        self.push_text(&line[consumed..]);
+    }
+
+    /// The position in the decoded source after the lines read so far
+    fn source_position(&self) -> SourceSpan {
+        SourceSpan::at(self.text_read, self.lines_read + 1, 1)
+    }
+}
+
+impl<R: BufRead> Iterator for PgnReader<R> {
+    type Item = Result<PgnGame, PgnGameError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(game) = self.finished_games.pop_front() {
+                return Some(game);
+            }
+            if self.finished {
+                return None;
+            }
+
+            self.line.clear();
+            let read = match self
+                .decoder
+                .read_line(&mut self.reader, &mut self.line)
+            {
+                Ok(read) => read,
+                Err(e) => {
+                    self.finished = true;
+                    let error = PgnParseError::ReadFailure {
+                        message: e.to_string(),
+                        span: self.source_position(),
+                        excerpt: SourceExcerpt::default(),
+                    };
+                    let game_index = self.games_read;
+                    self.games_read += 1;
+                    return Some(Err(PgnGameError { game_index, error }));
+                }
+            };
+
+            if read == 0 {
+                self.finished = true;
+                self.finish_game(self.source_position());
+                continue;
+            }
+            let line_start = self.source_position();
+            self.bytes_read += read as u64;
+            self.lines_read += 1;
+
+            let line = self.decoder.decode(&self.line);
+            self.text_read += line.len();
+            self.read_line(&line, line_start);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pgn::{write_pgn_game, PgnToken};
+
+    const GAMES: &str = r#"[Event "First"]
+[Site "?"]
+[Date "2024.01.01"]
+[Round "1"]
+[White "A"]
+[Black "B"]
+[Result "1-0"]
+
+1. e4 e5 {A comment
+[spanning lines]} 2. Nf3 1-0
+
+[Event "Second"]
+[Site "?"]
+[Date "2024.01.02"]
+[Round "2"]
+[White "C"]
+[Black "D"]
+[Result "0-1"]
+
+1. d4 d5 0-1
+"#;
+
+    #[test]
+    fn yields_one_game_at_a_time() {
+        let mut reader = PgnReader::new(GAMES.as_bytes());
+
+        let first = reader.next().unwrap().unwrap();
+        assert_eq!(first.get_tag("Event"), Some("First"));
+        assert_eq!(first.result.as_deref(), Some("1-0"));
+        assert!(reader.bytes_read() < GAMES.len() as u64);
+
+        let second = reader.next().unwrap().unwrap();
+        assert_eq!(second.get_tag("Event"), Some("Second"));
+        assert_eq!(second.span.line, 12);
+        assert_eq!(Some(second.span.start), GAMES.find("[Event \"Second\"]"));
+        assert_eq!(reader.bytes_read(), GAMES.len() as u64);
+
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn failed_games_are_reported_with_their_location() {
+        let broken = GAMES.replace("2. Nf3 1-0", "2. Nf3 @@ 1-0");
+        let mut reader = PgnReader::new(broken.as_bytes());
+
+        let error = reader.next().unwrap().unwrap_err();
+        assert_eq!(error.game_index, 0);
+        let span = error.error.span();
+        assert_eq!((span.line, span.column), (10, 26));
+        assert_eq!(&broken[span.start..span.start + 2], "@@");
+
+        let excerpt = error.error.excerpt();
+        assert_eq!(excerpt.text, "[spanning lines]} 2. Nf3 @@ 1-0");
+        assert_eq!(excerpt.caret, 25);
+
+        let second = reader.next().unwrap().unwrap();
+        assert_eq!(second.get_tag("Event"), Some("Second"));
+        assert_eq!(reader.games_read(), 2);
+    }
+
+    #[test]
+    fn oversized_games_are_skipped() {
+        let mut reader =
+            PgnReader::new(GAMES.as_bytes()).with_max_game_bytes(100);
+
+        assert!(matches!(
+            reader.next(),
+            Some(Err(PgnGameError {
+                error: PgnParseError::GameTooLarge { limit: 100, .. },
+                ..
+            }))
+        ));
+        assert!(matches!(
+            reader.next(),
+            Some(Err(PgnGameError {
+                error: PgnParseError::GameTooLarge { limit: 100, .. },
+                ..
+            }))
+        ));
+        assert!(reader.next().is_none());
+    }
+
+    /// The line and column each game, or its error, starts at
+    fn game_starts(pgn: &str) -> Vec<(usize, usize)> {
+        PgnReader::new(pgn.as_bytes())
+            .map(|game| {
+                let span = match &game {
+                    Ok(game) => game.span,
+                    Err(e) => *e.error.span(),
+                };
+                (span.line, span.column)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn games_without_an_event_tag_are_split() {
+        let pgn = r#"[White "Carlsen, Magnus"]
+[Black "Nepomniachtchi, Ian"]
+[Result "1/2-1/2"]
+[Date "2021.11.26"]
+
+1. e4 e5 2. Nf3 Nc6 1/2-1/2
+[White "Nepomniachtchi, Ian"]
+[Black "Carlsen, Magnus"]
+[Result "1-0"]
+[Date "2021.11.27"]
+
+1. d4 Nf6 1-0
+"#;
+        assert_eq!(game_starts(pgn), vec![(1, 1), (7, 1)]);
+        assert!(PgnReader::new(pgn.as_bytes()).all(|game| matches!(
+            game,
+            Err(PgnGameError {
+                error: PgnParseError::MissingRequiredTag { .. },
+                ..
+            })
+        )));
+    }
+
+    #[test]
+    fn tag_order_does_not_matter() {
+        let pgn = r#"[White "A"]
+[Black "B"]
+[Event "Casual"]
+[Site "?"]
+[Date "????.??.??"]
+[Round "-"]
+[Result "*"]
+
+1. e4 *
+
+[Black "D"]
+[White "C"]
+[Site "?"]
+[Event "Casual"]
+[Date "????.??.??"]
+[Round "-"]
+[Result "0-1"]
+
+1. d4 0-1
+"#;
+        let games: Vec<PgnGame> = PgnReader::new(pgn.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].get_tag("White"), Some("A"));
+        assert_eq!(games[1].get_tag("White"), Some("C"));
+        assert_eq!(games[1].result.as_deref(), Some("0-1"));
+    }
+
+    #[test]
+    fn movetext_only_games_are_split_at_their_results() {
+        let pgn = r#"1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 1-0
+
+1. d4 d5 2. c4 {1-0 in a comment} e6 (2... c6 3. Nc3) 0-1
+1. c4 *
+"#;
+        assert_eq!(game_starts(pgn), vec![(1, 1), (3, 1), (4, 1)]);
+    }
+
+    #[test]
+    fn games_separated_only_by_results_are_split() {
+        assert_eq!(
+            game_starts("1. e4 e5 1-0 1. d4 d5 0-1\n1. c4 1/2-1/2"),
+            vec![(1, 1), (1, 14), (2, 1)]
+        );
+
+        // Games finished by the same line keep their own index
+        let mut reader =
+            PgnReader::new("1. e4 e5 1-0 1. d4 d5 0-1\n".as_bytes())
+                .with_validation_policy(ValidationPolicy::Lenient);
+        let first = reader.next().unwrap().unwrap();
+        assert_eq!(reader.games_read(), 2);
+        let second = reader.next().unwrap().unwrap();
+        assert_eq!((first.game_index, second.game_index), (0, 1));
+    }
+
+    #[test]
+    fn escape_lines_and_rest_of_line_comments_are_handled() {
+        let pgn = "% [Event \"Not a tag\"] 1-0 {\n".to_string()
+            + &GAMES.replace(
+                "1. e4 e5 {A comment",
+                "%exported by a program 1-0\n\
+                 1. e4 ; best by test {not a brace comment\n\
+                 e5 {A comment",
+            );
+        let games: Vec<PgnGame> = PgnReader::new(pgn.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(games.len(), 2);
+
+        let first = &games[0];
+        assert_eq!(first.get_tag("Event"), Some("First"));
+        assert_eq!(first.result.as_deref(), Some("1-0"));
+        let comment = first
+            .moves
+            .iter()
+            .find_map(|token| match token {
+                PgnToken::Comment { text, span } => Some((text, span)),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(comment.0, "best by test {not a brace comment");
+        assert_eq!((comment.1.line, comment.1.column), (11, 7));
+        assert!(write_pgn_game(first)
+            .replace('\n', " ")
+            .contains("1. e4 {best by test {not a brace comment} 1... e5"));
+        assert_eq!(games[1].span.line, 15);
+    }
+
+    #[test]
+    fn validation_policy_decides_which_tags_are_required() {
+        let pgn = "[White \"A\"]\n[Black \"B\"]\n\n1. e4 e5 1-0\n\n\
+                   [Event \"Casual\"]\n[White \"C\"]\n\n1. d4 *\n";
+        let read = |policy| {
+            PgnReader::new(pgn.as_bytes())
+                .with_validation_policy(policy)
+                .collect::<Vec<_>>()
+        };
+
+        let strict = read(ValidationPolicy::Strict);
+        assert!(strict.iter().all(|game| game.is_err()));
+
+        let lenient = read(ValidationPolicy::Lenient);
+        let game = lenient[0].as_ref().unwrap();
+        assert_eq!(game.get_tag("Site"), Some("?"));
+        assert_eq!(game.get_tag("Date"), Some("????.??.??"));
+        assert_eq!(game.get_tag("Result"), Some("1-0"));
+        assert_eq!(game.warnings.len(), 5);
+        assert_eq!(game.warnings[0].span().line, 1);
+        assert!(write_pgn_game(game).starts_with("[Event \"?\"]\n[Site \"?\"]"));
+
+        let custom = read(ValidationPolicy::RequiredTags(vec![
+            "Event".to_string(),
+            "White".to_string(),
+        ]));
+        assert!(matches!(
+            &custom[0],
+            Err(PgnGameError {
+                error: PgnParseError::MissingRequiredTag { tag_name, .. },
+                ..
+            }) if tag_name == "Event"
+        ));
+        let game = custom[1].as_ref().unwrap();
+        assert_eq!(game.get_tag("Black"), Some("?"));
+        assert_eq!(game.warnings.len(), 5);
+    }
+
+    #[test]
+    fn legacy_encodings_are_decoded() {
+        let latin1 = GAMES
+            .replace("[White \"A\"]", "[White \"R\u{e9}ti\"]")
+            .replace("{A comment", "{Sch\u{e4}rfer");
+        let bytes: Vec<u8> = latin1.chars().map(|c| c as u8).collect();
+        let mut reader = PgnReader::new(bytes.as_slice());
+
+        let first = reader.next().unwrap().unwrap();
+        assert_eq!(first.get_tag("White"), Some("Réti"));
+        assert!(first.moves.iter().any(|token| matches!(
+            token,
+            PgnToken::Comment { text, .. } if text.starts_with("Schärfer")
+        )));
+        assert_eq!(reader.encoding(), TextEncoding::Latin1);
+        assert!(reader.next().unwrap().is_ok());
+        assert_eq!(reader.bytes_read(), bytes.len() as u64);
+    }
+
+    #[test]
+    fn spans_are_offsets_into_decoded_text() {
+        let source = b"[White \"R\xe9ti\"]\n\n1. e4 {R\xe9ti\xe9\xe9} Nc9 *\n";
+        let text: String = source.iter().map(|&byte| byte as char).collect();
+        let mut reader = PgnReader::new(source.as_slice())
+            .with_validation_policy(ValidationPolicy::Lenient);
+
+        let error = reader.next().unwrap().unwrap_err().error;
+        let span = error.span();
+        assert_eq!(span.start, text.find("Nc9").unwrap());
+        assert_eq!((span.line, span.column), (3, 16));
+        assert_eq!(reader.bytes_read(), source.len() as u64);
+    }
+}
@@ -0,0 +1,393 @@
+//! Standard Algebraic Notation (SAN) for moves in movetext
+//!
+//! Besides the notation the PGN standard exports, moves are accepted in the
+//! forms commonly found in the wild: castling with zeros (`0-0`), promotion
+//! without `=` (`e8Q`), en passant marked with `e.p.` (`exd6e.p.`), `++` for
+//! checkmate and null moves written as `--` or `Z0`. Every move is written
+//! back out in the standard form.
+//!
+//! Variant moves are accepted too: Crazyhouse drops like `N@f3` and
+//! promotion to a king for Antichess. Whether they are legal is up to the
+//! variant the game is played in.
+
+use serde::Serialize;
+
+/// A kind of piece named in a move
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum SanPiece {
+    Pawn,
+    Knight,
+    Bishop,
+    Rook,
+    Queen,
+    King,
+}
+
+impl SanPiece {
+    /// The piece for an uppercase letter like 'N'
+    pub fn from_char(letter: char) -> Option<Self> {
+        match letter {
+            'P' => Some(SanPiece::Pawn),
+            'N' => Some(SanPiece::Knight),
+            'B' => Some(SanPiece::Bishop),
+            'R' => Some(SanPiece::Rook),
+            'Q' => Some(SanPiece::Queen),
+            'K' => Some(SanPiece::King),
+            _ => None,
+        }
+    }
+
+    /// The uppercase letter for the piece
+    pub fn to_char(self) -> char {
+        match self {
+            SanPiece::Pawn => 'P',
+            SanPiece::Knight => 'N',
+            SanPiece::Bishop => 'B',
+            SanPiece::Rook => 'R',
+            SanPiece::Queen => 'Q',
+            SanPiece::King => 'K',
+        }
+    }
+}
+
+/// Which side of the board a king castles to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum CastlingSide {
+    KingSide,
+    QueenSide,
+}
+
+/// What a move does, without its check or checkmate marker
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind")]
+pub enum SanKind {
+    /// A piece (or pawn) moving to a square
+    Normal {
+        piece: SanPiece,
+        /// File of the moving piece, when needed to tell pieces apart
+        from_file: Option<char>,
+        /// Rank of the moving piece, when needed to tell pieces apart
+        from_rank: Option<char>,
+        capture: bool,
+        /// Destination square like "e4"
+        to: String,
+        promotion: Option<SanPiece>,
+    },
+    Castle {
+        side: CastlingSide,
+    },
+    /// A piece from the pocket dropped onto a square, in Crazyhouse
+    Drop {
+        piece: SanPiece,
+        /// Destination square like "e4"
+        to: String,
+    },
+    /// Passing the turn without moving, used in analysis
+    Null,
+}
+
+/// Check or checkmate marker at the end of a move
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum SanSuffix {
+    Check,
+    Checkmate,
+}
+
+/// A move in Standard Algebraic Notation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SanMove {
+    pub kind: SanKind,
+    pub suffix: Option<SanSuffix>,
+}
+
+impl SanMove {
+    /// Whether this is a null move
+    pub fn is_null(&self) -> bool {
+        self.kind == SanKind::Null
+    }
+}
+
+impl std::str::FromStr for SanMove {
+    /// Why the move isn't valid SAN
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        if text.is_empty() {
+            return Err("Empty move".to_string());
+        }
+
+        let (text, suffix) = if let Some(text) = text.strip_suffix("++") {
+            (text, Some(SanSuffix::Checkmate))
+        } else if let Some(text) = text.strip_suffix('#') {
+            (text, Some(SanSuffix::Checkmate))
+        } else if let Some(text) = text.strip_suffix('+') {
+            (text, Some(SanSuffix::Check))
+        } else {
+            (text, None)
+        };
+
+        let kind = match text {
+            "--" | "Z0" => SanKind::Null,
+            "O-O" | "0-0" => SanKind::Castle {
+                side: CastlingSide::KingSide,
+            },
+            "O-O-O" | "0-0-0" => SanKind::Castle {
+                side: CastlingSide::QueenSide,
+            },
+            _ if text.contains('@') => parse_drop(text)?,
+            _ => parse_normal(text)?,
+        };
+
+        Ok(SanMove { kind, suffix })
+    }
+}
+
+/// Parse a drop like "N@f3", or "@e4" for a pawn
+fn parse_drop(text: &str) -> Result<SanKind, String> {
+    let (piece, to) = text.split_once('@').unwrap_or(("", text));
+    let piece = match piece.chars().collect::<Vec<_>>()[..] {
+        [] => SanPiece::Pawn,
+        [letter] => SanPiece::from_char(letter)
+            .filter(|piece| *piece != SanPiece::King)
+            .ok_or_else(|| format!("Can't drop '{}'", letter))?,
+        _ => return Err(format!("Can't drop '{}'", piece)),
+    };
+    match to.chars().collect::<Vec<_>>()[..] {
+        [file, rank] if is_file(file) && is_rank(rank) => Ok(SanKind::Drop {
+            piece,
+            to: to.to_string(),
+        }),
+        _ => Err(format!("'{}' is not a square", to)),
+    }
+}
+
+/// Parse a piece or pawn move like "Nbxd7", "exd6e.p." or "e8=Q"
+fn parse_normal(text: &str) -> Result<SanKind, String> {
+    let (text, en_passant) = match text.strip_suffix("e.p.") {
+        Some(text) => (text, true),
+        None => (text, false),
+    };
+
+    let (piece, rest) = match text.chars().next().and_then(SanPiece::from_char)
+    {
+        Some(piece) => (piece, &text[1..]),
+        None => (SanPiece::Pawn, text),
+    };
+
+    // Promotion, either "=Q" or just "Q" after the destination
+    let (rest, promotion) = match rest.char_indices().last() {
+        Some((i, letter)) if letter.is_ascii_uppercase() => {
+            let promotion = SanPiece::from_char(letter)
+                .filter(|p| *p != SanPiece::Pawn)
+                .ok_or_else(|| format!("Can't promote to '{}'", letter))?;
+            let rest = &rest[..i];
+            (rest.strip_suffix('=').unwrap_or(rest), Some(promotion))
+        }
+        _ => (rest, None),
+    };
+
+    let chars: Vec<char> = rest.chars().collect();
+    if chars.len() < 2 {
+        return Err("Missing destination square".to_string());
+    }
+    let (before, to) = chars.split_at(chars.len() - 2);
+    if !is_file(to[0]) || !is_rank(to[1]) {
+        return Err(format!(
+            "'{}' is not a square",
+            to.iter().collect::<String>()
+        ));
+    }
+
+    let (from, capture) = match before.split_last() {
+        Some(('x', from)) | Some((':', from)) => (from, true),
+        _ => (before, false),
+    };
+    let (from_file, from_rank) = match from {
+        [] => (None, None),
+        [file] if is_file(*file) => (Some(*file), None),
+        [rank] if is_rank(*rank) => (None, Some(*rank)),
+        [file, rank] if is_file(*file) && is_rank(*rank) => {
+            (Some(*file), Some(*rank))
+        }
+        _ => {
+            return Err(format!(
+                "Unexpected '{}' before the destination",
+                from.iter().collect::<String>()
+            ))
+        }
+    };
+
+    if piece == SanPiece::Pawn {
+        if capture != from_file.is_some() || from_rank.is_some() {
+            return Err(
+                "Pawn captures name the file the pawn is on, like \"exd5\""
+                    .to_string(),
+            );
+        }
+        if promotion.is_some() && !matches!(to[1], '1' | '8') {
+            return Err("Pawns only promote on the last rank".to_string());
+        }
+    } else if promotion.is_some() {
+        return Err("Only pawns can promote".to_string());
+    }
+    if en_passant && (piece != SanPiece::Pawn || !capture) {
+        return Err("Only pawn captures can be en passant".to_string());
+    }
+
+    Ok(SanKind::Normal {
+        piece,
+        from_file,
+        from_rank,
+        capture,
+        to: to.iter().collect(),
+        promotion,
+    })
+}
+
+fn is_file(c: char) -> bool {
+    matches!(c, 'a'..='h')
+}
+
+fn is_rank(c: char) -> bool {
+    matches!(c, '1'..='8')
+}
+
+impl std::fmt::Display for SanMove {
+    /// Writes the move in the standard form, like "Nbxd7+" or "e8=Q"
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        match &self.kind {
+            SanKind::Normal {
+                piece,
+                from_file,
+                from_rank,
+                capture,
+                to,
+                promotion,
+            } => {
+                if *piece != SanPiece::Pawn {
+                    write!(f, "{}", piece.to_char())?;
+                }
+                if let Some(file) = from_file {
+                    write!(f, "{}", file)?;
+                }
+                if let Some(rank) = from_rank {
+                    write!(f, "{}", rank)?;
+                }
+                if *capture {
+                    write!(f, "x")?;
+                }
+                write!(f, "{}", to)?;
+                if let Some(promotion) = promotion {
+                    write!(f, "={}", promotion.to_char())?;
+                }
+            }
+            SanKind::Castle {
+                side: CastlingSide::KingSide,
+            } => write!(f, "O-O")?,
+            SanKind::Castle {
+                side: CastlingSide::QueenSide,
+            } => write!(f, "O-O-O")?,
+            SanKind::Drop { piece, to } => {
+                write!(f, "{}@{}", piece.to_char(), to)?
+            }
+            SanKind::Null => write!(f, "--")?,
+        }
+
+        match self.suffix {
+            Some(SanSuffix::Check) => write!(f, "+"),
+            Some(SanSuffix::Checkmate) => write!(f, "#"),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pgn::PgnParseError;
+
+    fn normalise(text: &str) -> String {
+        text.parse::<SanMove>().unwrap().to_string()
+    }
+
+    #[test]
+    fn standard_moves_are_parsed() {
+        let san: SanMove = "Nbxd7+".parse().unwrap();
+
+        assert_eq!(
+            san.kind,
+            SanKind::Normal {
+                piece: SanPiece::Knight,
+                from_file: Some('b'),
+                from_rank: None,
+                capture: true,
+                to: "d7".to_string(),
+                promotion: None,
+            }
+        );
+        assert_eq!(san.suffix, Some(SanSuffix::Check));
+        for text in ["e4", "exd5", "R1a3", "Qh4xe1#", "fxg8=N", "O-O-O+"] {
+            assert_eq!(normalise(text), text);
+        }
+    }
+
+    #[test]
+    fn common_variants_are_normalised() {
+        assert_eq!(normalise("0-0"), "O-O");
+        assert_eq!(normalise("0-0-0"), "O-O-O");
+        assert_eq!(normalise("e8Q"), "e8=Q");
+        assert_eq!(normalise("exd6e.p."), "exd6");
+        assert_eq!(normalise("Qg7++"), "Qg7#");
+        assert_eq!(normalise("Pe4"), "e4");
+        assert_eq!(normalise("Z0"), "--");
+        assert!("--".parse::<SanMove>().unwrap().is_null());
+    }
+
+    #[test]
+    fn variant_moves_are_parsed() {
+        let san: SanMove = "N@f3+".parse().unwrap();
+
+        assert_eq!(
+            san.kind,
+            SanKind::Drop {
+                piece: SanPiece::Knight,
+                to: "f3".to_string(),
+            }
+        );
+        assert_eq!(san.suffix, Some(SanSuffix::Check));
+        for text in ["P@e4", "Q@h7#", "e8=K", "bxa1=K"] {
+            assert_eq!(normalise(text), text);
+        }
+        assert_eq!(normalise("@e4"), "P@e4");
+    }
+
+    #[test]
+    fn invalid_moves_are_rejected() {
+        for text in [
+            "e9", "Nf", "xe4", "ed5", "e4=Q", "Ne8=Q", "e8=P", "Nzf3", "K@e4",
+            "N@e9", "Nb@c3",
+        ] {
+            assert!(text.parse::<SanMove>().is_err(), "{} parsed", text);
+        }
+    }
+
+    #[test]
+    fn invalid_moves_are_reported_where_they_are() {
+        let pgn = "[Event \"?\"]\n[Site \"?\"]\n[Date \"?\"]\n[Round \"?\"]\n\
+                   [White \"?\"]\n[Black \"?\"]\n[Result \"*\"]\n\n\
+                   1. e4 e5 2. Nf3 Nc9 *\n";
+
+        match crate::pgn::parse_pgn_game(pgn) {
+            Err(PgnParseError::InvalidMove {
+                move_text, span, ..
+            }) => {
+                assert_eq!(move_text, "Nc9");
+                assert_eq!((span.line, span.column), (9, 17));
+            }
+            other => panic!("Expected an invalid move, got {:?}", other),
+        }
+    }
+}
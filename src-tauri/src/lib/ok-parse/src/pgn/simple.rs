@@ -1,4 +1,5 @@
-use super::PgnToken;
+use super::nag::EVALUATION_SYMBOLS;
+use super::{PgnToken, SanMove, SourceSpan};
 use chumsky::prelude::*;
 
 /// Parse a tag name (allowing more characters than just identifiers)
@@ -16,6 +17,7 @@ fn tag_value() -> impl Parser<char, String, Error = Simple<char>> {
         .ignore_then(
             choice((
                 just('\\').ignore_then(just('"')).to('"'), // Handle escaped quotes
+                just('\\').ignore_then(just('\\')).to('\\'), // Handle escaped backslashes
                 filter(|&c| c != '"'),
             ))
             .repeated()
@@ -31,9 +33,10 @@ pub fn tag_parser() -> impl Parser<char, PgnToken, Error = Simple<char>> {
         .ignore_then(tag_name())
         .then(tag_value())
         .then_ignore(just(']'))
-        .map(|(name, value)| PgnToken::Tag {
+        .map_with_span(|(name, value), span| PgnToken::Tag {
             name: name.trim().to_string(),
             value,
+            span: SourceSpan::unresolved(span),
         })
         .labelled("tag")
 }
@@ -43,25 +46,39 @@ pub fn move_number_parser() -> impl Parser<char, PgnToken, Error = Simple<char>>
 {
     text::int(10)
         .then_ignore(just('.').repeated().at_least(1))
-        .map(|num: String| PgnToken::MoveNumber {
+        .map_with_span(|num: String, span| PgnToken::MoveNumber {
             number: num.parse().unwrap(),
+            span: SourceSpan::unresolved(span),
         })
         .labelled("move number")
 }
 
-/// Parse a chess move
+/// Parse a chess move in Standard Algebraic Notation
 ///
-/// Just captures the notation of the entire move
-/// does not validate any of the move's components
+/// Moves that aren't valid SAN fail with a custom error holding the
+/// reason, which is reported as `PgnParseError::InvalidMove`.
 ///
 /// Does not include move suffix notation or annotation glyphs
 pub fn chess_move_parser() -> impl Parser<char, PgnToken, Error = Simple<char>>
 {
-    filter(|&c: &char| c.is_alphanumeric() || "+#=x-".contains(c))
+    filter(|&c: &char| c.is_alphanumeric() || "+#=x:@-".contains(c))
         .repeated()
         .at_least(1)
         .collect::<String>()
-        .map(|notation| PgnToken::Move { notation })
+        // En passant captures can be marked like "exd6e.p."
+        .then(just(".p.").or_not())
+        .try_map(|(mut notation, en_passant), span| {
+            if en_passant.is_some() {
+                notation.push_str(".p.");
+            }
+            notation
+                .parse::<SanMove>()
+                .map_err(|reason| Simple::custom(span, reason))
+        })
+        .map_with_span(|san, span| PgnToken::Move {
+            san,
+            span: SourceSpan::unresolved(span),
+        })
         .labelled("chess move")
 }
 
@@ -69,8 +86,9 @@ pub fn chess_move_parser() -> impl Parser<char, PgnToken, Error = Simple<char>>
 pub fn game_result_parser() -> impl Parser<char, PgnToken, Error = Simple<char>>
 {
     choice((just("1-0"), just("0-1"), just("1/2-1/2"), just("*")))
-        .map(|s: &str| PgnToken::Result {
+        .map_with_span(|s: &str, span| PgnToken::Result {
             result: s.to_string(),
+            span: SourceSpan::unresolved(span),
         })
         .labelled("game result")
 }
@@ -80,7 +98,25 @@ pub fn comment_parser() -> impl Parser<char, PgnToken, Error = Simple<char>> {
     just('{')
         .ignore_then(filter(|&c| c != '}').repeated().collect::<String>())
         .then_ignore(just('}'))
-        .map(|text| PgnToken::Comment { text })
+        .map_with_span(|text, span| PgnToken::Comment {
+            text,
+            span: SourceSpan::unresolved(span),
+        })
+        .labelled("comment")
+}
+
+/// Parse rest-of-line comments starting with a semicolon
+///
+/// These become ordinary comment tokens, so they are written back out
+/// as brace comments unless they contain a closing brace.
+pub fn line_comment_parser() -> impl Parser<char, PgnToken, Error = Simple<char>>
+{
+    just(';')
+        .ignore_then(filter(|&c| c != '\n').repeated().collect::<String>())
+        .map_with_span(|text, span| PgnToken::Comment {
+            text: text.trim().to_string(),
+            span: SourceSpan::unresolved(span),
+        })
         .labelled("comment")
 }
 
@@ -88,11 +124,27 @@ pub fn comment_parser() -> impl Parser<char, PgnToken, Error = Simple<char>> {
 pub fn nag_parser() -> impl Parser<char, PgnToken, Error = Simple<char>> {
     just('$')
         .ignore_then(text::int(10))
-        .map(|num: String| PgnToken::NAG {
+        .map_with_span(|num: String, span| PgnToken::NAG {
             code: num.parse().unwrap(),
+            span: SourceSpan::unresolved(span),
         })
         .labelled("nag")
 }
+
+/// Parse evaluation symbols like "+-" or "=" into their NAGs
+///
+/// These must be tried before moves, which can contain the same
+/// characters.
+pub fn evaluation_symbol_parser(
+) -> impl Parser<char, PgnToken, Error = Simple<char>> {
+    choice(EVALUATION_SYMBOLS.map(|(symbol, code)| just(symbol).to(code)))
+        .map_with_span(|code, span| PgnToken::NAG {
+            code,
+            span: SourceSpan::unresolved(span),
+        })
+        .labelled("evaluation symbol")
+}
+
 /// Parse move suffix notation like "?", "!", "??", "!!", "?!", "!?"
 pub fn move_suffix_parser() -> impl Parser<char, PgnToken, Error = Simple<char>>
 {
@@ -104,8 +156,9 @@ pub fn move_suffix_parser() -> impl Parser<char, PgnToken, Error = Simple<char>>
         just("?"),
         just("!"),
     ))
-    .map(|suffix: &str| PgnToken::MoveSuffixNotation {
+    .map_with_span(|suffix: &str, span| PgnToken::MoveSuffixNotation {
         suffix: suffix.to_string(),
+        span: SourceSpan::unresolved(span),
     })
     .labelled("move suffix")
 }
@@ -0,0 +1,193 @@
+//! Source locations for PGN tokens and errors
+
+use serde::Serialize;
+
+use super::{PgnParseError, PgnToken};
+
+/// Characters of the source shown before the error in an excerpt
+const EXCERPT_CHARS_BEFORE: usize = 30;
+
+/// Characters of the source shown from the error onwards in an excerpt
+const EXCERPT_CHARS_AFTER: usize = 40;
+
+/// The part of the source a token or error covers
+///
+/// `start` and `end` are byte offsets into the source's text once decoded
+/// to UTF-8, the same as offsets into the source itself for UTF-8
+/// sources. `line` and `column` (both starting at 1) locate `start`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct SourceSpan {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl SourceSpan {
+    /// A span covering nothing at the given position
+    pub fn at(
+        offset: usize,
+        line: usize,
+        column: usize,
+    ) -> Self {
+        Self {
+            start: offset,
+            end: offset,
+            line,
+            column,
+        }
+    }
+
+    /// A span from the parsers, holding character offsets relative to
+    /// the parsed text until it is resolved by a `SourceMap`
+    pub(crate) fn unresolved(chars: std::ops::Range<usize>) -> Self {
+        Self {
+            start: chars.start,
+            end: chars.end,
+            line: 0,
+            column: 0,
+        }
+    }
+}
+
+impl std::fmt::Display for SourceSpan {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+/// A short piece of the source line an error occurred on
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SourceExcerpt {
+    /// The source text around the error
+    pub text: String,
+    /// Character position of the error within `text`
+    pub caret: usize,
+}
+
+impl std::fmt::Display for SourceExcerpt {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        if self.text.is_empty() {
+            return Ok(());
+        }
+        write!(f, "\n\t{}\n\t{}^", self.text, " ".repeat(self.caret))
+    }
+}
+
+/// Converts parser spans over one piece of text into source spans
+///
+/// The text can start part way through a larger source, like a single
+/// game read from a file, in which case `base` is where the text starts.
+pub(crate) struct SourceMap<'a> {
+    text: &'a str,
+    base: SourceSpan,
+    /// Byte offset (relative to `text`) of each character, plus the end
+    char_offsets: Vec<usize>,
+}
+
+impl<'a> SourceMap<'a> {
+    pub fn new(
+        text: &'a str,
+        base: SourceSpan,
+    ) -> Self {
+        let char_offsets = text
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(std::iter::once(text.len()))
+            .collect();
+        Self {
+            text,
+            base,
+            char_offsets,
+        }
+    }
+
+    /// Get the source span for a range of characters in the text
+    pub fn span(
+        &self,
+        chars: std::ops::Range<usize>,
+    ) -> SourceSpan {
+        let last = self.char_offsets.len() - 1;
+        let start = self.char_offsets[chars.start.min(last)];
+        let end = self.char_offsets[chars.end.min(last)];
+
+        let before = &self.text[..start];
+        let (line, column) = match before.rfind('\n') {
+            Some(newline) => (
+                self.base.line + before.matches('\n').count(),
+                before[newline + 1..].chars().count() + 1,
+            ),
+            // Still on the line the text starts on
+            None => (self.base.line, self.base.column + before.chars().count()),
+        };
+
+        SourceSpan {
+            start: self.base.start + start,
+            end: self.base.start + end,
+            line,
+            column,
+        }
+    }
+
+    /// Resolve the spans of tokens produced by the parsers
+    pub fn resolve(
+        &self,
+        tokens: &mut [PgnToken],
+    ) {
+        for token in tokens {
+            let span = token.span_mut();
+            *span = self.span(span.start..span.end);
+            if let PgnToken::Variation { moves, .. } = token {
+                self.resolve(moves);
+            }
+        }
+    }
+
+    /// Get an excerpt of the line a source span starts on
+    pub fn excerpt(
+        &self,
+        span: &SourceSpan,
+    ) -> SourceExcerpt {
+        let offset = span
+            .start
+            .saturating_sub(self.base.start)
+            .min(self.text.len());
+        let line_start = self.text[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = self.text[offset..]
+            .find('\n')
+            .map_or(self.text.len(), |i| offset + i);
+
+        let before: Vec<char> = self.text[line_start..offset].chars().collect();
+        let skipped = before.len().saturating_sub(EXCERPT_CHARS_BEFORE);
+        let text: String = before[skipped..]
+            .iter()
+            .copied()
+            .chain(
+                self.text[offset..line_end]
+                    .chars()
+                    .take(EXCERPT_CHARS_AFTER),
+            )
+            .collect();
+
+        SourceExcerpt {
+            text: text.trim_end().to_string(),
+            caret: before.len() - skipped,
+        }
+    }
+
+    /// Fill in the excerpt of an error from its span
+    pub fn attach_excerpt(
+        &self,
+        mut error: PgnParseError,
+    ) -> PgnParseError {
+        let excerpt = self.excerpt(error.span());
+        *error.excerpt_mut() = excerpt;
+        error
+    }
+}
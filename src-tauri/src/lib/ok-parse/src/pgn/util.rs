@@ -1,5 +1,7 @@
 use chumsky::prelude::*;
 
+use super::SanMove;
+
 /// Parse a string token surrounded by quotes
 pub fn quoted_string() -> impl Parser<char, String, Error = Simple<char>> {
     just('"')
@@ -90,27 +92,7 @@ pub fn skip_whitespace_and_comments(
 
 /// Validate a chess move format
 pub fn is_valid_move_format(move_str: &str) -> bool {
-    if move_str.is_empty() {
-        return false;
-    }
-
-    // Check for castling
-    if matches!(move_str, "O-O" | "O-O-O" | "0-0" | "0-0-0") {
-        return true;
-    }
-
-    // Basic move format validation
-    let chars: Vec<char> = move_str.chars().collect();
-
-    // Should start with a piece or file
-    if !chars[0].is_ascii_uppercase() && !chars[0].is_ascii_lowercase() {
-        return false;
-    }
-
-    // Should contain only valid chess notation characters
-    chars
-        .iter()
-        .all(|&c| c.is_alphanumeric() || "+#=x-".contains(c))
+    move_str.parse::<SanMove>().is_ok()
 }
 
 /// Extract the file (column) from a square notation like "e4"
@@ -0,0 +1,360 @@
+//! Writes games in the PGN export format
+//!
+//! Tags start with the Seven Tag Roster in its standard order, followed
+//! by any other tags in the order they were given. Move numbers are
+//! written from the position of each move in the game rather than taken
+//! from `MoveNumber` tokens, so they are always correct, including the
+//! `N...` before black moves that start a variation or follow a comment
+//! or variation. Movetext is wrapped to fit within 80 columns.
+//!
+//! Comments are written in braces, except those containing a closing
+//! brace, which can't be in a brace comment. They are written as `;`
+//! comments, which run to the end of their line, instead.
+
+use super::complex::SEVEN_TAG_ROSTER;
+use super::{PgnGame, PgnToken};
+
+/// The longest a line of movetext is allowed to be
+pub const MAX_LINE_WIDTH: usize = 80;
+
+/// Atom that ends its line, after a `;` comment
+const LINE_BREAK: &str = "\n";
+
+/// Write a game in the PGN export format, ending with a blank line
+pub fn write_pgn_game(game: &PgnGame) -> String {
+    let tags: Vec<(&str, &str)> = game
+        .tags
+        .iter()
+        .filter_map(|tag| match tag {
+            PgnToken::Tag { name, value, .. } => {
+                Some((name.as_str(), value.as_str()))
+            }
+            _ => None,
+        })
+        .collect();
+    let tag_value = |name: &str| {
+        tags.iter()
+            .find(|(tag_name, _)| *tag_name == name)
+            .map(|(_, value)| *value)
+    };
+
+    let result = game
+        .result
+        .as_deref()
+        .or_else(|| tag_value("Result"))
+        .unwrap_or("*");
+
+    let mut pgn = String::new();
+    for (name, default) in SEVEN_TAG_ROSTER {
+        let value = match name {
+            "Result" => result,
+            _ => tag_value(name).unwrap_or(default),
+        };
+        pgn.push_str(&format_tag(name, value));
+    }
+    for (name, value) in &tags {
+        if !SEVEN_TAG_ROSTER.iter().any(|(roster, _)| roster == name) {
+            pgn.push_str(&format_tag(name, value));
+        }
+    }
+    pgn.push('\n');
+
+    let mut movetext = Movetext::default();
+    movetext.write_tokens(&game.moves, starting_ply(tag_value("FEN")));
+    movetext.push(result.to_string());
+    pgn.push_str(&movetext.wrap(MAX_LINE_WIDTH));
+    pgn.push_str("\n\n");
+    pgn
+}
+
+/// Format a tag pair, escaping quotes and backslashes in the value
+fn format_tag(
+    name: &str,
+    value: &str,
+) -> String {
+    let escaped = value.replace('\\', "\\\\").replace('"', "\\\"");
+    format!("[{} \"{}\"]\n", name, escaped)
+}
+
+/// The ply the game starts at, counting from 0 for white's first move
+///
+/// Games set up from a FEN start at the side to move and move number
+/// the FEN gives.
+fn starting_ply(fen: Option<&str>) -> u32 {
+    let Some(fen) = fen else {
+        return 0;
+    };
+    let fields: Vec<&str> = fen.split_whitespace().collect();
+    let black_to_move = fields.get(1) == Some(&"b");
+    let move_number = fields
+        .get(5)
+        .and_then(|number| number.parse::<u32>().ok())
+        .unwrap_or(1)
+        .max(1);
+    (move_number - 1) * 2 + black_to_move as u32
+}
+
+/// Movetext broken into the pieces lines can be wrapped between
+#[derive(Default)]
+struct Movetext {
+    atoms: Vec<String>,
+    /// Text to put in front of the next atom, like an opening parenthesis
+    prefix: String,
+}
+
+impl Movetext {
+    fn push(
+        &mut self,
+        atom: String,
+    ) {
+        self.atoms.push(std::mem::take(&mut self.prefix) + &atom);
+    }
+
+    /// Add text to the end of the last atom, like a closing parenthesis
+    fn append(
+        &mut self,
+        text: &str,
+    ) {
+        if !self.prefix.is_empty() {
+            let atom = std::mem::take(&mut self.prefix) + text;
+            self.atoms.push(atom);
+        } else if let Some(last) = self
+            .atoms
+            .last_mut()
+            .filter(|last| last.as_str() != LINE_BREAK)
+        {
+            last.push_str(text);
+        } else {
+            self.atoms.push(text.to_string());
+        }
+    }
+
+    /// Write a line of moves starting at `ply`
+    fn write_tokens(
+        &mut self,
+        tokens: &[PgnToken],
+        mut ply: u32,
+    ) {
+        // Black moves need their number at the start of a line of moves
+        // and after anything that interrupts it
+        let mut needs_number = true;
+        // The ply of the last move, which a variation replaces
+        let mut last_move_ply = ply;
+
+        for token in tokens {
+            match token {
+                PgnToken::Move { san, .. } => {
+                    let move_number = ply / 2 + 1;
+                    if ply.is_multiple_of(2) {
+                        self.push(format!("{}.", move_number));
+                    } else if needs_number {
+                        self.push(format!("{}...", move_number));
+                    }
+                    self.push(san.to_string());
+                    last_move_ply = ply;
+                    ply += 1;
+                    needs_number = false;
+                }
+                PgnToken::MoveSuffixNotation { suffix, .. } => {
+                    self.append(suffix);
+                }
+                PgnToken::NAG { code, .. } => {
+                    self.push(format!("${}", code));
+                }
+                PgnToken::Comment { text, .. } => {
+                    self.write_comment(text);
+                    needs_number = true;
+                }
+                PgnToken::Variation { moves, .. } => {
+                    self.prefix.push('(');
+                    self.write_tokens(moves, last_move_ply);
+                    self.append(")");
+                    needs_number = true;
+                }
+                // Move numbers are worked out from the moves, tags and
+                // the result are written separately
+                PgnToken::MoveNumber { .. }
+                | PgnToken::Result { .. }
+                | PgnToken::Tag { .. } => {}
+            }
+        }
+    }
+
+    /// Write a comment, split into words so long comments can be wrapped
+    ///
+    /// A brace comment can't contain its closing brace, so comments that
+    /// do are written as `;` comments, one for each of their lines.
+    fn write_comment(
+        &mut self,
+        text: &str,
+    ) {
+        if text.contains('}') {
+            for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+                self.push(format!("; {}", line));
+                self.atoms.push(LINE_BREAK.to_string());
+            }
+            return;
+        }
+
+        let mut words = text.split_whitespace().peekable();
+        if words.peek().is_none() {
+            self.push("{}".to_string());
+            return;
+        }
+
+        self.prefix.push('{');
+        while let Some(word) = words.next() {
+            if words.peek().is_none() {
+                self.push(format!("{}}}", word));
+            } else {
+                self.push(word.to_string());
+            }
+        }
+    }
+
+    /// Join the atoms with spaces, starting a new line before any atom
+    /// that would go past `width`
+    fn wrap(
+        &self,
+        width: usize,
+    ) -> String {
+        let mut text = String::new();
+        let mut line_length = 0;
+        for atom in &self.atoms {
+            if atom == LINE_BREAK {
+                text.push('\n');
+                line_length = 0;
+                continue;
+            }
+            let atom_length = atom.chars().count();
+            if line_length > 0 && line_length + 1 + atom_length > width {
+                text.push('\n');
+                line_length = 0;
+            } else if line_length > 0 {
+                text.push(' ');
+                line_length += 1;
+            }
+            text.push_str(atom);
+            line_length += atom_length;
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pgn::parse_pgn_games;
+
+    /// The parts of a move token that matter to a game, without spans
+    /// and move numbers, which the writer works out itself
+    fn content(tokens: &[PgnToken]) -> Vec<String> {
+        tokens
+            .iter()
+            .filter_map(|token| match token {
+                PgnToken::MoveNumber { .. } => None,
+                PgnToken::Comment { text, .. } => Some(format!(
+                    "{{{}}}",
+                    text.split_whitespace().collect::<Vec<_>>().join(" ")
+                )),
+                PgnToken::Variation { moves, .. } => {
+                    Some(format!("({})", content(moves).join(" ")))
+                }
+                token => Some(token.to_string().trim().to_string()),
+            })
+            .collect()
+    }
+
+    fn parse_one(pgn: &str) -> PgnGame {
+        let mut result = parse_pgn_games(pgn);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(result.games.len(), 1);
+        result.games.remove(0)
+    }
+
+    const GAME: &str = r#"[Black "Doe, \"Jane\""]
+[White "Smith\\John"]
+[ECO "B20"]
+[Event "Club Championship"]
+[Round "3"]
+[Site "?"]
+[Date "2024.05.01"]
+[Result "1-0"]
+
+1. e4 {[%clk 0:05:00] [%eval 0.3] A long comment that should be wrapped
+because it goes on and on well past the end of the line} c5 $6 (1... e5 2.
+Nf3 (2. f4 exf4) 2... Nc6 {Black is fine} (2... d6)) 2. Nf3! d6 3. d4 1-0
+"#;
+
+    #[test]
+    fn tags_start_with_the_seven_tag_roster() {
+        let pgn = write_pgn_game(&parse_one(GAME));
+        let tags: Vec<&str> = pgn.lines().take(8).collect();
+
+        assert_eq!(
+            tags,
+            vec![
+                r#"[Event "Club Championship"]"#,
+                r#"[Site "?"]"#,
+                r#"[Date "2024.05.01"]"#,
+                r#"[Round "3"]"#,
+                r#"[White "Smith\\John"]"#,
+                r#"[Black "Doe, \"Jane\""]"#,
+                r#"[Result "1-0"]"#,
+                r#"[ECO "B20"]"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn movetext_has_correct_move_numbers_and_fits_in_80_columns() {
+        let pgn = write_pgn_game(&parse_one(GAME));
+        let movetext = pgn.split("\n\n").nth(1).unwrap();
+
+        assert!(movetext.lines().all(|line| line.len() <= MAX_LINE_WIDTH));
+        let movetext = movetext.replace('\n', " ");
+        assert!(movetext.starts_with("1. e4 {[%clk 0:05:00]"));
+        assert!(movetext.contains(
+            "c5 $6 (1... e5 2. Nf3 (2. f4 exf4) 2... Nc6 {Black is fine} \
+             (2... d6)) 2. Nf3! d6 3. d4 1-0"
+        ));
+    }
+
+    #[test]
+    fn written_games_round_trip() {
+        let game = parse_one(GAME);
+        let pgn = write_pgn_game(&game);
+        let reparsed = parse_one(&pgn);
+
+        assert_eq!(content(&reparsed.moves), content(&game.moves));
+        assert_eq!(reparsed.result, game.result);
+        assert_eq!(write_pgn_game(&reparsed), pgn);
+    }
+
+    #[test]
+    fn games_from_a_position_start_at_its_move_number() {
+        let pgn = GAME.split("\n\n").next().unwrap().to_string()
+            + "\n[FEN \"8/8/8/4k3/8/8/4K3/8 b - - 0 42\"]\n\n42... Kd5 43. Kd3 *";
+        let game = parse_one(&pgn);
+        let pgn = write_pgn_game(&game);
+
+        assert!(pgn.ends_with("42... Kd5 43. Kd3 *\n\n"));
+    }
+
+    #[test]
+    fn comments_with_closing_braces_are_kept() {
+        let pgn = GAME.split("\n\n").next().unwrap().to_string()
+            + "\n\n1. e4 ; a } that can't be in braces\ne5 (1... c5 ;} 2. Nf3\n) \
+               2. Nf3 {fine} 1-0";
+        let game = parse_one(&pgn);
+        let written = write_pgn_game(&game);
+
+        assert!(
+            written.contains("1. e4 ; a } that can't be in braces\n1... e5")
+        );
+        assert!(written.contains("(1... c5 ; } 2. Nf3\n) 2. Nf3 {fine} 1-0"));
+        let reparsed = parse_one(&written);
+        assert_eq!(content(&reparsed.moves), content(&game.moves));
+        assert_eq!(write_pgn_game(&reparsed), written);
+    }
+}
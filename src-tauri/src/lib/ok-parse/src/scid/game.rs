@@ -0,0 +1,596 @@
+//! Game records of the game file (`.sg4`) of a SCID database
+//!
+//! A record holds the tags the index has no room for, the starting
+//! position of games that don't start from the standard one, and the moves
+//! with their NAGs, variations and comments.
+//!
+//! Each move is a byte naming the moving piece by its place in its side's
+//! piece list and, in the other four bits, where it goes relative to its
+//! square. Decoding moves means keeping the piece lists the way SCID does:
+//! the king is always first, and a captured piece's place is taken by the
+//! last piece of the list.
+
+use super::index::format_date;
+use super::{ByteReader, ScidError, ScidToken};
+
+/// Tag names stored as a single byte above this length
+const MAX_TAG_LEN: u8 = 240;
+/// Tag byte of the event date stored as a 3 byte date
+const BINARY_EVENT_DATE: u8 = 255;
+/// The tags stored as a single byte, from 241
+const COMMON_TAGS: [&str; 10] = [
+    "WhiteCountry",
+    "BlackCountry",
+    "Annotator",
+    "PlyCount",
+    "EventDate",
+    "Opening",
+    "Variation",
+    "Setup",
+    "Source",
+    "SetUp",
+];
+
+/// Move bytes with special meanings, which would be impossible king moves
+const ENCODE_NAG: u8 = 11;
+const ENCODE_COMMENT: u8 = 12;
+const ENCODE_START_MARKER: u8 = 13;
+const ENCODE_END_MARKER: u8 = 14;
+const ENCODE_END_GAME: u8 = 15;
+
+/// A decoded game record
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScidGameRecord {
+    /// Tags besides the ones kept in the index
+    pub tags: Vec<(String, String)>,
+    /// The starting position, for games not starting from the standard one
+    pub fen: Option<String>,
+    pub moves: Vec<ScidToken>,
+}
+
+/// Decode a game record from the game file
+pub fn decode_game(data: &[u8]) -> Result<ScidGameRecord, ScidError> {
+    let mut bytes = ByteReader::new(data, "game");
+    let mut record = ScidGameRecord {
+        tags: decode_tags(&mut bytes)?,
+        ..Default::default()
+    };
+
+    // Flags for a non-standard start, promotions and underpromotions
+    let flags = bytes.u8()?;
+    let mut board = if flags & 1 != 0 {
+        let fen = super::decode_text(bytes.terminated()?);
+        let board = Board::from_fen(&fen).ok_or_else(|| {
+            ScidError::InvalidRecord(format!("invalid start position {}", fen))
+        })?;
+        record.fen = Some(fen);
+        board
+    } else {
+        Board::standard()
+    };
+
+    if decode_line(&mut bytes, &mut board, &mut record.moves)? != LineEnd::Game
+    {
+        return Err(ScidError::InvalidRecord(
+            "main line ends like a variation".to_string(),
+        ));
+    }
+
+    // Comments follow the moves, in the order they're marked
+    fill_comments(&mut bytes, &mut record.moves)?;
+    Ok(record)
+}
+
+/// Decode the tags at the start of a record
+fn decode_tags(
+    bytes: &mut ByteReader
+) -> Result<Vec<(String, String)>, ScidError> {
+    let mut tags = Vec::new();
+    loop {
+        let tag_length = bytes.u8()?;
+        let name = match tag_length {
+            0 => return Ok(tags),
+            BINARY_EVENT_DATE => {
+                let date = bytes.u24()?;
+                tags.push(("EventDate".to_string(), format_date(date)));
+                continue;
+            }
+            length if length > MAX_TAG_LEN => COMMON_TAGS
+                .get(usize::from(length - MAX_TAG_LEN - 1))
+                .ok_or_else(|| {
+                    ScidError::InvalidRecord(format!("unknown tag {}", length))
+                })?
+                .to_string(),
+            length => super::decode_text(bytes.bytes(usize::from(length))?),
+        };
+        let value_length = usize::from(bytes.u8()?);
+        let value = super::decode_text(bytes.bytes(value_length)?);
+        tags.push((name, value));
+    }
+}
+
+/// How a line of moves ended
+#[derive(Debug, PartialEq, Eq)]
+enum LineEnd {
+    Variation,
+    Game,
+}
+
+/// Decode the moves of a line, and the variations within it, until the
+/// line's end marker
+fn decode_line(
+    bytes: &mut ByteReader,
+    board: &mut Board,
+    tokens: &mut Vec<ScidToken>,
+) -> Result<LineEnd, ScidError> {
+    // Variations are played from before the last move
+    let mut before_last = board.clone();
+    loop {
+        match bytes.u8()? {
+            ENCODE_NAG => tokens.push(ScidToken::Nag(bytes.u8()?)),
+            ENCODE_COMMENT => tokens.push(ScidToken::Comment(String::new())),
+            ENCODE_START_MARKER => {
+                let mut variation = Vec::new();
+                let mut variation_board = before_last.clone();
+                let end =
+                    decode_line(bytes, &mut variation_board, &mut variation)?;
+                if end != LineEnd::Variation {
+                    return Err(ScidError::InvalidRecord(
+                        "game ends inside a variation".to_string(),
+                    ));
+                }
+                tokens.push(ScidToken::Variation(variation));
+            }
+            ENCODE_END_MARKER => return Ok(LineEnd::Variation),
+            ENCODE_END_GAME => return Ok(LineEnd::Game),
+            byte => {
+                before_last = board.clone();
+                let uci =
+                    board.play_encoded(byte, bytes)?.ok_or_else(|| {
+                        ScidError::InvalidRecord(format!(
+                            "invalid move byte {:#04x}",
+                            byte
+                        ))
+                    })?;
+                tokens.push(ScidToken::Move(uci));
+            }
+        }
+    }
+}
+
+/// Fill in the comments marked in the moves from the strings that follow
+/// them
+fn fill_comments(
+    bytes: &mut ByteReader,
+    tokens: &mut [ScidToken],
+) -> Result<(), ScidError> {
+    for token in tokens {
+        match token {
+            ScidToken::Comment(text) => {
+                *text = super::decode_text(bytes.terminated()?);
+            }
+            ScidToken::Variation(moves) => fill_comments(bytes, moves)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Role {
+    Pawn,
+    Knight,
+    Bishop,
+    Rook,
+    Queen,
+    King,
+}
+
+const WHITE: usize = 0;
+const BLACK: usize = 1;
+
+/// The board with SCID's piece lists, just enough to tell which square
+/// an encoded move goes from and to
+#[derive(Debug, Clone)]
+struct Board {
+    squares: [Option<(usize, Role)>; 64],
+    /// Each side's pieces by their squares, the king first
+    pieces: [[u8; 16]; 2],
+    counts: [usize; 2],
+    turn: usize,
+}
+
+impl Board {
+    fn empty() -> Self {
+        Self {
+            squares: [None; 64],
+            pieces: [[0; 16]; 2],
+            counts: [0; 2],
+            turn: WHITE,
+        }
+    }
+
+    /// The standard starting position, with the pieces listed in the
+    /// order SCID sets them up
+    fn standard() -> Self {
+        let mut board = Self::empty();
+        let back_rank = [
+            (4, Role::King),
+            (0, Role::Rook),
+            (1, Role::Knight),
+            (2, Role::Bishop),
+            (3, Role::Queen),
+            (5, Role::Bishop),
+            (6, Role::Knight),
+            (7, Role::Rook),
+        ];
+        for (file, role) in back_rank {
+            board.add_piece(WHITE, role, file);
+            board.add_piece(BLACK, role, 56 + file);
+        }
+        for file in 0..8 {
+            board.add_piece(WHITE, Role::Pawn, 8 + file);
+        }
+        for file in 0..8 {
+            board.add_piece(BLACK, Role::Pawn, 48 + file);
+        }
+        board
+    }
+
+    /// A position from a FEN, with the pieces listed in the order the FEN
+    /// names them
+    fn from_fen(fen: &str) -> Option<Self> {
+        let mut fields = fen.split_whitespace();
+        let mut board = Self::empty();
+        let mut rank = 7u8;
+        let mut file = 0u8;
+        for c in fields.next()?.chars() {
+            match c {
+                '/' => {
+                    rank = rank.checked_sub(1)?;
+                    file = 0;
+                }
+                '1'..='8' => file += c as u8 - b'0',
+                _ => {
+                    let role = match c.to_ascii_lowercase() {
+                        'p' => Role::Pawn,
+                        'n' => Role::Knight,
+                        'b' => Role::Bishop,
+                        'r' => Role::Rook,
+                        'q' => Role::Queen,
+                        'k' => Role::King,
+                        _ => return None,
+                    };
+                    let color =
+                        if c.is_ascii_uppercase() { WHITE } else { BLACK };
+                    if file > 7 || board.counts[color] == 16 {
+                        return None;
+                    }
+                    board.add_piece(color, role, rank * 8 + file);
+                    file += 1;
+                }
+            }
+        }
+        board.turn = match fields.next() {
+            Some("b") => BLACK,
+            _ => WHITE,
+        };
+        Some(board)
+    }
+
+    /// Add a piece to the end of its side's list, or the start for a king
+    fn add_piece(
+        &mut self,
+        color: usize,
+        role: Role,
+        square: u8,
+    ) {
+        let count = self.counts[color];
+        if role == Role::King && count > 0 {
+            self.pieces[color][count] = self.pieces[color][0];
+            self.pieces[color][0] = square;
+        } else {
+            self.pieces[color][count] = square;
+        }
+        self.counts[color] += 1;
+        self.squares[usize::from(square)] = Some((color, role));
+    }
+
+    /// Take the piece on a square off the board, filling its place in the
+    /// list with the last piece
+    fn remove_piece(
+        &mut self,
+        square: u8,
+    ) {
+        let Some((color, _)) = self.squares[usize::from(square)].take() else {
+            return;
+        };
+        let count = self.counts[color];
+        if let Some(index) = self.pieces[color][..count]
+            .iter()
+            .position(|&s| s == square)
+        {
+            self.pieces[color][index] = self.pieces[color][count - 1];
+            self.counts[color] -= 1;
+        }
+    }
+
+    /// Move a piece without capturing, keeping its place in the list
+    fn move_piece(
+        &mut self,
+        from: u8,
+        to: u8,
+    ) {
+        let Some((color, role)) = self.squares[usize::from(from)].take() else {
+            return;
+        };
+        let count = self.counts[color];
+        if let Some(square) = self.pieces[color][..count]
+            .iter_mut()
+            .find(|square| **square == from)
+        {
+            *square = to;
+        }
+        self.squares[usize::from(to)] = Some((color, role));
+    }
+
+    /// Decode a move byte, reading a second byte for a queen's diagonal
+    /// move, and play it
+    ///
+    /// Returns the move in UCI, or None if the byte isn't a move in this
+    /// position.
+    fn play_encoded(
+        &mut self,
+        byte: u8,
+        bytes: &mut ByteReader,
+    ) -> Result<Option<String>, ScidError> {
+        let index = usize::from(byte >> 4);
+        let value = i32::from(byte & 0x0F);
+        if index >= self.counts[self.turn] {
+            return Ok(None);
+        }
+        let from = self.pieces[self.turn][index];
+        let Some((_, role)) = self.squares[usize::from(from)] else {
+            return Ok(None);
+        };
+        let from_square = i32::from(from);
+        let (file, rank) = (from_square % 8, from_square / 8);
+
+        let mut promotion = None;
+        let to = match role {
+            Role::King => {
+                const DIFFS: [i32; 11] = [0, -9, -8, -7, -1, 1, 7, 8, 9, -2, 2];
+                match DIFFS.get(value as usize) {
+                    Some(diff) => from_square + diff,
+                    None => return Ok(None),
+                }
+            }
+            Role::Queen if value < 8 && value == file => {
+                // Diagonal queen moves take a second byte with the square
+                i32::from(bytes.u8()?) - 64
+            }
+            Role::Queen | Role::Rook => {
+                if value >= 8 {
+                    (value - 8) * 8 + file
+                } else {
+                    rank * 8 + value
+                }
+            }
+            Role::Bishop => {
+                let file_diff = (value & 7) - file;
+                if value >= 8 {
+                    from_square - 7 * file_diff
+                } else {
+                    from_square + 9 * file_diff
+                }
+            }
+            Role::Knight => {
+                const DIFFS: [i32; 9] = [0, -17, -15, -10, -6, 6, 10, 15, 17];
+                match DIFFS.get(value as usize) {
+                    Some(&diff) if value > 0 => from_square + diff,
+                    _ => return Ok(None),
+                }
+            }
+            Role::Pawn => {
+                const DIFFS: [i32; 16] =
+                    [7, 8, 9, 7, 8, 9, 7, 8, 9, 7, 8, 9, 7, 8, 9, 16];
+                promotion = match value {
+                    3..=5 => Some(('q', Role::Queen)),
+                    6..=8 => Some(('r', Role::Rook)),
+                    9..=11 => Some(('b', Role::Bishop)),
+                    12..=14 => Some(('n', Role::Knight)),
+                    _ => None,
+                };
+                let diff = DIFFS[value as usize];
+                if self.turn == WHITE {
+                    from_square + diff
+                } else {
+                    from_square - diff
+                }
+            }
+        };
+        let Ok(to) = u8::try_from(to) else {
+            return Ok(None);
+        };
+        if to > 63 {
+            return Ok(None);
+        }
+
+        // A king "moving" to its own square is a null move
+        if to == from {
+            self.turn = 1 - self.turn;
+            return Ok(Some("0000".to_string()));
+        }
+
+        // Squares of corrupt records can be off the board, so they're
+        // checked before the board changes
+        let on_board = |square: Option<u8>| square.filter(|&s| s < 64);
+        let castling_rook = if role == Role::King && from.abs_diff(to) == 2 {
+            let squares = if to > from {
+                (on_board(from.checked_add(3)), on_board(from.checked_add(1)))
+            } else {
+                (on_board(from.checked_sub(4)), on_board(from.checked_sub(1)))
+            };
+            match squares {
+                (Some(rook_from), Some(rook_to)) => Some((rook_from, rook_to)),
+                _ => return Ok(None),
+            }
+        } else {
+            None
+        };
+        let en_passant = if role == Role::Pawn
+            && from % 8 != to % 8
+            && self.squares[usize::from(to)].is_none()
+        {
+            // The captured pawn is beside the pawn
+            let captured = if self.turn == WHITE {
+                to.checked_sub(8)
+            } else {
+                to.checked_add(8)
+            };
+            match on_board(captured) {
+                Some(captured) => Some(captured),
+                None => return Ok(None),
+            }
+        } else {
+            None
+        };
+
+        if let Some((rook_from, rook_to)) = castling_rook {
+            self.move_piece(rook_from, rook_to);
+        }
+        if let Some(captured) = en_passant {
+            self.remove_piece(captured);
+        }
+        if self.squares[usize::from(to)].is_some() {
+            self.remove_piece(to);
+        }
+        self.move_piece(from, to);
+        if let Some((_, role)) = promotion {
+            self.squares[usize::from(to)] = Some((self.turn, role));
+        }
+        self.turn = 1 - self.turn;
+
+        let mut uci = format!("{}{}", square_name(from), square_name(to));
+        if let Some((letter, _)) = promotion {
+            uci.push(letter);
+        }
+        Ok(Some(uci))
+    }
+}
+
+/// The name of a square like "e4", from a1 as 0 to h8 as 63
+fn square_name(square: u8) -> String {
+    format!("{}{}", char::from(b'a' + square % 8), square / 8 + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn moves(tokens: &[ScidToken]) -> Vec<&str> {
+        tokens
+            .iter()
+            .filter_map(|token| match token {
+                ScidToken::Move(uci) => Some(uci.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn decode_moves_variations_and_comments() {
+        let record = [
+            // An Annotator tag then the end of the tags
+            243,
+            3,
+            b'B',
+            b'o',
+            b'b',
+            0, // No flags
+            0, // 1. e4 $1 d5 2. exd5 {Center} Qxd5 (2... Nf6) 3. Nc3 h6
+            0xCF,
+            ENCODE_NAG,
+            1,
+            0xBF,
+            0xC0,
+            ENCODE_COMMENT,
+            0x4C,
+            ENCODE_START_MARKER,
+            0x61,
+            ENCODE_END_MARKER,
+            0x28,
+            0xB1,
+            ENCODE_END_GAME,
+            b'C',
+            b'e',
+            b'n',
+            b't',
+            b'e',
+            b'r',
+            0,
+        ];
+        let game = decode_game(&record).unwrap();
+        assert_eq!(
+            game.tags,
+            vec![("Annotator".to_string(), "Bob".to_string())]
+        );
+        assert_eq!(game.fen, None);
+        assert_eq!(
+            moves(&game.moves),
+            ["e2e4", "d7d5", "e4d5", "d8d5", "b1c3", "h7h6"]
+        );
+        assert_eq!(game.moves[1], ScidToken::Nag(1));
+        assert_eq!(game.moves[4], ScidToken::Comment("Center".to_string()));
+        assert_eq!(
+            game.moves[6],
+            ScidToken::Variation(vec![ScidToken::Move("g8f6".to_string())])
+        );
+    }
+
+    #[test]
+    fn decode_moves_from_a_position() {
+        let fen = "r3k3/1P6/8/8/8/8/8/4K2R w Kq - 0 1";
+        let mut record = vec![0, 1];
+        record.extend_from_slice(fen.as_bytes());
+        record.push(0);
+        // The king takes the first place in the list from the piece named
+        // before it, so White has Ke1, Pb7, Rh1 and Black Ke8, Ra8.
+        // 1. O-O O-O-O 2. b8=Q Rd1 3. Qa7, the queen's diagonal move
+        // taking a second byte
+        record.extend_from_slice(&[
+            0x0A,
+            0x09,
+            0x14,
+            0x18,
+            0x11,
+            48 + 64,
+            ENCODE_END_GAME,
+        ]);
+        let game = decode_game(&record).unwrap();
+        assert_eq!(game.fen.as_deref(), Some(fen));
+        assert_eq!(
+            moves(&game.moves),
+            ["e1g1", "e8c8", "b7b8q", "d8d1", "b8a7"]
+        );
+    }
+
+    #[test]
+    fn moves_off_the_board_are_invalid() {
+        // Castling queenside from c1 and kingside from f8 would put the
+        // rook off the board, as would a pawn on a1 taking en passant on h1
+        for (fen, byte) in [
+            ("4k3/8/8/8/8/8/8/2K5 w - - 0 1", 0x09),
+            ("5k2/8/8/8/8/8/8/K7 b - - 0 1", 0x0A),
+            ("4k3/8/8/8/8/8/8/P3K3 w - - 0 1", 0x10),
+        ] {
+            let mut record = vec![0, 1];
+            record.extend_from_slice(fen.as_bytes());
+            record.extend_from_slice(&[0, byte, ENCODE_END_GAME]);
+            assert!(matches!(
+                decode_game(&record),
+                Err(ScidError::InvalidRecord(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,208 @@
+//! The index file (`.si4`) of a SCID database
+//!
+//! The index starts with a header followed by a fixed size entry for each
+//! game, holding where the game is in the game file along with the data
+//! SCID searches on, like the players' name IDs, the date and the result.
+
+use super::{ByteReader, ScidError};
+
+/// Magic bytes the index file starts with
+const INDEX_MAGIC: &[u8; 8] = b"Scid.si\0";
+/// Size of the header of version 3 index files, version 4 adds the
+/// descriptions of the custom flags
+const HEADER_SIZE_V3: usize = 128;
+const HEADER_SIZE_V4: usize = 182;
+/// Size of each game's entry, version 4 adds a byte for longer games
+const ENTRY_SIZE_V3: usize = 46;
+const ENTRY_SIZE_V4: usize = 47;
+/// The index flag marking a game as deleted
+const FLAG_DELETE: u16 = 1 << 3;
+
+/// The header of an index file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScidIndexHeader {
+    /// Format version, like 400 for SCID 4
+    pub version: u16,
+    /// Number of games in the database, including deleted ones
+    pub num_games: u32,
+    /// The database's description
+    pub description: String,
+}
+
+impl ScidIndexHeader {
+    /// Size of the header in bytes for the version that wrote it
+    pub fn size(version: u16) -> usize {
+        if version >= 400 {
+            HEADER_SIZE_V4
+        } else {
+            HEADER_SIZE_V3
+        }
+    }
+
+    /// Size of each entry in bytes
+    pub fn entry_size(&self) -> usize {
+        if self.version >= 400 {
+            ENTRY_SIZE_V4
+        } else {
+            ENTRY_SIZE_V3
+        }
+    }
+
+    /// Parse the header from the start of an index file, which must hold
+    /// at least the version 3 header's bytes
+    pub fn parse(data: &[u8]) -> Result<Self, ScidError> {
+        let mut bytes = ByteReader::new(data, "index");
+        if bytes.bytes(8)? != INDEX_MAGIC {
+            return Err(ScidError::InvalidMagic("index"));
+        }
+        let version = bytes.u16()?;
+        if !(300..=400).contains(&version) {
+            return Err(ScidError::UnsupportedVersion(version));
+        }
+        let _base_type = bytes.u32()?;
+        let num_games = bytes.u24()?;
+        let _auto_load = bytes.u24()?;
+        let description = bytes.bytes(108)?;
+        let description = description.split(|&b| b == 0).next().unwrap_or(&[]);
+
+        Ok(Self {
+            version,
+            num_games,
+            description: super::decode_text(description),
+        })
+    }
+}
+
+/// The entry of a game in the index
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScidIndexEntry {
+    /// Where the game's record starts in the game file
+    pub offset: u32,
+    /// Length of the game's record in bytes
+    pub length: u32,
+    pub flags: u16,
+    pub white_id: u32,
+    pub black_id: u32,
+    pub event_id: u32,
+    pub site_id: u32,
+    pub round_id: u32,
+    /// 0 for an unknown result, 1 for a White win, 2 for a Black win and
+    /// 3 for a draw
+    pub result: u8,
+    /// The encoded ECO code, 0 if the game has none
+    pub eco: u16,
+    /// The encoded date the game was played on
+    pub date: u32,
+    /// Ratings, 0 if unknown
+    pub white_elo: u16,
+    pub black_elo: u16,
+    /// Number of plies in the main line
+    pub num_half_moves: u16,
+}
+
+impl ScidIndexEntry {
+    /// Parse an entry written by the given index version
+    pub fn parse(
+        data: &[u8],
+        version: u16,
+    ) -> Result<Self, ScidError> {
+        let mut bytes = ByteReader::new(data, "index");
+        let offset = bytes.u32()?;
+        let length_low = u32::from(bytes.u16()?);
+        // The top bit is the 17th bit of the length, the rest are custom
+        // flags
+        let length_high = if version >= 400 { bytes.u8()? } else { 0 };
+        let flags = bytes.u16()?;
+
+        // Name IDs are 20 bits, their top bits share a byte
+        let players_high = u32::from(bytes.u8()?);
+        let white_low = u32::from(bytes.u16()?);
+        let black_low = u32::from(bytes.u16()?);
+        let names_high = u32::from(bytes.u8()?);
+        let event_low = u32::from(bytes.u16()?);
+        let site_low = u32::from(bytes.u16()?);
+        let round_low = u32::from(bytes.u16()?);
+
+        // The result shares its two bytes with the numbers of
+        // variations, comments and NAGs
+        let var_counts = bytes.u16()?;
+        let eco = bytes.u16()?;
+        // The event date is in the top 12 bits, relative to the date
+        let dates = bytes.u32()?;
+        let white_elo = bytes.u16()? & 0x0FFF;
+        let black_elo = bytes.u16()? & 0x0FFF;
+        // The low byte of the ply count is the top byte of the final
+        // material signature
+        let final_material = bytes.u32()?;
+        let _stored_line = bytes.u8()?;
+        // The top two bits of the home pawn data are the ply count's high
+        // bits
+        let home_pawns = bytes.bytes(9)?;
+        let num_half_moves = ((u16::from(home_pawns[0]) >> 6) << 8)
+            | (final_material >> 24) as u16;
+
+        Ok(Self {
+            offset,
+            length: length_low + (u32::from(length_high & 0x80) << 9),
+            flags,
+            white_id: ((players_high >> 4) << 16) | white_low,
+            black_id: ((players_high & 0x0F) << 16) | black_low,
+            event_id: ((names_high >> 5) << 16) | event_low,
+            site_id: (((names_high >> 2) & 0x07) << 16) | site_low,
+            round_id: ((names_high & 0x03) << 16) | round_low,
+            result: (var_counts >> 12) as u8,
+            eco,
+            date: dates & 0x000F_FFFF,
+            white_elo,
+            black_elo,
+            num_half_moves,
+        })
+    }
+
+    /// Whether the game is marked for deletion
+    pub fn is_deleted(&self) -> bool {
+        self.flags & FLAG_DELETE != 0
+    }
+
+    /// The game's PGN result
+    pub fn result(&self) -> &'static str {
+        match self.result {
+            1 => "1-0",
+            2 => "0-1",
+            3 => "1/2-1/2",
+            _ => "*",
+        }
+    }
+
+    /// The game's ECO code like "B90", without SCID's extensions
+    pub fn eco(&self) -> Option<String> {
+        if self.eco == 0 {
+            return None;
+        }
+        let code = self.eco - 1;
+        let letter = char::from(b'A' + (code / 13100) as u8);
+        let number = (code % 13100) / 131;
+        Some(format!("{}{:02}", letter, number))
+    }
+
+    /// The game's date as a PGN date like "2024.01.31", with question
+    /// marks for unknown parts
+    pub fn date(&self) -> String {
+        format_date(self.date)
+    }
+}
+
+/// Write an encoded SCID date as a PGN date
+pub fn format_date(date: u32) -> String {
+    let year = date >> 9;
+    let month = (date >> 5) & 0x0F;
+    let day = date & 0x1F;
+    let part = |value: u32, width: usize| {
+        if value == 0 {
+            "?".repeat(width)
+        } else {
+            format!("{:0width$}", value, width = width)
+        }
+    };
+    format!("{}.{}.{}", part(year, 4), part(month, 2), part(day, 2))
+}
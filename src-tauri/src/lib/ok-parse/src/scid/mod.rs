@@ -0,0 +1,433 @@
+//! SCID databases
+//!
+//! A SCID v4 database is three files: the index (`.si4`) with an entry per
+//! game, the name file (`.sn4`) with the player, event, site and round
+//! names, and the game file (`.sg4`) with each game's moves, variations and
+//! comments. SCID 3 databases (version 300) differ only slightly and are
+//! read too.
+//!
+//! `ScidReader` reads the games one at a time, only the names are held in
+//! memory as a whole.
+
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+pub mod game;
+pub mod index;
+pub mod names;
+
+pub use game::{decode_game, ScidGameRecord};
+pub use index::{ScidIndexEntry, ScidIndexHeader};
+pub use names::{ScidNameBase, ScidNameKind};
+
+/// A SCID database that can't be read
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error, Serialize)]
+pub enum ScidError {
+    #[error("IO error: {0}")]
+    Io(String),
+    /// A file doesn't start the way the kind of file should
+    #[error("Not a SCID {0} file")]
+    InvalidMagic(&'static str),
+    #[error("Unsupported SCID version {0}")]
+    UnsupportedVersion(u16),
+    /// A file or game record ends part way through
+    #[error("Unexpected end of {0}")]
+    Truncated(&'static str),
+    #[error("Invalid name file: {0}")]
+    InvalidNames(String),
+    #[error("Invalid game record: {0}")]
+    InvalidRecord(String),
+}
+
+/// An error reading one game of a database
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error, Serialize)]
+#[error("Game {}: {error}", game_index + 1)]
+pub struct ScidGameError {
+    /// Index of the game in the database, starting at 0
+    pub game_index: usize,
+    pub error: ScidError,
+}
+
+/// A move, annotation or variation of a game, in the order they're
+/// played
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum ScidToken {
+    /// A move in UCI, "0000" for a null move
+    Move(String),
+    Nag(u8),
+    /// A comment on the move before it, or on the line if it comes first
+    Comment(String),
+    /// Alternatives to the move before it
+    Variation(Vec<ScidToken>),
+}
+
+/// A game read from a SCID database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScidGame {
+    /// Index of the game in the database, starting at 0
+    pub game_index: usize,
+    pub entry: ScidIndexEntry,
+    /// The game's tags, starting with the Seven Tag Roster
+    pub tags: Vec<(String, String)>,
+    /// The starting position, for games not starting from the standard one
+    pub fen: Option<String>,
+    pub moves: Vec<ScidToken>,
+}
+
+/// Reads the games of a SCID database one at a time
+pub struct ScidReader<I, G> {
+    index: I,
+    games: G,
+    header: ScidIndexHeader,
+    names: ScidNameBase,
+    games_read: usize,
+    /// Set once the index can't be read any further
+    failed: bool,
+}
+
+impl ScidReader<BufReader<File>, BufReader<File>> {
+    /// Open a database from the path of any of its three files, or its
+    /// path without an extension
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, ScidError> {
+        let base = database_base_path(path.as_ref());
+        let open = |extension: &str| {
+            File::open(base.with_extension(extension))
+                .map_err(|e| ScidError::Io(format!("{}: {}", extension, e)))
+        };
+        let names = std::fs::read(base.with_extension("sn4"))
+            .map_err(|e| ScidError::Io(format!("sn4: {}", e)))?;
+        Self::new(
+            BufReader::new(open("si4")?),
+            &names,
+            BufReader::new(open("sg4")?),
+        )
+    }
+}
+
+impl<I: Read, G: Read + Seek> ScidReader<I, G> {
+    /// Read a database from its index, the contents of its name file and
+    /// its game file
+    pub fn new(
+        mut index: I,
+        names: &[u8],
+        games: G,
+    ) -> Result<Self, ScidError> {
+        let mut header = vec![0; ScidIndexHeader::size(300)];
+        read_exact(&mut index, &mut header, "index")?;
+        let version = u16::from_be_bytes([header[8], header[9]]);
+        let extra = ScidIndexHeader::size(version) - header.len();
+        let mut rest = vec![0; extra];
+        read_exact(&mut index, &mut rest, "index")?;
+
+        Ok(Self {
+            index,
+            games,
+            header: ScidIndexHeader::parse(&header)?,
+            names: ScidNameBase::parse(names)?,
+            games_read: 0,
+            failed: false,
+        })
+    }
+
+    pub fn header(&self) -> &ScidIndexHeader {
+        &self.header
+    }
+
+    /// Number of games read so far, including those that failed
+    pub fn games_read(&self) -> usize {
+        self.games_read
+    }
+
+    /// Read the game with the next index entry
+    fn read_game(
+        &mut self,
+        entry: ScidIndexEntry,
+    ) -> Result<ScidGame, ScidError> {
+        let mut data = vec![0; entry.length as usize];
+        self.games
+            .seek(SeekFrom::Start(u64::from(entry.offset)))
+            .map_err(|e| ScidError::Io(e.to_string()))?;
+        read_exact(&mut self.games, &mut data, "game file")?;
+        let record = decode_game(&data)?;
+
+        let name =
+            |kind, id| self.names.name(kind, id).unwrap_or("?").to_string();
+        let mut tags = vec![
+            (
+                "Event".to_string(),
+                name(ScidNameKind::Event, entry.event_id),
+            ),
+            ("Site".to_string(), name(ScidNameKind::Site, entry.site_id)),
+            ("Date".to_string(), entry.date()),
+            (
+                "Round".to_string(),
+                name(ScidNameKind::Round, entry.round_id),
+            ),
+            (
+                "White".to_string(),
+                name(ScidNameKind::Player, entry.white_id),
+            ),
+            (
+                "Black".to_string(),
+                name(ScidNameKind::Player, entry.black_id),
+            ),
+            ("Result".to_string(), entry.result().to_string()),
+        ];
+        if entry.white_elo > 0 {
+            tags.push(("WhiteElo".to_string(), entry.white_elo.to_string()));
+        }
+        if entry.black_elo > 0 {
+            tags.push(("BlackElo".to_string(), entry.black_elo.to_string()));
+        }
+        if let Some(eco) = entry.eco() {
+            tags.push(("ECO".to_string(), eco));
+        }
+        tags.extend(record.tags.into_iter().filter(|(name, _)| {
+            !matches!(name.as_str(), "SetUp" | "Setup" | "FEN")
+        }));
+        if let Some(fen) = &record.fen {
+            tags.push(("SetUp".to_string(), "1".to_string()));
+            tags.push(("FEN".to_string(), fen.clone()));
+        }
+
+        Ok(ScidGame {
+            game_index: self.games_read - 1,
+            entry,
+            tags,
+            fen: record.fen,
+            moves: record.moves,
+        })
+    }
+}
+
+impl<I: Read, G: Read + Seek> Iterator for ScidReader<I, G> {
+    type Item = Result<ScidGame, ScidGameError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.games_read >= self.header.num_games as usize {
+            return None;
+        }
+        let game_index = self.games_read;
+        self.games_read += 1;
+
+        // A game that can't be read is skipped, but an index that can't be
+        // read ends the database
+        let mut entry = vec![0; self.header.entry_size()];
+        let entry = read_exact(&mut self.index, &mut entry, "index")
+            .and_then(|_| ScidIndexEntry::parse(&entry, self.header.version));
+        let game = match entry {
+            Ok(entry) => self.read_game(entry),
+            Err(error) => {
+                self.failed = true;
+                Err(error)
+            }
+        };
+        Some(game.map_err(|error| ScidGameError { game_index, error }))
+    }
+}
+
+/// The path of a database without the extension of its files
+fn database_base_path(path: &Path) -> PathBuf {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("si4" | "sn4" | "sg4") => path.with_extension(""),
+        _ => path.to_path_buf(),
+    }
+}
+
+fn read_exact(
+    reader: &mut impl Read,
+    buffer: &mut [u8],
+    source: &'static str,
+) -> Result<(), ScidError> {
+    reader.read_exact(buffer).map_err(|e| match e.kind() {
+        std::io::ErrorKind::UnexpectedEof => ScidError::Truncated(source),
+        _ => ScidError::Io(e.to_string()),
+    })
+}
+
+/// Text of a name, tag or comment. SCID stores text as it was given,
+/// which is UTF-8 in recent databases and usually Latin-1 or
+/// Windows-1252 in older ones.
+pub(crate) fn decode_text(bytes: &[u8]) -> String {
+    crate::encoding::decode_text(bytes)
+}
+
+/// Reads the big-endian numbers and strings SCID files are made of
+pub(crate) struct ByteReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    /// The kind of data read, for errors
+    source: &'static str,
+}
+
+impl<'a> ByteReader<'a> {
+    pub(crate) fn new(
+        data: &'a [u8],
+        source: &'static str,
+    ) -> Self {
+        Self {
+            data,
+            position: 0,
+            source,
+        }
+    }
+
+    pub(crate) fn bytes(
+        &mut self,
+        length: usize,
+    ) -> Result<&'a [u8], ScidError> {
+        let bytes = self
+            .data
+            .get(self.position..self.position + length)
+            .ok_or(ScidError::Truncated(self.source))?;
+        self.position += length;
+        Ok(bytes)
+    }
+
+    /// A number of `length` bytes, at most 4
+    fn number(
+        &mut self,
+        length: usize,
+    ) -> Result<u32, ScidError> {
+        Ok(self
+            .bytes(length)?
+            .iter()
+            .fold(0, |number, &byte| (number << 8) | u32::from(byte)))
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, ScidError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub(crate) fn u16(&mut self) -> Result<u16, ScidError> {
+        Ok(self.number(2)? as u16)
+    }
+
+    pub(crate) fn u24(&mut self) -> Result<u32, ScidError> {
+        self.number(3)
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, ScidError> {
+        self.number(4)
+    }
+
+    /// A number stored in as few bytes as `max` needs
+    pub(crate) fn sized(
+        &mut self,
+        max: u32,
+    ) -> Result<u32, ScidError> {
+        match max {
+            0..=255 => self.number(1),
+            256..=65535 => self.number(2),
+            _ => self.number(3),
+        }
+    }
+
+    /// The bytes up to a zero byte, skipping the zero
+    pub(crate) fn terminated(&mut self) -> Result<&'a [u8], ScidError> {
+        let rest = &self.data[self.position..];
+        let length = rest
+            .iter()
+            .position(|&byte| byte == 0)
+            .ok_or(ScidError::Truncated(self.source))?;
+        self.position += length + 1;
+        Ok(&rest[..length])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// An index with a header for `num_games` and the given entries
+    fn index(
+        num_games: u32,
+        entries: &[[u8; 47]],
+    ) -> Vec<u8> {
+        let mut index = b"Scid.si\0".to_vec();
+        index.extend_from_slice(&400u16.to_be_bytes());
+        index.extend_from_slice(&[0; 4]);
+        index.extend_from_slice(&num_games.to_be_bytes()[1..]);
+        index.extend_from_slice(&[0; 3]);
+        let mut description = b"Test games".to_vec();
+        description.resize(108 + 54, 0);
+        index.extend_from_slice(&description);
+        for entry in entries {
+            index.extend_from_slice(entry);
+        }
+        index
+    }
+
+    #[test]
+    fn read_scid_database() {
+        // Two players front coded, "Carlsen, Magnus" then "Caruana,
+        // Fabiano" sharing "Car", and one event, site and round
+        let mut names = b"Scid.sn\0\0\0\0\0".to_vec();
+        names.extend_from_slice(&[0, 0, 2, 0, 0, 1, 0, 0, 1, 0, 0, 1]);
+        names.extend_from_slice(&[0, 0, 1, 0, 0, 1, 0, 0, 1, 0, 0, 1]);
+        names.extend_from_slice(&[0, 1, 15]);
+        names.extend_from_slice(b"Carlsen, Magnus");
+        names.extend_from_slice(&[1, 1, 16, 3]);
+        names.extend_from_slice(b"uana, Fabiano");
+        for name in ["Norway Chess", "Stavanger", "5"] {
+            names.extend_from_slice(&[0, 1, name.len() as u8]);
+            names.extend_from_slice(name.as_bytes());
+        }
+
+        // 1. e4, then a record that ends part way through
+        let games = vec![0, 0, 0xCF, 15, 0, 0];
+
+        let mut entry = [0; 47];
+        entry[4..6].copy_from_slice(&4u16.to_be_bytes());
+        entry[12..14].copy_from_slice(&1u16.to_be_bytes()); // Black
+        entry[21] = 0x10; // White won
+        entry[23..25].copy_from_slice(&(13100u16 + 90 * 131 + 1).to_be_bytes());
+        let date = (2024u32 << 9) | (5 << 5) | 28;
+        entry[25..29].copy_from_slice(&date.to_be_bytes());
+        entry[29..31].copy_from_slice(&2830u16.to_be_bytes());
+        let mut truncated = entry;
+        truncated[0..4].copy_from_slice(&4u32.to_be_bytes());
+        truncated[4..6].copy_from_slice(&2u16.to_be_bytes());
+
+        let mut reader = ScidReader::new(
+            Cursor::new(index(3, &[entry, truncated])),
+            &names,
+            Cursor::new(games),
+        )
+        .unwrap();
+        assert_eq!(reader.header().description, "Test games");
+
+        let game = reader.next().unwrap().unwrap();
+        let tag = |name: &str| {
+            game.tags
+                .iter()
+                .find(|(tag, _)| tag == name)
+                .map(|(_, value)| value.as_str())
+        };
+        assert_eq!(tag("White"), Some("Carlsen, Magnus"));
+        assert_eq!(tag("Black"), Some("Caruana, Fabiano"));
+        assert_eq!(tag("Event"), Some("Norway Chess"));
+        assert_eq!(tag("Round"), Some("5"));
+        assert_eq!(tag("Date"), Some("2024.05.28"));
+        assert_eq!(tag("Result"), Some("1-0"));
+        assert_eq!(tag("WhiteElo"), Some("2830"));
+        assert_eq!(tag("BlackElo"), None);
+        assert_eq!(tag("ECO"), Some("B90"));
+        assert_eq!(game.moves, vec![ScidToken::Move("e2e4".to_string())]);
+
+        let error = reader.next().unwrap().unwrap_err();
+        assert_eq!(error.game_index, 1);
+        assert_eq!(error.error, ScidError::Truncated("game"));
+
+        // The index claims a third game it has no entry for
+        assert_eq!(
+            reader.next().unwrap().unwrap_err().error,
+            ScidError::Truncated("index")
+        );
+        assert!(reader.next().is_none());
+    }
+}
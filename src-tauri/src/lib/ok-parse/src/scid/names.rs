@@ -0,0 +1,87 @@
+//! The name file (`.sn4`) of a SCID database
+//!
+//! Player, event, site and round names are stored once and referred to by
+//! ID from the index. Each kind of name is sorted and front coded: a name
+//! only stores what differs from the one before it.
+
+use super::{ByteReader, ScidError};
+
+/// Magic bytes the name file starts with
+const NAME_MAGIC: &[u8; 8] = b"Scid.sn\0";
+
+/// The kinds of names, in the order they're stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScidNameKind {
+    Player,
+    Event,
+    Site,
+    Round,
+}
+
+/// The names of a SCID database, by kind and ID
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScidNameBase {
+    names: [Vec<String>; 4],
+}
+
+impl ScidNameBase {
+    /// Parse a whole name file
+    pub fn parse(data: &[u8]) -> Result<Self, ScidError> {
+        let mut bytes = ByteReader::new(data, "name file");
+        if bytes.bytes(8)? != NAME_MAGIC {
+            return Err(ScidError::InvalidMagic("name"));
+        }
+        let _timestamp = bytes.u32()?;
+        let mut counts = [0; 4];
+        for count in &mut counts {
+            *count = bytes.u24()?;
+        }
+        let mut max_frequencies = [0; 4];
+        for frequency in &mut max_frequencies {
+            *frequency = bytes.u24()?;
+        }
+
+        let mut base = Self::default();
+        for (kind, (&count, &max_frequency)) in
+            counts.iter().zip(&max_frequencies).enumerate()
+        {
+            let names = &mut base.names[kind];
+            names.resize(count as usize, String::new());
+            let mut previous: Vec<u8> = Vec::new();
+            for i in 0..count {
+                // IDs and frequencies take as few bytes as the largest
+                // needs
+                let id = bytes.sized(count)? as usize;
+                let _frequency = bytes.sized(max_frequency)?;
+                let length = bytes.u8()? as usize;
+                let prefix = if i > 0 { bytes.u8()? as usize } else { 0 };
+                if prefix > length || prefix > previous.len() {
+                    return Err(ScidError::InvalidNames(format!(
+                        "name {} shares more than it has with the one before",
+                        i
+                    )));
+                }
+                let mut name = previous[..prefix].to_vec();
+                name.extend_from_slice(bytes.bytes(length - prefix)?);
+
+                let slot = names.get_mut(id).ok_or_else(|| {
+                    ScidError::InvalidNames(format!("name ID {} too large", id))
+                })?;
+                *slot = super::decode_text(&name);
+                previous = name;
+            }
+        }
+        Ok(base)
+    }
+
+    /// The name of a kind with the given ID
+    pub fn name(
+        &self,
+        kind: ScidNameKind,
+        id: u32,
+    ) -> Option<&str> {
+        self.names[kind as usize]
+            .get(id as usize)
+            .map(String::as_str)
+    }
+}
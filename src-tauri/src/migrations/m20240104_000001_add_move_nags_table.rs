@@ -0,0 +1,69 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(Iden)]
+enum MoveNag {
+    Table,
+    MoveNagId,
+    MoveId,
+    Nag,
+}
+
+#[derive(Iden)]
+enum Move {
+    Table,
+    MoveId,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(
+        &self,
+        manager: &SchemaManager,
+    ) -> Result<(), DbErr> {
+        // Numeric Annotation Glyphs of each move, in the order they're
+        // written (move suffixes like "!?" are stored as their NAG)
+        manager
+            .create_table(
+                Table::create()
+                    .table(MoveNag::Table)
+                    .if_not_exists()
+                    .col(pk_auto(MoveNag::MoveNagId))
+                    .col(integer(MoveNag::MoveId))
+                    .col(integer(MoveNag::Nag))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(MoveNag::Table, MoveNag::MoveId)
+                            .to(Move::Table, Move::MoveId)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_move_nag_move_id")
+                    .table(MoveNag::Table)
+                    .col(MoveNag::MoveId)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(
+        &self,
+        manager: &SchemaManager,
+    ) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(MoveNag::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
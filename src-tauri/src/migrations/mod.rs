@@ -3,6 +3,7 @@ use sea_orm_migration::prelude::*;
 pub mod m20240101_000001_create_tables;
 pub mod m20240102_000001_add_cascade_constraints;
 pub mod m20240103_000001_add_game_headers_table;
+pub mod m20240104_000001_add_move_nags_table;
 
 pub struct Migrator;
 
@@ -13,6 +14,7 @@ impl MigratorTrait for Migrator {
             Box::new(m20240101_000001_create_tables::Migration),
             Box::new(m20240102_000001_add_cascade_constraints::Migration),
             Box::new(m20240103_000001_add_game_headers_table::Migration),
+            Box::new(m20240104_000001_add_move_nags_table::Migration),
         ]
     }
 }
@@ -1,23 +1,63 @@
-use ok_parse::pgn::{PgnGame, PgnToken};
+use ok_parse::epd::EpdRecord;
+use ok_parse::json::chesscom::ChessComPlayer;
+use ok_parse::json::lichess::{LichessEval, LichessPlayer};
+use ok_parse::json::{ChessComGame, LichessGame};
+use ok_parse::pgn::{
+    parse_movetext, write_pgn_game, PgnGame, PgnReader, PgnToken, SourceSpan,
+    ValidationPolicy,
+};
+use ok_parse::scid::{ScidGame, ScidToken};
+use sea_orm::sqlx::types::chrono::DateTime;
+use shakmaty::san::SanPlus;
+use shakmaty::uci::UciMove;
+use shakmaty::{fen::Fen, variant::Variant, CastlingMode, Chess, Position};
 
 use crate::models::{
-    parse::pgn_tokens_to_move_tree, structs::ChessHeader, ChessPosition,
+    chess_variant,
+    parse::{pgn_tokens_to_move_tree, replay_pgn_tokens, IllegalMove},
+    structs::{ChessHeader, ChessTournament},
+    variant_name, ChessAnnotation, ChessEvaluation, ChessMove, ChessMoveTime,
+    ChessMoveTree, ChessPosition,
 };
+use crate::utils::AppError;
+
+use super::player_ops;
+use super::structs::{ChessGame, ChessPlayer};
+
+/// Converts a PgnGame into a ChessGame, failing at the first move that
+/// can't be played
+impl TryFrom<PgnGame> for ChessGame {
+    type Error = IllegalMove;
 
-use super::structs::ChessGame;
+    fn try_from(pgn_game: PgnGame) -> Result<Self, Self::Error> {
+        ChessGame::from_pgn_game(pgn_game, false).map(|(game, _)| game)
+    }
+}
 
-/// Converts a PgnGame into a ChessGame
-impl From<PgnGame> for ChessGame {
-    fn from(pgn_game: PgnGame) -> Self {
+impl ChessGame {
+    /// Convert a PgnGame into a ChessGame, playing its moves to check
+    /// they're legal
+    ///
+    /// An illegal move fails the conversion unless `keep_incomplete` is
+    /// set. Then the moves before it are kept instead: an illegal move in
+    /// a variation ends the variation, and one in the main line ends the
+    /// game, which is marked incomplete with a `*` result and an
+    /// "unterminated" `Termination` tag. The illegal moves are returned
+    /// along with the game.
+    pub fn from_pgn_game(
+        pgn_game: PgnGame,
+        keep_incomplete: bool,
+    ) -> Result<(Self, Vec<IllegalMove>), IllegalMove> {
         let mut chess_game = ChessGame::new_default();
 
         let pgn_tags = pgn_game.tags;
         let pgn_moves = pgn_game.moves;
         let pgn_result = pgn_game.result;
+        let mut setup_fen = None;
 
         // Parse and set headers/tags
         for tag in pgn_tags {
-            if let PgnToken::Tag { name, value } = tag {
+            if let PgnToken::Tag { name, value, .. } = tag {
                 match name.as_str() {
                     "Event" => {
                         let mut tournament =
@@ -61,7 +101,7 @@ impl From<PgnGame> for ChessGame {
                         opening.variation = Some(value);
                         chess_game.opening = Some(opening);
                     }
-                    "FEN" => chess_game.fen = Some(value),
+                    "FEN" => setup_fen = Some(value),
                     "Variant" => chess_game.variant = value,
                     _ => {
                         // All unknown tags are added to the headers
@@ -76,100 +116,733 @@ impl From<PgnGame> for ChessGame {
             }
         }
 
-        // Parse and set moves
+        // Variants are spelled many ways, store each under one name. Games
+        // without a Variant tag are still Chess960 if their starting
+        // position can only be castled from with Chess960 rules.
+        chess_game.variant = variant_name(&chess_game.variant);
+        if chess_variant(&chess_game.variant) == Some(Variant::Chess)
+            && setup_fen.as_deref().is_some_and(has_chess960_castling)
+        {
+            chess_game.variant = "Chess960".to_string();
+        }
+
+        // Parse and set moves, from the variant's starting position if the
+        // game doesn't set one up
         let variant = chess_game.variant.clone();
-        let starting_position = chess_game
-            .fen
-            .clone()
-            .map_or(ChessPosition::default(), |fen| {
+        let starting_position = match setup_fen {
+            Some(fen) => {
                 ChessPosition::from_fen(Some(fen), Some(variant)).unwrap()
-            });
-        chess_game.move_tree = pgn_tokens_to_move_tree(
-            chess_game.id,
-            starting_position,
-            &pgn_moves,
-        )
-        .unwrap();
+            }
+            None => ChessPosition::starting(&variant).unwrap_or_else(|_| {
+                ChessPosition {
+                    variant: Some(variant),
+                    ..ChessPosition::default()
+                }
+            }),
+        };
+        chess_game.fen = Some(starting_position.fen.clone());
+        if !keep_incomplete {
+            chess_game.move_tree = pgn_tokens_to_move_tree(
+                chess_game.id,
+                starting_position,
+                &pgn_moves,
+            )?;
+            chess_game.result = pgn_result.unwrap_or("*".to_string());
+            return Ok((chess_game, Vec::new()));
+        }
+
+        let replay =
+            replay_pgn_tokens(chess_game.id, starting_position, &pgn_moves);
+        chess_game.move_tree = replay.tree;
 
         // Set result
-        chess_game.result = pgn_result.unwrap_or("*".to_string());
+        if replay.complete {
+            chess_game.result = pgn_result.unwrap_or("*".to_string());
+        } else {
+            chess_game.result = "*".to_string();
+            chess_game
+                .headers
+                .retain(|header| header.name != "Termination");
+            chess_game.headers.push(ChessHeader {
+                id: None,
+                game_id: chess_game.id,
+                name: "Termination".to_string(),
+                value: "unterminated".to_string(),
+            });
+        }
 
-        chess_game
+        Ok((chess_game, replay.illegal_moves))
     }
 }
 
-/// Converts a ChessGame into a PGN string
-impl From<ChessGame> for String {
-    fn from(game: ChessGame) -> Self {
-        let mut pgn = String::new();
+impl ChessGame {
+    /// Convert a position from an EPD test suite into a game starting at
+    /// the position, with no moves
+    ///
+    /// The suite's name is the game's event and the position's line in
+    /// the suite, `index` + 1, its round. Each operation, like the best
+    /// moves (`bm`), is kept as a header named after its opcode, like
+    /// `EPD_bm`.
+    pub fn from_epd_record(
+        record: &EpdRecord,
+        suite_name: &str,
+        index: usize,
+    ) -> Self {
+        let fen = record.fen();
+        let mut game = ChessGame {
+            white_player: player_ops::new_player("?"),
+            black_player: player_ops::new_player("?"),
+            tournament: Some(ChessTournament {
+                name: suite_name.to_string(),
+                ..Default::default()
+            }),
+            round: Some(index as i32 + 1),
+            ..ChessGame::new_default()
+        };
+        if has_chess960_castling(&fen) {
+            game.variant = "Chess960".to_string();
+        }
 
-        // Add standard tags
-        pgn.push_str(&format!(
-            "[Event \"{}\"]\n",
-            game.tournament
+        // The move counters are already part of the FEN
+        for operation in &record.operations {
+            if operation.opcode == "hmvc" || operation.opcode == "fmvn" {
+                continue;
+            }
+            game.headers.push(ChessHeader {
+                id: None,
+                game_id: game.id,
+                name: format!("EPD_{}", operation.opcode),
+                value: operation.operands.join(" "),
+            });
+        }
+
+        let start = ChessPosition::from_fen(
+            Some(fen.clone()),
+            Some(game.variant.clone()),
+        )
+        .unwrap_or_default();
+        game.move_tree = ChessMoveTree::new(game.id, start);
+        game.fen = Some(fen);
+        game
+    }
+}
+
+impl ChessGame {
+    /// Convert a game from a lichess NDJSON export
+    ///
+    /// The game gets the tags lichess writes when exporting PGN, along
+    /// with each player's accuracy and average centipawn loss when the
+    /// game was analysed. Clocks and the server's evaluations are added
+    /// to the main line's moves, and its judgments of bad moves become
+    /// comments and NAGs.
+    pub fn from_lichess_game(game: &LichessGame) -> Result<Self, AppError> {
+        let mut tags = vec![
+            (
+                "Event".to_string(),
+                format!(
+                    "{} {} game",
+                    if game.rated { "Rated" } else { "Casual" },
+                    capitalize(game.speed.as_deref().unwrap_or("casual"))
+                ),
+            ),
+            (
+                "Site".to_string(),
+                format!("https://lichess.org/{}", game.id),
+            ),
+            ("Result".to_string(), game.result().to_string()),
+            ("TimeControl".to_string(), game.time_control()),
+            ("Termination".to_string(), game.termination().to_string()),
+        ];
+        if let Some(date) =
+            game.created_at.and_then(DateTime::from_timestamp_millis)
+        {
+            tags.push((
+                "Date".to_string(),
+                date.format("%Y.%m.%d").to_string(),
+            ));
+        }
+        for (color, player) in [
+            ("White", &game.players.white),
+            ("Black", &game.players.black),
+        ] {
+            tags.extend(lichess_player_tags(color, player));
+        }
+        if let Some(opening) = &game.opening {
+            if let Some(eco) = &opening.eco {
+                tags.push(("ECO".to_string(), eco.clone()));
+            }
+            // lichess names variations after the opening, like
+            // "Semi-Slav Defense: Marshall Gambit"
+            if let Some(name) = &opening.name {
+                match name.split_once(": ") {
+                    Some((name, variation)) => {
+                        tags.push(("Opening".to_string(), name.to_string()));
+                        tags.push((
+                            "Variation".to_string(),
+                            variation.to_string(),
+                        ));
+                    }
+                    None => tags.push(("Opening".to_string(), name.clone())),
+                }
+            }
+        }
+        if let Some(variant) = game.variant.as_deref() {
+            if !matches!(variant, "standard" | "fromPosition") {
+                tags.push(("Variant".to_string(), variant.to_string()));
+            }
+        }
+        if let Some(fen) = &game.initial_fen {
+            tags.push(("SetUp".to_string(), "1".to_string()));
+            tags.push(("FEN".to_string(), fen.clone()));
+        }
+
+        let mut pgn_game = parse_movetext(&game.moves)?;
+        pgn_game.tags = tags
+            .into_iter()
+            .map(|(name, value)| pgn_tag(name, value))
+            .collect();
+        // lichess leaves the result out of its moves, and the movetext's
+        // result would otherwise win over the tag
+        pgn_game
+            .result
+            .get_or_insert_with(|| game.result().to_string());
+        let (mut chess_game, _) = Self::from_pgn_game(pgn_game, false)
+            .map_err(|e| AppError::ChessError(e.to_string()))?;
+
+        set_json_player(
+            &mut chess_game.white_player,
+            game.players.white.rating,
+            game.players
+                .white
+                .user
                 .as_ref()
-                .map_or("Casual Game".to_string(), |t| t.name.clone())
-        ));
-        pgn.push_str(&format!(
-            "[Site \"{}\"]\n",
-            game.tournament
+                .and_then(|u| u.title.clone()),
+        );
+        set_json_player(
+            &mut chess_game.black_player,
+            game.players.black.rating,
+            game.players
+                .black
+                .user
                 .as_ref()
-                .and_then(|t| t.location.as_ref())
-                .map(String::as_str)
-                .unwrap_or("?")
-        ));
-        pgn.push_str(&format!("[Date \"{}\"]\n", game.date));
-        pgn.push_str(&format!(
-            "[Round \"{}\"]\n",
-            game.round.map_or("?".to_string(), |r| r.to_string())
-        ));
-        pgn.push_str(&format!("[White \"{}\"]\n", game.white_player.name));
-        pgn.push_str(&format!("[Black \"{}\"]\n", game.black_player.name));
-        pgn.push_str(&format!("[Result \"{}\"]\n", game.result));
+                .and_then(|u| u.title.clone()),
+        );
+        if let Some(tournament) = chess_game.tournament.as_mut() {
+            tournament.time_control = Some(game.time_control());
+        }
+
+        chess_game.annotate_main_line(|ply, game_move| {
+            if let Some(&centiseconds) = game.clocks.get(ply) {
+                game_move.time_info = Some(ChessMoveTime {
+                    time_spent_ms: None,
+                    time_left_ms: i32::try_from(centiseconds * 10).ok(),
+                });
+            }
+            let Some(eval) = game.analysis.get(ply) else {
+                return;
+            };
+            if let (Some(evaluation), Some(position)) =
+                (lichess_evaluation(eval), game_move.position.as_mut())
+            {
+                position.evaluations.push(evaluation);
+            }
+            if let Some(judgment) = &eval.judgment {
+                // The NAGs lichess marks judged moves with in its PGN
+                match judgment.name.as_str() {
+                    "Inaccuracy" => game_move.add_nag(6),
+                    "Mistake" => game_move.add_nag(2),
+                    "Blunder" => game_move.add_nag(4),
+                    _ => {}
+                }
+                if let Some(comment) = &judgment.comment {
+                    game_move.annotations.push(ChessAnnotation {
+                        id: 0,
+                        comment: Some(comment.clone()),
+                        arrows: None,
+                        highlights: None,
+                    });
+                }
+            }
+        });
+
+        Ok(chess_game)
+    }
+
+    /// Convert a game from a chess.com archive
+    ///
+    /// The game is read from its PGN, which has the clocks as `[%clk]`
+    /// comments. The players' ratings, their accuracies and the opening's
+    /// name, which the PGN leaves out, are added from the archive.
+    pub fn from_chesscom_game(game: &ChessComGame) -> Result<Self, AppError> {
+        let pgn = game.pgn.as_deref().unwrap_or_default();
+        let mut pgn_game = PgnReader::new(pgn.as_bytes())
+            .with_validation_policy(ValidationPolicy::Lenient)
+            .next()
+            .ok_or_else(|| {
+                AppError::GeneralError("Game has no PGN".to_string())
+            })?
+            .map_err(|e| e.error)?;
+
+        let has_tag = |pgn_game: &PgnGame, tag: &str| {
+            pgn_game.tags.iter().any(|token| {
+                matches!(token, PgnToken::Tag { name, .. } if name == tag)
+            })
+        };
+        let mut tags = Vec::new();
+        if let Some(accuracies) = &game.accuracies {
+            tags.push((
+                "WhiteAccuracy".to_string(),
+                accuracies.white.to_string(),
+            ));
+            tags.push((
+                "BlackAccuracy".to_string(),
+                accuracies.black.to_string(),
+            ));
+        }
+        if !has_tag(&pgn_game, "Opening") {
+            if let Some(name) = game.opening_name() {
+                tags.push(("Opening".to_string(), name));
+            }
+        }
+        if let Some(rules) = game.rules.as_deref() {
+            if rules != "chess" && !has_tag(&pgn_game, "Variant") {
+                tags.push(("Variant".to_string(), rules.to_string()));
+            }
+        }
+        if !has_tag(&pgn_game, "FEN") {
+            if let Some(fen) = &game.initial_setup {
+                tags.push(("FEN".to_string(), fen.clone()));
+            }
+        }
+        pgn_game
+            .tags
+            .extend(tags.into_iter().map(|(name, value)| pgn_tag(name, value)));
+
+        let (mut chess_game, _) = Self::from_pgn_game(pgn_game, false)
+            .map_err(|e| AppError::ChessError(e.to_string()))?;
+        set_chesscom_player(&mut chess_game.white_player, &game.white);
+        set_chesscom_player(&mut chess_game.black_player, &game.black);
+        if let Some(time_control) = &game.time_control {
+            let mut tournament = chess_game.tournament.unwrap_or_default();
+            tournament.time_control = Some(time_control.clone());
+            chess_game.tournament = Some(tournament);
+        }
+        Ok(chess_game)
+    }
+
+    /// Call `annotate` with each move of the main line and its index,
+    /// keeping each node's copy of the position in sync with its move's
+    fn annotate_main_line(
+        &mut self,
+        mut annotate: impl FnMut(usize, &mut ChessMove),
+    ) {
+        let tree = &mut self.move_tree;
+        let mut node_id = tree
+            .root_id
+            .and_then(|root_id| tree.nodes[root_id].children_ids.first())
+            .copied();
+        let mut ply = 0;
+        while let Some(id) = node_id {
+            let node = &mut tree.nodes[id];
+            if let Some(game_move) = node.game_move.as_mut() {
+                annotate(ply, game_move);
+                if let Some(position) = &game_move.position {
+                    node.position.evaluations = position.evaluations.clone();
+                }
+            }
+            node_id = node.children_ids.first().copied();
+            ply += 1;
+        }
+    }
+}
+
+impl ChessGame {
+    /// Convert a game read from a SCID database
+    ///
+    /// SCID stores moves by piece and square, so each is played to write
+    /// it as SAN. The game then goes through the same checks as an
+    /// imported PGN game.
+    pub fn from_scid_game(game: &ScidGame) -> Result<Self, AppError> {
+        let start = match &game.fen {
+            Some(fen) => fen
+                .parse::<Fen>()
+                .map_err(|e| AppError::ChessError(e.to_string()))?
+                .into_position::<Chess>(CastlingMode::Standard)
+                .map_err(|e| AppError::ChessError(e.to_string()))?,
+            None => Chess::default(),
+        };
+        let pgn_game = PgnGame {
+            tags: game
+                .tags
+                .iter()
+                .map(|(name, value)| pgn_tag(name.clone(), value.clone()))
+                .collect(),
+            moves: scid_tokens_to_pgn(&game.moves, start)
+                .map_err(AppError::ChessError)?,
+            result: Some(game.entry.result().to_string()),
+            span: SourceSpan::default(),
+            warnings: Vec::new(),
+            game_index: game.game_index,
+        };
+        Self::from_pgn_game(pgn_game, false)
+            .map(|(chess_game, _)| chess_game)
+            .map_err(|e| AppError::ChessError(e.to_string()))
+    }
+}
+
+/// Convert the moves of a SCID game to PGN tokens, playing them from
+/// `position` to write them as SAN
+fn scid_tokens_to_pgn(
+    tokens: &[ScidToken],
+    mut position: Chess,
+) -> Result<Vec<PgnToken>, String> {
+    let mut pgn_tokens = Vec::new();
+    // Variations are played from before the last move
+    let mut before_last = position.clone();
+    for token in tokens {
+        let span = SourceSpan::default();
+        match token {
+            ScidToken::Move(uci) => {
+                before_last = position.clone();
+                let san = if uci == "0000" {
+                    position = position
+                        .swap_turn()
+                        .map_err(|e| format!("Null move: {}", e))?;
+                    "--".to_string()
+                } else {
+                    let m = UciMove::from_ascii(uci.as_bytes())
+                        .map_err(|e| e.to_string())?
+                        .to_move(&position)
+                        .map_err(|e| format!("Illegal move {}: {}", uci, e))?;
+                    SanPlus::from_move_and_play_unchecked(&mut position, &m)
+                        .to_string()
+                };
+                pgn_tokens.push(PgnToken::Move {
+                    san: san.parse()?,
+                    span,
+                });
+            }
+            ScidToken::Nag(code) => {
+                pgn_tokens.push(PgnToken::NAG { code: *code, span });
+            }
+            ScidToken::Comment(text) => pgn_tokens.push(PgnToken::Comment {
+                text: text.clone(),
+                span,
+            }),
+            ScidToken::Variation(moves) => {
+                pgn_tokens.push(PgnToken::Variation {
+                    moves: scid_tokens_to_pgn(moves, before_last.clone())?,
+                    span,
+                });
+            }
+        }
+    }
+    Ok(pgn_tokens)
+}
+
+/// A PGN tag with the given name and value
+fn pgn_tag(
+    name: String,
+    value: String,
+) -> PgnToken {
+    PgnToken::Tag {
+        name,
+        value,
+        span: SourceSpan::default(),
+    }
+}
+
+/// The tags lichess writes for a player, along with the player's accuracy
+/// and average centipawn loss if the game was analysed
+fn lichess_player_tags(
+    color: &str,
+    player: &LichessPlayer,
+) -> Vec<(String, String)> {
+    let mut tags = vec![(color.to_string(), player.name())];
+    if let Some(rating) = player.rating {
+        tags.push((format!("{}Elo", color), rating.to_string()));
+    }
+    if let Some(title) = player.user.as_ref().and_then(|u| u.title.clone()) {
+        tags.push((format!("{}Title", color), title));
+    }
+    if let Some(diff) = player.rating_diff {
+        tags.push((format!("{}RatingDiff", color), format!("{:+}", diff)));
+    }
+    if let Some(analysis) = &player.analysis {
+        if let Some(accuracy) = analysis.accuracy {
+            tags.push((format!("{}Accuracy", color), accuracy.to_string()));
+        }
+        if let Some(acpl) = analysis.acpl {
+            tags.push((format!("{}ACPL", color), acpl.to_string()));
+        }
+    }
+    tags
+}
+
+/// The lichess server's evaluation of a position, stored the way a
+/// `[%eval]` comment is
+fn lichess_evaluation(eval: &LichessEval) -> Option<ChessEvaluation> {
+    let (score, is_mate) = match (eval.mate, eval.eval) {
+        (Some(moves), _) => (moves, true),
+        (None, Some(cp)) => (cp, false),
+        (None, None) => return None,
+    };
+    Some(ChessEvaluation {
+        score: Some(score as f32),
+        eval_type: Some(if is_mate { "mate" } else { "cp" }.to_string()),
+        is_mate,
+        depth: None,
+        engine: Some("lichess".to_string()),
+        principal_variation: Vec::new(),
+    })
+}
+
+/// Set the rating and title a JSON export gives a player
+fn set_json_player(
+    player: &mut ChessPlayer,
+    rating: Option<i32>,
+    title: Option<String>,
+) {
+    player.elo = rating.or(player.elo);
+    player.title = title.or(player.title.take());
+}
+
+/// Set a chess.com player's name and rating from the archive
+fn set_chesscom_player(
+    player: &mut ChessPlayer,
+    chesscom_player: &ChessComPlayer,
+) {
+    if !chesscom_player.username.is_empty() {
+        player.name = chesscom_player.username.clone();
+    }
+    set_json_player(player, chesscom_player.rating, None);
+}
+
+/// Uppercase the first letter of a word, like "blitz" to "Blitz"
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Whether a FEN's castling rights are only valid in Chess960, where the
+/// king and rooks don't have to start on their usual squares
+fn has_chess960_castling(fen: &str) -> bool {
+    let Ok(fen) = fen.parse::<Fen>() else {
+        return false;
+    };
+    fen.clone()
+        .into_position::<Chess>(CastlingMode::Standard)
+        .is_err()
+        && fen.into_position::<Chess>(CastlingMode::Chess960).is_ok()
+}
+
+/// Converts a ChessGame into a PgnGame, ready to be written out
+impl From<&ChessGame> for PgnGame {
+    fn from(game: &ChessGame) -> Self {
+        let mut tags: Vec<(String, String)> = vec![
+            (
+                "Event".to_string(),
+                game.tournament
+                    .as_ref()
+                    .map_or("Casual Game".to_string(), |t| t.name.clone()),
+            ),
+            (
+                "Site".to_string(),
+                game.tournament
+                    .as_ref()
+                    .and_then(|t| t.location.clone())
+                    .unwrap_or("?".to_string()),
+            ),
+            // PGN dates are always separated by dots
+            ("Date".to_string(), game.date.replace('-', ".")),
+            (
+                "Round".to_string(),
+                game.round.map_or("?".to_string(), |r| r.to_string()),
+            ),
+            ("White".to_string(), game.white_player.name.clone()),
+            ("Black".to_string(), game.black_player.name.clone()),
+            ("Result".to_string(), game.result.clone()),
+        ];
 
         if let Some(ref opening) = game.opening {
             if let Some(ref eco) = opening.eco {
-                pgn.push_str(&format!("[ECO \"{}\"]\n", eco));
+                tags.push(("ECO".to_string(), eco.clone()));
             }
             if let Some(ref name) = opening.name {
-                pgn.push_str(&format!("[Opening \"{}\"]\n", name));
+                tags.push(("Opening".to_string(), name.clone()));
             }
             if let Some(ref variation) = opening.variation {
-                pgn.push_str(&format!("[Variation \"{}\"]\n", variation));
+                tags.push(("Variation".to_string(), variation.clone()));
             }
         }
 
-        if let Some(ref elo) = game.white_player.elo {
-            pgn.push_str(&format!("[WhiteElo \"{}\"]\n", elo));
+        if let Some(elo) = game.white_player.elo {
+            tags.push(("WhiteElo".to_string(), elo.to_string()));
         }
-        if let Some(ref elo) = game.black_player.elo {
-            pgn.push_str(&format!("[BlackElo \"{}\"]\n", elo));
+        if let Some(elo) = game.black_player.elo {
+            tags.push(("BlackElo".to_string(), elo.to_string()));
         }
 
-        // Add any custom tags
+        if !game.variant.eq_ignore_ascii_case("standard") {
+            tags.push(("Variant".to_string(), game.variant.clone()));
+        }
+        if let Some(ref fen) = game.fen {
+            tags.push(("SetUp".to_string(), "1".to_string()));
+            tags.push(("FEN".to_string(), fen.clone()));
+        }
+
+        // Add any other headers, without repeating the ones above
+        for header in &game.headers {
+            if !tags.iter().any(|(name, _)| *name == header.name) {
+                tags.push((header.name.clone(), header.value.clone()));
+            }
+        }
+
+        // Add any custom tags, which are stored as `[Name "Value"]`
         for tag in &game.tags {
-            if !tag.starts_with("[Event ")
-                && !tag.starts_with("[Site ")
-                && !tag.starts_with("[Date ")
-                && !tag.starts_with("[Round ")
-                && !tag.starts_with("[White ")
-                && !tag.starts_with("[Black ")
-                && !tag.starts_with("[Result ")
-                && !tag.starts_with("[ECO ")
-                && !tag.starts_with("[Opening ")
-                && !tag.starts_with("[Variation ")
-                && !tag.starts_with("[WhiteElo ")
-                && !tag.starts_with("[BlackElo ")
-            {
-                pgn.push_str(&format!("{}\n", tag));
+            let Some((name, value)) = tag
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .split_once(' ')
+            else {
+                continue;
+            };
+            if !tags.iter().any(|(existing, _)| existing == name) {
+                tags.push((
+                    name.to_string(),
+                    value.trim_matches('"').to_string(),
+                ));
             }
         }
 
-        pgn.push('\n');
+        PgnGame {
+            tags: tags
+                .into_iter()
+                .map(|(name, value)| PgnToken::Tag {
+                    name,
+                    value,
+                    span: SourceSpan::default(),
+                })
+                .collect(),
+            moves: game.move_tree.to_pgn_tokens(),
+            result: Some(game.result.clone()),
+            span: SourceSpan::default(),
+            warnings: Vec::new(),
+            game_index: 0,
+        }
+    }
+}
+
+/// Converts a ChessGame into a PGN string
+impl From<ChessGame> for String {
+    fn from(game: ChessGame) -> Self {
+        game.to_pgn()
+    }
+}
+
+impl ChessGame {
+    /// Write the game in the PGN export format
+    pub fn to_pgn(&self) -> String {
+        write_pgn_game(&PgnGame::from(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ok_parse::json::{parse_chesscom_archive, parse_lichess_game};
+
+    use super::*;
+
+    /// The moves of a game's main line, in order
+    fn main_line_moves(game: &ChessGame) -> Vec<ChessMove> {
+        let tree = &game.move_tree;
+        let mut moves = Vec::new();
+        let mut node_id = tree.root_id;
+        while let Some(id) = node_id {
+            moves.extend(tree.nodes[id].game_move.clone());
+            node_id = tree.nodes[id].children_ids.first().copied();
+        }
+        moves
+    }
+
+    fn header<'a>(
+        game: &'a ChessGame,
+        name: &str,
+    ) -> Option<&'a str> {
+        game.headers
+            .iter()
+            .find(|header| header.name == name)
+            .map(|header| header.value.as_str())
+    }
+
+    #[test]
+    fn lichess_game_conversion() {
+        let json = r#"{"id":"q7ZvsdUF","rated":true,"variant":"standard","speed":"blitz","status":"resign","players":{"white":{"user":{"name":"Lance5500","title":"LM","id":"lance5500"},"rating":2389,"ratingDiff":4,"analysis":{"acpl":25,"accuracy":91}},"black":{"user":{"name":"Bob","id":"bob"},"rating":2210}},"winner":"white","opening":{"eco":"D31","name":"Semi-Slav Defense: Marshall Gambit","ply":7},"moves":"d4 d5 c4 c6 Nc3","clocks":[30003,29803,29503,29200],"analysis":[{"eval":18},{"eval":-4,"judgment":{"name":"Inaccuracy","comment":"Inaccuracy. Nf6 was best."}},{"eval":20,"judgment":{"name":"Mistake"}},{"eval":-300,"judgment":{"name":"Blunder"}}],"clock":{"initial":300,"increment":3}}"#;
+        let game = parse_lichess_game(json, 1).unwrap();
+        let game = ChessGame::from_lichess_game(&game).unwrap();
+
+        assert_eq!(game.white_player.name, "Lance5500");
+        assert_eq!(game.white_player.elo, Some(2389));
+        assert_eq!(game.white_player.title.as_deref(), Some("LM"));
+        assert_eq!(game.black_player.elo, Some(2210));
+        assert_eq!(game.black_player.title, None);
+        assert_eq!(game.result, "1-0");
+        assert_eq!(header(&game, "WhiteAccuracy"), Some("91"));
+        assert_eq!(header(&game, "WhiteRatingDiff"), Some("+4"));
+
+        let opening = game.opening.as_ref().unwrap();
+        assert_eq!(opening.eco.as_deref(), Some("D31"));
+        assert_eq!(opening.name.as_deref(), Some("Semi-Slav Defense"));
+        assert_eq!(opening.variation.as_deref(), Some("Marshall Gambit"));
+
+        let moves = main_line_moves(&game);
+        assert_eq!(moves.len(), 5);
+        let clocks: Vec<_> = moves
+            .iter()
+            .map(|game_move| game_move.time_info.as_ref()?.time_left_ms)
+            .collect();
+        assert_eq!(
+            clocks,
+            [Some(300030), Some(298030), Some(295030), Some(292000), None]
+        );
+        let nags: Vec<_> = moves
+            .iter()
+            .map(|game_move| game_move.nags.clone())
+            .collect();
+        assert_eq!(nags, [vec![], vec![6], vec![2], vec![4], vec![]]);
+        assert_eq!(
+            moves[1].annotations[0].comment.as_deref(),
+            Some("Inaccuracy. Nf6 was best.")
+        );
+    }
+
+    #[test]
+    fn chesscom_game_conversion() {
+        let json = r#"[{"url": "https://www.chess.com/game/live/1", "pgn": "[Event \"Live Chess\"]\n[White \"alice\"]\n[Black \"bob\"]\n[Result \"1-0\"]\n\n1. e4 {[%clk 0:02:59.9]} 1-0", "time_control": "180", "rules": "chess", "accuracies": {"white": 83.5, "black": 77.1}, "white": {"rating": 1500, "result": "win", "username": "alice"}, "black": {"rating": 1480, "result": "resigned", "username": "bob"}, "eco": "https://www.chess.com/openings/Kings-Pawn-Opening"}]"#;
+        let archive = parse_chesscom_archive(json).unwrap();
+        let game = ChessGame::from_chesscom_game(&archive.games[0].1).unwrap();
+
+        assert_eq!(game.white_player.name, "alice");
+        assert_eq!(game.white_player.elo, Some(1500));
+        assert_eq!(game.black_player.elo, Some(1480));
+        assert_eq!(header(&game, "WhiteAccuracy"), Some("83.5"));
+        assert_eq!(header(&game, "BlackAccuracy"), Some("77.1"));
+        assert_eq!(
+            game.opening.as_ref().unwrap().name.as_deref(),
+            Some("Kings Pawn Opening")
+        );
+        assert_eq!(
+            game.tournament.as_ref().unwrap().time_control.as_deref(),
+            Some("180")
+        );
 
-        // Add moves
-        pgn.push_str(&game.move_tree.to_pgn_moves());
-        pgn.push_str(&game.result);
-        pgn
+        let moves = main_line_moves(&game);
+        assert_eq!(
+            moves[0].time_info.as_ref().unwrap().time_left_ms,
+            Some(179900)
+        );
     }
 }
@@ -73,7 +73,8 @@ pub fn create_game_model(
         round_number: Set(game.round),
         date_played: Set(game_date),
         fen: Set(game.fen.clone()),
-        pgn: Set(game.pgn.clone().unwrap_or_default()),
+        pgn: Set(game.to_pgn()),
+        variant: Set(Some(game.variant.clone())),
         ..Default::default()
     };
 
@@ -6,8 +6,15 @@ pub mod player_ops;
 
 pub mod structs;
 
+use std::io::{BufRead, Read, Seek};
+
 use log::{debug, error, info, warn};
-use ok_parse::pgn::parse_pgn_games;
+use ok_parse::epd::{parse_epd_line, EpdParseError};
+use ok_parse::json::{
+    parse_chesscom_archive, parse_lichess_game, JsonParseError,
+};
+use ok_parse::pgn::PgnReader;
+use ok_parse::scid::ScidReader;
 use sea_orm::prelude::*;
 use sea_orm::sqlx::types::chrono;
 use sea_orm::ActiveValue::Set;
@@ -15,25 +22,37 @@ use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
 
 use super::parse::load_moves_from_db;
 use crate::entities::*;
-use crate::models::{ChessMoveTree, ChessPosition};
+use crate::models::{variant_name, ChessMoveTree, ChessPosition};
 use crate::utils::AppError;
 
 // Re-export structs for public API
-pub use structs::{ChessGame, ChessOpening, ChessPlayer, ChessTournament};
+pub use structs::{
+    ChessGame, ChessOpening, ChessPlayer, ChessTournament, GameWarning,
+    PgnImportOptions, PgnImportReport, SkippedGame,
+};
+
+/// Number of games read from a file that can wait to be saved, so reading
+/// doesn't run far ahead of the database
+const READ_AHEAD_GAMES: usize = 64;
 
 impl ChessGame {
-    /// Creates a new chess game with default starting position
+    /// Creates a new chess game with the variant's starting position
+    ///
+    /// Chess960 games start from the standard position, which is one of
+    /// the 960 starting positions.
     pub async fn new(
         variant: &str,
         db: &DatabaseConnection,
     ) -> Result<Self, AppError> {
-        if variant != "standard" {
-            // Display warning message
-            warn!(
-                "Warning: Non-standard chess variant ({}) support is in progress and may not work as expected.",
-                variant
-            );
-        }
+        let variant = variant_name(variant);
+        let starting_position = ChessPosition::starting(&variant)
+            .unwrap_or_else(|e| {
+                warn!("{}, starting from the standard position", e);
+                ChessPosition {
+                    variant: Some(variant.clone()),
+                    ..ChessPosition::default()
+                }
+            });
 
         // Create default players
         let white_player_id =
@@ -41,7 +60,6 @@ impl ChessGame {
         let black_player_id =
             player_ops::create_default_player(db, "Black Player").await?;
 
-        let starting_position = ChessPosition::default();
         let current_date = {
             let now = chrono::Utc::now();
             let formatted = format!("{}", now.format("%Y.%m.%d"));
@@ -56,12 +74,14 @@ impl ChessGame {
                 name: "White Player".to_string(),
                 elo: None,
                 country: None,
+                title: None,
             },
             black_player: ChessPlayer {
                 id: black_player_id,
                 name: "Black Player".to_string(),
                 elo: None,
                 country: None,
+                title: None,
             },
             tournament: None,
             opening: None,
@@ -71,9 +91,9 @@ impl ChessGame {
             headers: vec![],
             move_tree: ChessMoveTree::default(),
             tags: vec!["local".to_string()],
-            fen: Some(starting_position.fen),
+            fen: Some(starting_position.fen.clone()),
             pgn: None,
-            variant: variant.to_string(),
+            variant,
         };
 
         // Insert into database
@@ -87,6 +107,7 @@ impl ChessGame {
             date_played: Set(Some(current_date)),
             fen: Set(game.fen.clone()),
             pgn: Set("".to_string()),
+            variant: Set(Some(game.variant.clone())),
             created_at: Set(Some(chrono::Utc::now())),
             ..Default::default()
         };
@@ -102,6 +123,7 @@ impl ChessGame {
             })?;
 
         game.id = insert_result.last_insert_id;
+        game.move_tree = ChessMoveTree::new(game.id, starting_position);
         Ok(game)
     }
 
@@ -236,12 +258,14 @@ impl ChessGame {
                 name: white_player.name,
                 elo: white_player.elo_rating,
                 country: white_player.country_code,
+                title: white_player.title,
             },
             black_player: ChessPlayer {
                 id: black_player.player_id,
                 name: black_player.name,
                 elo: black_player.elo_rating,
                 country: black_player.country_code,
+                title: black_player.title,
             },
             tournament,
             opening,
@@ -262,10 +286,13 @@ impl ChessGame {
         &mut self,
         db: &DatabaseConnection,
     ) -> Result<(), AppError> {
-        let starting_position = ChessPosition::from_fen(self.fen.clone(), None)
-            .map_err(|e| {
-                AppError::ChessError(format!("Invalid FEN in game: {}", e))
-            })?;
+        let starting_position = ChessPosition::from_fen(
+            self.fen.clone(),
+            Some(self.variant.clone()),
+        )
+        .map_err(|e| {
+            AppError::ChessError(format!("Invalid FEN in game: {}", e))
+        })?;
 
         let move_tree = load_moves_from_db(db, self.id, starting_position)
             .await
@@ -328,86 +355,565 @@ impl ChessGame {
     }
 
     /// Saves multiple chess games from PGN format to the database
+    ///
+    /// Games that fail to parse or save are skipped and listed in the
+    /// returned report along with the reason. Missing tags and illegal
+    /// moves that `options` allow are listed as warnings.
     pub async fn save_from_pgn(
         db: &DatabaseConnection,
         pgn: &str,
-    ) -> Result<Vec<Self>, AppError> {
-        let chess_games: Vec<Self> = parse_pgn_games(pgn)
-            .map(|games| games.into_iter().map(|g| g.into()).collect())
-            .map_err(|e| AppError::ParseError(e.into_iter().next().unwrap()))?;
+        options: PgnImportOptions,
+    ) -> Result<PgnImportReport, AppError> {
+        Self::save_from_pgn_reader(
+            db,
+            std::io::Cursor::new(pgn.to_string()),
+            Some(pgn.len() as u64),
+            options,
+        )
+        .await
+    }
+
+    /// Saves chess games read one at a time from a PGN source
+    ///
+    /// Unlike parsing the whole source up front, the source is never held
+    /// in memory as a whole, so this is suitable for very large files.
+    /// `total_bytes` is the size of the source, if known, and is used to
+    /// log progress.
+    ///
+    /// Reading the source blocks, so games are read and parsed on a
+    /// blocking thread and handed over to be saved as they are parsed.
+    pub async fn save_from_pgn_reader<R: BufRead + Send + 'static>(
+        db: &DatabaseConnection,
+        reader: R,
+        total_bytes: Option<u64>,
+        options: PgnImportOptions,
+    ) -> Result<PgnImportReport, AppError> {
+        let policy = options.policy;
+        let (sender, mut receiver) =
+            tokio::sync::mpsc::channel(READ_AHEAD_GAMES);
+        let reading = tokio::task::spawn_blocking(move || {
+            let mut reader =
+                PgnReader::new(reader).with_validation_policy(policy);
+            while let Some(pgn_game) = reader.next() {
+                // The import stopped, there's no one left to save the games
+                if sender
+                    .blocking_send((pgn_game, reader.bytes_read()))
+                    .is_err()
+                {
+                    break;
+                }
+            }
+            (reader.games_read(), reader.encoding())
+        });
+        let mut report = PgnImportReport::default();
+        let mut games_received = 0;
 
-        if chess_games.is_empty() {
+        // Process games individually to prevent batch failures from crashing everything
+        // This also helps identify which specific game is causing issues
+        while let Some((pgn_game, bytes_read)) = receiver.recv().await {
+            games_received += 1;
+            let game_index = match &pgn_game {
+                Ok(pgn_game) => pgn_game.game_index,
+                Err(e) => e.game_index,
+            };
+            match pgn_game {
+                Ok(pgn_game) => {
+                    let mut warnings = pgn_game
+                        .warnings
+                        .iter()
+                        .map(|warning| GameWarning {
+                            game_index,
+                            line: warning.span().line,
+                            column: warning.span().column,
+                            message: warning.to_string(),
+                        })
+                        .collect::<Vec<_>>();
+                    match Self::from_pgn_game(
+                        pgn_game,
+                        options.keep_incomplete_games,
+                    ) {
+                        Ok((chess_game, illegal_moves)) => {
+                            warnings.extend(illegal_moves.iter().map(
+                                |illegal_move| GameWarning {
+                                    game_index,
+                                    line: illegal_move.span.line,
+                                    column: illegal_move.span.column,
+                                    message: illegal_move.to_string(),
+                                },
+                            ));
+                            Self::save_imported_game(
+                                db,
+                                game_index,
+                                &chess_game,
+                                warnings,
+                                &mut report,
+                            )
+                            .await;
+                        }
+                        Err(illegal_move) => {
+                            error!(
+                                "✗ Error replaying game {}: {}",
+                                game_index + 1,
+                                illegal_move
+                            );
+                            report.skipped.push(SkippedGame {
+                                game_index,
+                                line: Some(illegal_move.span.line),
+                                column: Some(illegal_move.span.column),
+                                snippet: Some(illegal_move.san.clone()),
+                                reason: illegal_move.to_string(),
+                            });
+                        }
+                    }
+                }
+                Err(e) => {
+                    error!("✗ Error parsing game {}: {}", game_index + 1, e);
+                    report.skipped.push(SkippedGame {
+                        game_index,
+                        line: Some(e.error.span().line),
+                        column: Some(e.error.span().column),
+                        snippet: Some(e.error.excerpt().text.clone()),
+                        reason: e.error.to_string(),
+                    });
+                }
+            }
+
+            if games_received % 100 == 0 {
+                match total_bytes {
+                    Some(total) if total > 0 => info!(
+                        "  → Read {} of {} bytes ({:.1}%), {} games saved",
+                        bytes_read,
+                        total,
+                        bytes_read as f64 / total as f64 * 100.0,
+                        report.imported
+                    ),
+                    _ => info!(
+                        "  → Read {} bytes, {} games saved",
+                        bytes_read, report.imported
+                    ),
+                }
+            }
+        }
+
+        let (games_read, encoding) = reading.await.map_err(|e| {
+            AppError::GeneralError(format!("Failed to read PGN: {}", e))
+        })?;
+        if games_read == 0 {
             return Err(AppError::GeneralError(
                 "No games found in PGN".to_string(),
             ));
         }
+        report.encoding = Some(encoding.to_string());
 
-        info!(
-            "Parsed {} games from PGN, starting import process...",
-            chess_games.len()
-        );
-        let mut saved_games = Vec::new();
-
-        // Process games individually to prevent batch failures from crashing everything
-        // This also helps identify which specific game is causing issues
-        for (game_index, chess_game) in chess_games.iter().enumerate() {
-            info!(
-                "Processing game {} of {} (White: {} vs Black: {})",
-                game_index + 1,
-                chess_games.len(),
-                chess_game.white_player.name,
-                chess_game.black_player.name
-            );
+        info!("Import completed: {}", report);
+        Ok(report)
+    }
 
-            // Pre-validate the game before attempting to save
-            let move_count = chess_game.move_tree.nodes.len();
-            if move_count > 2000 {
-                warn!(
-                    "⚠ Skipping game {} - too many moves ({}) might cause issues",
-                    game_index + 1,
-                    move_count
-                );
+    /// Parses an EPD test suite and saves each position as a game
+    /// starting at it, see `ChessGame::from_epd_record`
+    ///
+    /// Lines that aren't valid EPD and invalid positions are skipped and
+    /// listed in the report, with the line's index as the game index.
+    pub async fn save_from_epd(
+        db: &DatabaseConnection,
+        epd: &str,
+        suite_name: &str,
+    ) -> Result<PgnImportReport, AppError> {
+        let mut report = PgnImportReport::default();
+        let mut found_lines = false;
+
+        for (index, line) in epd.lines().enumerate() {
+            if line.trim().is_empty() {
                 continue;
             }
+            found_lines = true;
+            let record = match parse_epd_line(line, index + 1) {
+                Ok(record) => record,
+                Err(error) => {
+                    let EpdParseError::ParseFailure { line, input, .. } =
+                        &error;
+                    report.skipped.push(SkippedGame {
+                        game_index: index,
+                        line: Some(*line),
+                        column: None,
+                        snippet: Some(input.clone()),
+                        reason: error.to_string(),
+                    });
+                    continue;
+                }
+            };
+            let chess_game = Self::from_epd_record(&record, suite_name, index);
+            let start = ChessPosition::from_fen(
+                chess_game.fen.clone(),
+                Some(chess_game.variant.clone()),
+            )
+            .and_then(|position| position.to_position().map(|_| ()));
+            if let Err(e) = start {
+                report.skipped.push(SkippedGame {
+                    game_index: index,
+                    line: Some(index + 1),
+                    column: None,
+                    snippet: chess_game.fen.clone(),
+                    reason: format!("Invalid position: {}", e),
+                });
+                continue;
+            }
+            Self::save_imported_game(
+                db,
+                index,
+                &chess_game,
+                Vec::new(),
+                &mut report,
+            )
+            .await;
+        }
+
+        if !found_lines {
+            return Err(AppError::GeneralError(
+                "No positions found in EPD".to_string(),
+            ));
+        }
 
-            // Add detailed error handling for each game
-            match Self::save_single_game_with_retries(db, chess_game, 3).await {
-                Ok(game) => {
-                    saved_games.push(game);
-                    info!("✓ Successfully saved game {}", game_index + 1);
+        info!("EPD import completed: {}", report);
+        Ok(report)
+    }
+
+    /// Saves the games of a lichess NDJSON export, read one line at a time
+    ///
+    /// Lines that aren't valid games and games with illegal moves are
+    /// skipped and listed in the report, with the game's line as its
+    /// index. See `ChessGame::from_lichess_game` for what is kept.
+    ///
+    /// Like `ChessGame::save_from_pgn_reader`, lines are read and parsed
+    /// on a blocking thread and handed over to be saved.
+    pub async fn save_from_lichess_reader<R: BufRead + Send + 'static>(
+        db: &DatabaseConnection,
+        reader: R,
+    ) -> Result<PgnImportReport, AppError> {
+        let (sender, mut receiver) =
+            tokio::sync::mpsc::channel(READ_AHEAD_GAMES);
+        let reading = tokio::task::spawn_blocking(move || {
+            for (index, line) in reader.lines().enumerate() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let game = parse_lichess_game(&line, index + 1);
+                // The import stopped, there's no one left to save the games
+                if sender.blocking_send((index, game)).is_err() {
+                    break;
                 }
+            }
+            Ok::<_, std::io::Error>(())
+        });
+        let mut report = PgnImportReport::default();
+        let mut games_read = 0;
+
+        while let Some((index, game)) = receiver.recv().await {
+            games_read += 1;
+            let game = match game {
+                Ok(game) => game,
                 Err(e) => {
-                    error!(
-                        "✗ Error saving game {} (White: {} vs Black: {}): {}",
-                        game_index + 1,
-                        chess_game.white_player.name,
-                        chess_game.black_player.name,
-                        e
-                    );
-                    // Continue with other games instead of failing the entire batch
+                    error!("✗ Error parsing game on line {}: {}", index + 1, e);
+                    report.skipped.push(SkippedGame {
+                        game_index: index,
+                        line: Some(index + 1),
+                        column: None,
+                        snippet: None,
+                        reason: e.to_string(),
+                    });
+                    continue;
+                }
+            };
+            match Self::from_lichess_game(&game) {
+                Ok(chess_game) => {
+                    Self::save_imported_game(
+                        db,
+                        index,
+                        &chess_game,
+                        Vec::new(),
+                        &mut report,
+                    )
+                    .await;
                 }
+                Err(e) => report.skipped.push(SkippedGame {
+                    game_index: index,
+                    line: Some(index + 1),
+                    column: None,
+                    snippet: Some(game.id),
+                    reason: e.to_string(),
+                }),
             }
+        }
 
-            // Add a small delay to prevent overwhelming the database and allow cleanup
-            tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+        reading
+            .await
+            .map_err(|e| {
+                AppError::GeneralError(format!(
+                    "Failed to read lichess export: {}",
+                    e
+                ))
+            })?
+            .map_err(|e| AppError::IoError(e.to_string()))?;
+        if games_read == 0 {
+            return Err(AppError::GeneralError(
+                "No games found in lichess export".to_string(),
+            ));
+        }
+
+        info!("lichess import completed: {}", report);
+        Ok(report)
+    }
+
+    /// Saves the games of a chess.com archive
+    ///
+    /// Games that aren't valid or have illegal moves are skipped and
+    /// listed in the report. See `ChessGame::from_chesscom_game` for what
+    /// is kept.
+    ///
+    /// Archives are a single JSON document, so the whole archive is read
+    /// and parsed, on a blocking thread, before any game is saved.
+    pub async fn save_from_chesscom<R: Read + Send + 'static>(
+        db: &DatabaseConnection,
+        mut reader: R,
+    ) -> Result<PgnImportReport, AppError> {
+        let parsed = tokio::task::spawn_blocking(move || {
+            let mut json = String::new();
+            reader
+                .read_to_string(&mut json)
+                .map_err(|e| AppError::IoError(e.to_string()))?;
+            parse_chesscom_archive(&json)
+                .map_err(|e| AppError::GeneralError(e.to_string()))
+        })
+        .await
+        .map_err(|e| {
+            AppError::GeneralError(format!(
+                "Failed to read chess.com archive: {}",
+                e
+            ))
+        })??;
+        let mut report = PgnImportReport::default();
+
+        for error in &parsed.errors {
+            if let JsonParseError::InvalidArchiveGame { index, .. } = error {
+                report.skipped.push(SkippedGame {
+                    game_index: *index,
+                    line: None,
+                    column: None,
+                    snippet: None,
+                    reason: error.to_string(),
+                });
+            }
+        }
+
+        for &(index, ref game) in &parsed.games {
+            match Self::from_chesscom_game(game) {
+                Ok(chess_game) => {
+                    Self::save_imported_game(
+                        db,
+                        index,
+                        &chess_game,
+                        Vec::new(),
+                        &mut report,
+                    )
+                    .await;
+                }
+                Err(e) => report.skipped.push(SkippedGame {
+                    game_index: index,
+                    line: None,
+                    column: None,
+                    snippet: game.url.clone(),
+                    reason: e.to_string(),
+                }),
+            }
+        }
+
+        if parsed.games.is_empty() && parsed.errors.is_empty() {
+            return Err(AppError::GeneralError(
+                "No games found in chess.com archive".to_string(),
+            ));
+        }
+
+        info!("chess.com import completed: {}", report);
+        Ok(report)
+    }
+
+    /// Saves the games of a SCID database, read one at a time
+    ///
+    /// Games marked for deletion in SCID, games that can't be decoded and
+    /// games with illegal moves are skipped and listed in the report.
+    ///
+    /// Like `ChessGame::save_from_pgn_reader`, games are read on a
+    /// blocking thread and handed over to be saved.
+    pub async fn save_from_scid<I, G>(
+        db: &DatabaseConnection,
+        mut reader: ScidReader<I, G>,
+    ) -> Result<PgnImportReport, AppError>
+    where
+        I: Read + Send + 'static,
+        G: Read + Seek + Send + 'static,
+    {
+        let total = reader.header().num_games;
+        let (sender, mut receiver) =
+            tokio::sync::mpsc::channel(READ_AHEAD_GAMES);
+        let reading = tokio::task::spawn_blocking(move || {
+            while let Some(game) = reader.next() {
+                // The import stopped, there's no one left to save the games
+                if sender.blocking_send((game, reader.games_read())).is_err() {
+                    break;
+                }
+            }
+            reader.games_read()
+        });
+        let mut report = PgnImportReport::default();
+
+        while let Some((game, games_read)) = receiver.recv().await {
+            let game = match game {
+                Ok(game) => game,
+                Err(e) => {
+                    error!("✗ Error reading game {}: {}", e.game_index + 1, e);
+                    report.skipped.push(SkippedGame {
+                        game_index: e.game_index,
+                        line: None,
+                        column: None,
+                        snippet: None,
+                        reason: e.error.to_string(),
+                    });
+                    continue;
+                }
+            };
+
+            if game.entry.is_deleted() {
+                report.skipped.push(SkippedGame {
+                    game_index: game.game_index,
+                    line: None,
+                    column: None,
+                    snippet: None,
+                    reason: "Game is marked as deleted".to_string(),
+                });
+            } else {
+                match Self::from_scid_game(&game) {
+                    Ok(chess_game) => {
+                        Self::save_imported_game(
+                            db,
+                            game.game_index,
+                            &chess_game,
+                            Vec::new(),
+                            &mut report,
+                        )
+                        .await;
+                    }
+                    Err(e) => report.skipped.push(SkippedGame {
+                        game_index: game.game_index,
+                        line: None,
+                        column: None,
+                        snippet: None,
+                        reason: e.to_string(),
+                    }),
+                }
+            }
 
-            // Force garbage collection every 5 games to prevent memory buildup
-            if (game_index + 1) % 5 == 0 {
+            if games_read % 100 == 0 {
                 info!(
-                    "  → Processed {} games, allowing cleanup...",
-                    game_index + 1
+                    "  → Read {} of {} games, {} saved",
+                    games_read, total, report.imported
                 );
-                tokio::time::sleep(tokio::time::Duration::from_millis(100))
-                    .await;
             }
         }
 
+        let games_read = reading.await.map_err(|e| {
+            AppError::GeneralError(format!(
+                "Failed to read SCID database: {}",
+                e
+            ))
+        })?;
+        if games_read == 0 {
+            return Err(AppError::GeneralError(
+                "No games found in SCID database".to_string(),
+            ));
+        }
+
+        info!("SCID import completed: {}", report);
+        Ok(report)
+    }
+
+    /// Saves one converted game as part of an import, recording the
+    /// outcome and the game's warnings in the report
+    async fn save_imported_game(
+        db: &DatabaseConnection,
+        game_index: usize,
+        chess_game: &ChessGame,
+        warnings: Vec<GameWarning>,
+        report: &mut PgnImportReport,
+    ) {
         info!(
-            "Import completed: Successfully saved {} out of {} games",
-            saved_games.len(),
-            chess_games.len()
+            "Processing game {} (White: {} vs Black: {})",
+            game_index + 1,
+            chess_game.white_player.name,
+            chess_game.black_player.name
         );
-        Ok(saved_games)
+        match Self::import_single_game(db, game_index, chess_game).await {
+            Ok(_) => {
+                report.imported += 1;
+                report.add_warnings(warnings);
+            }
+            Err(e) => report.skipped.push(SkippedGame {
+                game_index,
+                line: None,
+                column: None,
+                snippet: None,
+                reason: e.to_string(),
+            }),
+        }
+    }
+
+    /// Saves one parsed game as part of an import
+    async fn import_single_game(
+        db: &DatabaseConnection,
+        game_index: usize,
+        chess_game: &ChessGame,
+    ) -> Result<Self, AppError> {
+        // Pre-validate the game before attempting to save
+        let move_count = chess_game.move_tree.nodes.len();
+        if move_count > 2000 {
+            warn!(
+                "⚠ Skipping game {} - too many moves ({}) might cause issues",
+                game_index + 1,
+                move_count
+            );
+            return Err(AppError::GeneralError(format!(
+                "Too many moves ({})",
+                move_count
+            )));
+        }
+
+        // Add detailed error handling for each game
+        let saved =
+            Self::save_single_game_with_retries(db, chess_game, 3).await;
+        match &saved {
+            Ok(_) => info!("✓ Successfully saved game {}", game_index + 1),
+            Err(e) => error!(
+                "✗ Error saving game {} (White: {} vs Black: {}): {}",
+                game_index + 1,
+                chess_game.white_player.name,
+                chess_game.black_player.name,
+                e
+            ),
+        }
+
+        // Add a small delay to prevent overwhelming the database and allow cleanup
+        tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
+
+        // Force garbage collection every 5 games to prevent memory buildup
+        if (game_index + 1) % 5 == 0 {
+            info!(
+                "  → Processed {} games, allowing cleanup...",
+                game_index + 1
+            );
+            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+        }
+
+        saved
     }
 
     /// Saves a single chess game with retry logic for better reliability
@@ -57,20 +57,30 @@ where
             AppError::DatabaseError(format!("Failed to query player: {}", e))
         })?
     {
-        // Update ELO if the new one is provided and different
-        if let Some(new_elo) = player.elo {
-            if existing_player.elo_rating != Some(new_elo) {
-                let mut player_model: player::ActiveModel =
-                    existing_player.clone().into();
-                player_model.elo_rating = Set(Some(new_elo));
-                player_model.updated_at = Set(Some(chrono::Utc::now()));
-                player_model.update(db).await.map_err(|e| {
-                    AppError::DatabaseError(format!(
-                        "Failed to update player ELO: {}",
-                        e
-                    ))
-                })?;
+        // Update ELO and title if new ones are provided and different
+        let new_elo = player
+            .elo
+            .filter(|&elo| existing_player.elo_rating != Some(elo));
+        let new_title = player
+            .title
+            .clone()
+            .filter(|title| existing_player.title.as_ref() != Some(title));
+        if new_elo.is_some() || new_title.is_some() {
+            let mut player_model: player::ActiveModel =
+                existing_player.clone().into();
+            if let Some(elo) = new_elo {
+                player_model.elo_rating = Set(Some(elo));
             }
+            if let Some(title) = new_title {
+                player_model.title = Set(Some(title));
+            }
+            player_model.updated_at = Set(Some(chrono::Utc::now()));
+            player_model.update(db).await.map_err(|e| {
+                AppError::DatabaseError(format!(
+                    "Failed to update player: {}",
+                    e
+                ))
+            })?;
         }
         Ok(existing_player.player_id)
     } else {
@@ -78,6 +88,7 @@ where
         let player_model = player::ActiveModel {
             name: Set(player.name.clone()),
             elo_rating: Set(player.elo),
+            title: Set(player.title.clone()),
             country_code: Set(player.country.clone()),
             created_at: Set(Some(chrono::Utc::now())),
             updated_at: Set(Some(chrono::Utc::now())),
@@ -102,5 +113,6 @@ pub fn new_player(name: &str) -> ChessPlayer {
         name: name.to_string(),
         elo: None,
         country: None,
+        title: None,
     }
 }
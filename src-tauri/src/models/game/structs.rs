@@ -1,4 +1,5 @@
 use crate::models::ChessMoveTree;
+use ok_parse::pgn::ValidationPolicy;
 use ok_utils::ts_export;
 use serde::{Deserialize, Serialize};
 use ts_rs::TS;
@@ -27,6 +28,8 @@ pub struct ChessPlayer {
     pub name: String,
     pub elo: Option<i32>,
     pub country: Option<String>,
+    /// Title like "GM", or "BOT" for engine accounts on lichess
+    pub title: Option<String>,
 }
 
 #[ts_export]
@@ -58,3 +61,185 @@ pub struct ChessHeader {
     pub name: String,
     pub value: String,
 }
+
+/// How games are checked when importing a PGN source
+#[derive(Debug, Clone, Default)]
+pub struct PgnImportOptions {
+    /// Which tags a game must have
+    pub policy: ValidationPolicy,
+    /// Keep the moves before a game's first illegal move instead of
+    /// skipping the game
+    pub keep_incomplete_games: bool,
+}
+
+/// The most warnings an import report keeps, so a large source full of
+/// games missing tags doesn't fill memory with warnings
+pub const MAX_REPORT_WARNINGS: usize = 1000;
+
+/// The outcome of importing a PGN source
+#[ts_export]
+#[derive(Default)]
+pub struct PgnImportReport {
+    /// Number of games saved to the database
+    pub imported: usize,
+    /// Games that were not saved, in source order
+    pub skipped: Vec<SkippedGame>,
+    /// Problems with saved games that didn't stop them being saved,
+    /// like tags filled with defaults, in source order
+    ///
+    /// Only the first `MAX_REPORT_WARNINGS` are kept.
+    pub warnings: Vec<GameWarning>,
+    /// Number of warnings left out of `warnings` once it was full
+    pub omitted_warnings: usize,
+    /// Encoding the source was read as, like "UTF-8" or "Windows-1252",
+    /// for sources read as text
+    pub encoding: Option<String>,
+}
+
+impl PgnImportReport {
+    /// Add the warnings of a saved game, counting those past
+    /// `MAX_REPORT_WARNINGS` instead of keeping them
+    pub fn add_warnings(
+        &mut self,
+        warnings: Vec<GameWarning>,
+    ) {
+        let room = MAX_REPORT_WARNINGS.saturating_sub(self.warnings.len());
+        self.omitted_warnings += warnings.len().saturating_sub(room);
+        self.warnings.extend(warnings.into_iter().take(room));
+    }
+}
+
+/// A game left out of an import
+#[ts_export]
+pub struct SkippedGame {
+    /// Index of the game in the source, starting at 0
+    pub game_index: usize,
+    /// Line the game failed to parse at, if it failed to parse
+    pub line: Option<usize>,
+    /// Column the game failed to parse at, if it failed to parse
+    pub column: Option<usize>,
+    /// Excerpt of the source line the game failed to parse at
+    pub snippet: Option<String>,
+    /// Why the game was skipped
+    pub reason: String,
+}
+
+/// A problem with an imported game that didn't stop it being saved
+#[ts_export]
+pub struct GameWarning {
+    /// Index of the game in the source, starting at 0
+    pub game_index: usize,
+    /// Line of the source the warning is about
+    pub line: usize,
+    /// Column of the source the warning is about
+    pub column: usize,
+    /// What the problem is
+    pub message: String,
+}
+
+impl std::fmt::Display for PgnImportReport {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        write!(
+            f,
+            "Imported {}, skipped {}",
+            format_count(self.imported),
+            format_count(self.skipped.len())
+        )?;
+        if let Some(encoding) = &self.encoding {
+            write!(f, " (read as {})", encoding)?;
+        }
+        for skipped in &self.skipped {
+            write!(f, "\nGame {}: {}", skipped.game_index + 1, skipped.reason)?;
+        }
+        for warning in &self.warnings {
+            write!(
+                f,
+                "\nGame {} warning: {}",
+                warning.game_index + 1,
+                warning.message
+            )?;
+        }
+        if self.omitted_warnings > 0 {
+            write!(
+                f,
+                "\n{} more warnings",
+                format_count(self.omitted_warnings)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Format a count with thousands separators, like "9,812"
+fn format_count(count: usize) -> String {
+    let digits = count.to_string();
+    let mut formatted = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            formatted.push(',');
+        }
+        formatted.push(digit);
+    }
+    formatted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn warnings(count: usize) -> Vec<GameWarning> {
+        (0..count)
+            .map(|game_index| GameWarning {
+                game_index,
+                line: 1,
+                column: 1,
+                message: "Missing tag Site".to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn report_warnings_are_capped() {
+        let mut report = PgnImportReport::default();
+        report.add_warnings(warnings(600));
+        report.add_warnings(warnings(600));
+        report.add_warnings(warnings(5));
+
+        assert_eq!(report.warnings.len(), MAX_REPORT_WARNINGS);
+        assert_eq!(report.omitted_warnings, 205);
+        // The first warnings are the ones kept
+        assert_eq!(report.warnings[600].game_index, 0);
+        assert!(report.to_string().ends_with("\n205 more warnings"));
+    }
+
+    #[test]
+    fn report_summary_lists_skipped_games() {
+        let mut report = PgnImportReport {
+            imported: 9812,
+            ..Default::default()
+        };
+        assert_eq!(report.to_string(), "Imported 9,812, skipped 0");
+
+        report.encoding = Some("Windows-1252".to_string());
+        report.skipped.push(SkippedGame {
+            game_index: 4,
+            line: Some(12),
+            column: Some(3),
+            snippet: None,
+            reason: "Illegal move Nc9".to_string(),
+        });
+        assert_eq!(
+            report.to_string(),
+            "Imported 9,812, skipped 1 (read as Windows-1252)\n\
+             Game 5: Illegal move Nc9"
+        );
+
+        assert_eq!(format_count(0), "0");
+        assert_eq!(format_count(999), "999");
+        assert_eq!(format_count(1_000), "1,000");
+        assert_eq!(format_count(1_234_567), "1,234,567");
+    }
+}
@@ -1,4 +1,8 @@
-use crate::entities::{annotation, move_time_tracking, position, r#move};
+use crate::entities::{
+    annotation, game, move_nag, move_time_tracking, position, r#move,
+};
+use ok_parse::pgn::commands::{parse_list, CommentEval, EvalScore};
+use ok_parse::pgn::{CommentCommands, PgnToken, SourceSpan};
 use ok_utils::ts_export;
 
 use sea_orm::prelude::*;
@@ -8,6 +12,7 @@ use sea_orm::{ConnectionTrait, EntityTrait};
 use serde::{Deserialize, Serialize};
 use shakmaty::fen::Fen;
 use shakmaty::uci::UciMove;
+use shakmaty::variant::{Variant, VariantPosition};
 use shakmaty::{san::San, Chess, Position};
 use shakmaty::{CastlingMode, EnPassantMode, Move};
 use std::error::Error;
@@ -23,6 +28,8 @@ pub struct ChessMove {
     pub uci: String,
     pub position: Option<ChessPosition>, // The resulting position after the move is played
     pub annotations: Vec<ChessAnnotation>,
+    /// Numeric Annotation Glyphs, like 1 for "!" or 18 for "+-"
+    pub nags: Vec<u8>,
     pub time_info: Option<ChessMoveTime>,
     pub parent_move_id: Option<i32>,
 }
@@ -32,7 +39,9 @@ pub struct ChessPosition {
     pub id: i32,
     pub fen: String,
     pub evaluations: Vec<ChessEvaluation>,
-    pub variant: Option<String>, // TODO: Handle Chess960
+    /// The variant the position is played in, like "Crazyhouse", named as
+    /// in PGN `Variant` tags. "Chess960" positions use Chess960 castling rules
+    pub variant: Option<String>,
 }
 
 #[ts_export]
@@ -65,33 +74,114 @@ fn hash_fen(fen: &str) -> String {
     hasher.finish().to_string()
 }
 
-pub fn generate_uci(
+/// Whether a variant name, as written in a PGN `Variant` tag, is Chess960
+///
+/// PGN files spell it in many ways, like "Chess960", "chess 960" or
+/// "Fischerandom".
+pub fn is_chess960(variant: &str) -> bool {
+    let name = normalise_variant_name(variant);
+    matches!(
+        name.as_str(),
+        "chess960" | "960" | "fischerandom" | "fischerrandom" | "freestyle"
+    )
+}
+
+/// The variants games can be played in, named as in PGN `Variant` tags
+const VARIANT_NAMES: [(&str, Variant); 7] = [
+    ("Crazyhouse", Variant::Crazyhouse),
+    ("Atomic", Variant::Atomic),
+    ("King of the Hill", Variant::KingOfTheHill),
+    ("Three-check", Variant::ThreeCheck),
+    ("Antichess", Variant::Antichess),
+    ("Horde", Variant::Horde),
+    ("Racing Kings", Variant::RacingKings),
+];
+
+/// Lowercase a variant name and drop everything but letters and digits,
+/// so "King of the Hill" and "kingOfTheHill" are the same name
+fn normalise_variant_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_ascii_lowercase()
+}
+
+/// The shakmaty variant for a variant name, like a PGN `Variant` tag
+///
+/// Standard chess, Chess960, "From Position" and puzzle games are all
+/// played by the standard rules. Returns `None` for variants that aren't
+/// supported.
+pub fn chess_variant(name: &str) -> Option<Variant> {
+    if is_chess960(name) {
+        return Some(Variant::Chess);
+    }
+    let name = normalise_variant_name(name);
+    match name.as_str() {
+        "" | "standard" | "chess" | "normal" | "fromposition" | "puzzle" => {
+            Some(Variant::Chess)
+        }
+        "3check" | "threecheck" => Some(Variant::ThreeCheck),
+        "koth" => Some(Variant::KingOfTheHill),
+        "giveaway" => Some(Variant::Antichess),
+        _ => VARIANT_NAMES
+            .iter()
+            .find(|(variant_name, _)| {
+                normalise_variant_name(variant_name) == name
+            })
+            .map(|(_, variant)| *variant),
+    }
+}
+
+/// The name a variant is stored under, so games in the same variant are
+/// named the same however their PGN spelled it
+///
+/// Standard chess and unsupported variants keep the name they were given.
+pub fn variant_name(name: &str) -> String {
+    if is_chess960(name) {
+        return "Chess960".to_string();
+    }
+    let variant = chess_variant(name);
+    VARIANT_NAMES
+        .iter()
+        .find(|(_, known)| Some(*known) == variant)
+        .map_or(name, |(variant_name, _)| variant_name)
+        .to_string()
+}
+
+/// The castling rules used by a variant
+pub fn castling_mode(variant: Option<&str>) -> CastlingMode {
+    match variant {
+        Some(variant) if is_chess960(variant) => CastlingMode::Chess960,
+        _ => CastlingMode::Standard,
+    }
+}
+
+/// Generate the UCI notation for a SAN move
+///
+/// Castling is written the way the position's castling rules expect, so
+/// in Chess960 the king "captures" its own rook (e1h1 rather than e1g1).
+pub fn generate_uci<P: Position>(
     san: &str,
-    pos: &Chess,
+    pos: &P,
 ) -> Result<String, Box<dyn Error>> {
-    let uci = san
-        .parse::<San>()
-        .unwrap()
-        .to_move(pos)
-        .unwrap()
-        .to_uci(CastlingMode::Standard)
-        .to_string();
+    let san = san.parse::<San>()?;
+    // Null moves don't move a piece, UCI writes them as "0000"
+    if san == San::Null {
+        return Ok("0000".to_string());
+    }
+    let uci = san.to_move(pos)?.to_uci(pos.castles().mode()).to_string();
     Ok(uci)
 }
 
 // Convert a ChessPosition to a shakmaty::Position
+//
+// Only for standard chess positions. Panics if the FEN isn't a valid
+// position, use `ChessPosition::to_position` for positions in any variant
+// or that haven't been checked.
 impl From<ChessPosition> for Chess {
     fn from(position: ChessPosition) -> Self {
-        let fen: Fen = position.fen.parse::<Fen>().unwrap();
-        let variant: CastlingMode = match position.variant {
-            Some(variant) => match variant.as_str() {
-                "Chess960" => CastlingMode::Chess960,
-                "Standard" => CastlingMode::Standard,
-                _ => CastlingMode::Standard,
-            },
-            None => CastlingMode::Standard,
-        };
-        fen.into_position(variant).unwrap()
+        let fen: Fen = position.fen.parse().unwrap();
+        fen.into_position(position.castling_mode()).unwrap()
     }
 }
 
@@ -130,14 +220,58 @@ impl ChessPosition {
         })
     }
 
+    /// The starting position of a variant, which is the standard one
+    /// except in variants like Horde and Racing Kings
+    pub fn starting(variant: &str) -> Result<Self, Box<dyn Error>> {
+        let chess_variant = chess_variant(variant)
+            .ok_or_else(|| format!("Unsupported variant '{}'", variant))?;
+        let fen = Fen::from_position(
+            VariantPosition::new(chess_variant),
+            EnPassantMode::Legal,
+        )
+        .to_string();
+        Self::from_fen(Some(fen), Some(variant.to_string()))
+    }
+
+    /// The shakmaty variant the position is played in, failing if the
+    /// variant isn't supported
+    pub fn chess_variant(&self) -> Result<Variant, Box<dyn Error>> {
+        let name = self.variant.as_deref().unwrap_or("Standard");
+        chess_variant(name)
+            .ok_or_else(|| format!("Unsupported variant '{}'", name).into())
+    }
+
+    /// Convert to a shakmaty position in the position's variant, failing if
+    /// the FEN isn't valid or the variant isn't supported
+    pub fn to_position(&self) -> Result<VariantPosition, Box<dyn Error>> {
+        let variant = self.chess_variant()?;
+        let fen: Fen = self.fen.parse::<Fen>()?;
+        Ok(VariantPosition::from_setup(
+            variant,
+            fen.into_setup(),
+            self.castling_mode(),
+        )?)
+    }
+
+    /// The castling rules for the position's variant
+    pub fn castling_mode(&self) -> CastlingMode {
+        castling_mode(self.variant.as_deref())
+    }
+
     pub fn make_san_move(
         &self,
         move_san: &str,
     ) -> Result<Self, Box<dyn Error>> {
-        let pos = Chess::from(self.clone());
+        let pos = self.to_position()?;
         let parsed_move = San::from_ascii(move_san.as_bytes())?;
-        let chess_move = parsed_move.to_move(&pos)?;
-        let new_pos = pos.play(&chess_move)?;
+        let new_pos = match parsed_move {
+            // A null move only passes the turn to the other side
+            San::Null => pos.swap_turn()?,
+            _ => {
+                let chess_move = parsed_move.to_move(&pos)?;
+                pos.play(&chess_move)?
+            }
+        };
         let fen = Fen::from_position(new_pos, EnPassantMode::Legal).to_string();
         Ok(ChessPosition {
             id: self.id,
@@ -152,7 +286,7 @@ impl ChessPosition {
         &self,
         uci: &str,
     ) -> Result<(Self, Move), Box<dyn Error>> {
-        let pos = Chess::from(self.clone());
+        let pos = self.to_position()?;
         let parsed_move = UciMove::from_ascii(uci.as_bytes())?;
         let chess_move = parsed_move.to_move(&pos)?;
         let new_pos = pos.play(&chess_move)?;
@@ -186,12 +320,16 @@ impl ChessMove {
             .one(db)
             .await?
             .ok_or("Position not found")?;
+        let variant = game::Entity::find_by_id(move_data.game_id)
+            .one(db)
+            .await?
+            .and_then(|game| game.variant);
 
         self.position = Some(ChessPosition {
             id: pos.position_id,
             fen: pos.fen,
             evaluations: Vec::new(), // TODO: Load evaluations
-            variant: None,           // TODO: Load variant
+            variant,
         });
 
         Ok(())
@@ -307,6 +445,15 @@ impl ChessMove {
             let current_move_id = result.last_insert_id;
             parent_move_id = Some(current_move_id);
 
+            for &nag in &chess_move.nags {
+                let nag_model = move_nag::ActiveModel {
+                    move_id: Set(current_move_id),
+                    nag: Set(i32::from(nag)),
+                    ..Default::default()
+                };
+                move_nag::Entity::insert(nag_model).exec(db).await?;
+            }
+
             // Save annotations if any
             for annotation in &chess_move.annotations {
                 if let Some(ref comment) = annotation.comment {
@@ -361,6 +508,7 @@ impl ChessMove {
             uci: move_data.uci,
             position: Some(move_position),
             annotations: Vec::new(),
+            nags: Vec::new(),
             time_info: None,
             parent_move_id: move_data.parent_move_id,
         }
@@ -384,10 +532,145 @@ impl ChessMove {
             san: move_san.to_string(),
             position: None,
             annotations: Vec::new(),
+            nags: Vec::new(),
             time_info: None,
             parent_move_id: None,
         };
 
         Ok(chess_move)
     }
+
+    /// Add a Numeric Annotation Glyph, unless the move already has it
+    pub fn add_nag(
+        &mut self,
+        code: u8,
+    ) {
+        if !self.nags.contains(&code) {
+            self.nags.push(code);
+        }
+    }
+
+    /// The PGN tokens for this move: the move itself followed by its
+    /// NAGs and comments
+    ///
+    /// Clock times and the deepest evaluation of the resulting position
+    /// are written as `[%clk]`, `[%emt]` and `[%eval]` commands in the first
+    /// comment, and each annotation's arrows and highlights as `[%cal]` and
+    /// `[%csl]` commands in its own comment.
+    ///
+    /// Fails if the move's SAN can't be parsed.
+    pub fn to_pgn_tokens(&self) -> Result<Vec<PgnToken>, String> {
+        let mut tokens = vec![PgnToken::Move {
+            san: self.san.parse()?,
+            span: SourceSpan::default(),
+        }];
+        tokens.extend(self.nags.iter().map(|&code| PgnToken::NAG {
+            code,
+            span: SourceSpan::default(),
+        }));
+
+        let mut move_commands = CommentCommands {
+            clock_ms: self
+                .time_info
+                .as_ref()
+                .and_then(|t| t.time_left_ms)
+                .and_then(|ms| u64::try_from(ms).ok()),
+            elapsed_ms: self
+                .time_info
+                .as_ref()
+                .and_then(|t| t.time_spent_ms)
+                .and_then(|ms| u64::try_from(ms).ok()),
+            eval: self
+                .position
+                .as_ref()
+                .and_then(|p| p.evaluations.iter().max_by_key(|e| e.depth))
+                .and_then(ChessEvaluation::to_comment_eval),
+            ..Default::default()
+        };
+
+        let mut comments = Vec::new();
+        for annotation in &self.annotations {
+            let mut commands = std::mem::take(&mut move_commands);
+            commands.text = annotation.comment.clone().unwrap_or_default();
+            commands.arrows = annotation
+                .arrows
+                .as_deref()
+                .and_then(parse_list)
+                .unwrap_or_default();
+            commands.highlights = annotation
+                .highlights
+                .as_deref()
+                .and_then(parse_list)
+                .unwrap_or_default();
+            comments.push(commands);
+        }
+        // Moves without annotations can still have clock times and evaluations
+        if move_commands.has_commands() {
+            comments.push(move_commands);
+        }
+
+        tokens.extend(
+            comments
+                .into_iter()
+                .filter(|commands| {
+                    commands.has_commands() || !commands.text.is_empty()
+                })
+                .map(|commands| PgnToken::Comment {
+                    text: commands.to_string(),
+                    span: SourceSpan::default(),
+                }),
+        );
+        Ok(tokens)
+    }
+}
+
+impl ChessEvaluation {
+    /// Convert to an `[%eval]` comment command, if there is a score
+    pub fn to_comment_eval(&self) -> Option<CommentEval> {
+        let score = self.score?;
+        let score = if self.is_mate || self.eval_type.as_deref() == Some("mate")
+        {
+            EvalScore::Mate(score as i32)
+        } else {
+            EvalScore::Centipawns(score.round() as i32)
+        };
+        Some(CommentEval {
+            score,
+            depth: self.depth.and_then(|depth| u32::try_from(depth).ok()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn variant_names_are_normalised() {
+        assert_eq!(variant_name("crazyhouse"), "Crazyhouse");
+        assert_eq!(variant_name("kingOfTheHill"), "King of the Hill");
+        assert_eq!(variant_name("Three-Check"), "Three-check");
+        assert_eq!(variant_name("Standard"), "Standard");
+        assert_eq!(variant_name("Shogi"), "Shogi");
+
+        assert_eq!(chess_variant("3check"), Some(Variant::ThreeCheck));
+        assert_eq!(chess_variant("koth"), Some(Variant::KingOfTheHill));
+        assert_eq!(chess_variant("giveaway"), Some(Variant::Antichess));
+        assert_eq!(chess_variant("Racing Kings"), Some(Variant::RacingKings));
+        assert_eq!(chess_variant("From Position"), Some(Variant::Chess));
+        assert_eq!(chess_variant(""), Some(Variant::Chess));
+        assert_eq!(chess_variant("Shogi"), None);
+    }
+
+    #[test]
+    fn chess960_spellings() {
+        for name in ["Chess960", "chess 960", "Fischerandom", "freestyle"] {
+            assert!(is_chess960(name), "{name}");
+            assert_eq!(variant_name(name), "Chess960");
+            assert_eq!(castling_mode(Some(name)), CastlingMode::Chess960);
+        }
+        assert!(!is_chess960("Standard"));
+        assert_eq!(castling_mode(Some("Standard")), CastlingMode::Standard);
+        assert_eq!(castling_mode(None), CastlingMode::Standard);
+    }
 }
@@ -12,6 +12,8 @@ mod navigation;
 pub mod parse;
 pub mod util;
 
+pub use util::ExtractedPosition;
+
 #[ts_export]
 pub struct ChessMoveTree {
     pub game_id: i32,
@@ -77,7 +79,9 @@ impl ChessMoveTree {
     where
         C: sea_orm::ConnectionTrait,
     {
-        use crate::entities::{annotation, position, r#move};
+        use crate::entities::{
+            annotation, move_nag, move_time_tracking, position, r#move,
+        };
         use sea_orm::prelude::*;
         use sea_orm::ActiveValue::Set;
         use std::collections::{HashMap, VecDeque};
@@ -159,6 +163,9 @@ impl ChessMoveTree {
                                     result.last_insert_id
                                 };
 
+                            Self::save_evaluations(db, position_id, position)
+                                .await?;
+
                             Some(position_id)
                         } else {
                             None
@@ -233,6 +240,45 @@ impl ChessMoveTree {
                         }
                     }
 
+                    // Save NAGs in the order they're written
+                    for &nag in &chess_move.nags {
+                        let nag_model = move_nag::ActiveModel {
+                            move_id: Set(current_move_id),
+                            nag: Set(i32::from(nag)),
+                            ..Default::default()
+                        };
+
+                        if let Err(e) =
+                            move_nag::Entity::insert(nag_model).exec(db).await
+                        {
+                            warn!("    ✗ Warning: Failed to save NAG: {}", e);
+                        }
+                    }
+
+                    // Save clock times if any
+                    if let Some(time_info) = &chess_move.time_info {
+                        let time_model = move_time_tracking::ActiveModel {
+                            move_id: Set(current_move_id),
+                            time_spent_ms: Set(time_info.time_spent_ms),
+                            time_left_ms: Set(time_info.time_left_ms),
+                            created_at: Set(Some(
+                                sea_orm::sqlx::types::chrono::Utc::now(),
+                            )),
+                            ..Default::default()
+                        };
+
+                        if let Err(e) =
+                            move_time_tracking::Entity::insert(time_model)
+                                .exec(db)
+                                .await
+                        {
+                            warn!(
+                                "    ✗ Warning: Failed to save move time: {}",
+                                e
+                            );
+                        }
+                    }
+
                     // Add all children to the queue with this move as their parent
                     for &child_id in &node.children_ids {
                         queue.push_back((child_id, Some(current_move_id)));
@@ -256,6 +302,67 @@ impl ChessMoveTree {
         Ok(())
     }
 
+    /// Save the evaluations recorded for a position, like PGN `[%eval]`
+    /// commands, skipping any the position already has
+    async fn save_evaluations<C>(
+        db: &C,
+        position_id: i32,
+        position: &ChessPosition,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+    where
+        C: sea_orm::ConnectionTrait,
+    {
+        use crate::entities::evaluation;
+        use sea_orm::prelude::*;
+        use sea_orm::ActiveValue::Set;
+
+        if position.evaluations.is_empty() {
+            return Ok(());
+        }
+
+        let existing = evaluation::Entity::find()
+            .filter(evaluation::Column::PositionId.eq(position_id))
+            .all(db)
+            .await?;
+
+        for chess_eval in &position.evaluations {
+            let score = chess_eval.score.map(f64::from);
+            let engine_name = chess_eval.engine.clone().unwrap_or_default();
+            let already_saved = existing.iter().any(|saved| {
+                saved.score == score
+                    && saved.r#type == chess_eval.eval_type
+                    && saved.depth == chess_eval.depth
+                    && saved.engine_name == engine_name
+            });
+            if already_saved {
+                continue;
+            }
+
+            let best_line = (!chess_eval.principal_variation.is_empty())
+                .then(|| chess_eval.principal_variation.join(" "));
+            let eval_model = evaluation::ActiveModel {
+                position_id: Set(position_id),
+                score: Set(score),
+                r#type: Set(chess_eval.eval_type.clone()),
+                best_line: Set(best_line),
+                depth: Set(chess_eval.depth),
+                engine_name: Set(engine_name),
+                engine_version: Set(String::new()),
+                created_at: Set(Some(sea_orm::sqlx::types::chrono::Utc::now())),
+                ..Default::default()
+            };
+
+            if let Err(e) =
+                evaluation::Entity::insert(eval_model).exec(db).await
+            {
+                warn!("    ✗ Warning: Failed to save evaluation: {}", e);
+                // Continue processing, evaluations are not critical
+            }
+        }
+
+        Ok(())
+    }
+
     /// Hash a FEN string for position deduplication
     fn hash_fen(fen: &str) -> String {
         use std::hash::{DefaultHasher, Hasher};
@@ -1,4 +1,4 @@
-use shakmaty::{san::San, Chess};
+use shakmaty::san::San;
 use slotmap::DefaultKey;
 
 use crate::models::ChessMove;
@@ -69,11 +69,10 @@ impl ChessMoveTree {
             None => new_move.ply_number = 1, // Root node - first move of the game
         }
         // san
-        new_move.san = San::from_move(
-            &Chess::from(current_node.position.clone()),
-            &chess_move,
-        )
-        .to_string();
+        new_move.san = match current_node.position.to_position() {
+            Ok(pos) => San::from_move(&pos, &chess_move).to_string(),
+            Err(_) => uci_move_notation.to_string(),
+        };
 
         // Add the move to the move tree
         let new_node_id = self.add_move(new_move);
@@ -81,4 +80,26 @@ impl ChessMoveTree {
         // Move to the new node
         self.current_node_id = Some(new_node_id);
     }
+
+    /// Replace the NAGs of the current move, keeping each NAG once
+    ///
+    /// Returns false if the current node is the root, which has no move
+    pub fn set_current_move_nags(
+        &mut self,
+        nags: &[u8],
+    ) -> bool {
+        let Some(current_node_id) = self.current_node_id else {
+            return false;
+        };
+        let Some(game_move) = self.nodes[current_node_id].game_move.as_mut()
+        else {
+            return false;
+        };
+
+        game_move.nags.clear();
+        for &code in nags {
+            game_move.add_nag(code);
+        }
+        true
+    }
 }
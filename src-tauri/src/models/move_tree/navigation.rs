@@ -1,3 +1,5 @@
+use log::warn;
+use ok_parse::pgn::{PgnToken, SourceSpan};
 use slotmap::DefaultKey;
 
 use crate::models::move_tree::ChessMoveTree;
@@ -71,117 +73,67 @@ impl ChessMoveTree {
         }
     }
 
-    /// Generates the PGN move notation with proper variation handling
-    pub fn to_pgn_moves(&self) -> String {
+    /// Generates the PGN tokens for the moves, with variations and comments
+    ///
+    /// Variations follow the main line move they are an alternative to,
+    /// as PGN expects.
+    pub fn to_pgn_tokens(&self) -> Vec<PgnToken> {
+        let mut tokens = Vec::new();
         if let Some(root_id) = self.root_id {
-            let mut pgn = String::new();
-            let mut move_number = 1;
-            let mut is_white = true;
-            self.write_pgn_moves_recursive(
-                root_id,
-                &mut pgn,
-                &mut move_number,
-                &mut is_white,
-            );
-            pgn
-        } else {
-            String::new()
+            self.write_pgn_tokens(root_id, &mut tokens);
         }
+        tokens
     }
 
-    fn write_pgn_moves_recursive(
+    /// Write the line continuing from a node, starting with its first child
+    fn write_pgn_tokens(
         &self,
         node_id: DefaultKey,
-        pgn: &mut String,
-        move_number: &mut i32,
-        is_white: &mut bool,
+        tokens: &mut Vec<PgnToken>,
     ) {
-        let node = &self.nodes[node_id];
-
-        // Add the move if this isn't the root node
-        if let Some(ref game_move) = node.game_move {
-            if *is_white {
-                pgn.push_str(&format!("{}. ", move_number));
+        let mut node = &self.nodes[node_id];
+
+        // Follow the main line, writing every other child as a variation
+        while let Some((&main_line_id, variation_ids)) =
+            node.children_ids.split_first()
+        {
+            if !self.write_move_tokens(main_line_id, tokens) {
+                return;
             }
-            pgn.push_str(&format!("{} ", game_move.san));
 
-            if !*is_white {
-                *move_number += 1;
+            for &variation_id in variation_ids {
+                let mut moves = Vec::new();
+                if self.write_move_tokens(variation_id, &mut moves) {
+                    self.write_pgn_tokens(variation_id, &mut moves);
+                }
+                tokens.push(PgnToken::Variation {
+                    moves,
+                    span: SourceSpan::default(),
+                });
             }
-            *is_white = !*is_white;
-        }
 
-        // Process children
-        if !node.children_ids.is_empty() {
-            // Additional children are variations
-            for &variation_id in node.children_ids.iter().skip(1) {
-                pgn.push('(');
-
-                // For variations, we need to indicate whose turn it is at the start
-                let variation_node = &self.nodes[variation_id];
-                if let Some(ref game_move) = variation_node.game_move {
-                    // Determine whose turn it is for this variation based on ply number
-                    // Odd ply numbers (1, 3, 5...) are white moves, even (2, 4, 6...) are black moves
-                    let var_is_white = game_move.ply_number % 2 == 1;
-                    let var_move_number = (game_move.ply_number + 1) / 2;
-
-                    if var_is_white {
-                        pgn.push_str(&format!("{}. ", var_move_number));
-                    } else {
-                        pgn.push_str(&format!("{}... ", var_move_number));
-                    }
-                    pgn.push_str(&format!("{} ", game_move.san));
-
-                    // Continue with the variation
-                    let mut var_move_num = if var_is_white {
-                        var_move_number
-                    } else {
-                        var_move_number + 1
-                    };
-                    let mut var_is_white_turn = !var_is_white;
-
-                    // Process variation's children
-                    if !variation_node.children_ids.is_empty() {
-                        if let Some(&child_id) =
-                            variation_node.children_ids.first()
-                        {
-                            self.write_pgn_moves_recursive(
-                                child_id,
-                                pgn,
-                                &mut var_move_num,
-                                &mut var_is_white_turn,
-                            );
-                        }
-
-                        // Handle sub-variations
-                        for &sub_var_id in
-                            variation_node.children_ids.iter().skip(1)
-                        {
-                            pgn.push('(');
-                            let mut sub_var_move_num = var_move_num;
-                            let mut sub_var_is_white = var_is_white_turn;
-                            self.write_pgn_moves_recursive(
-                                sub_var_id,
-                                pgn,
-                                &mut sub_var_move_num,
-                                &mut sub_var_is_white,
-                            );
-                            pgn.push_str(") ");
-                        }
-                    }
-                }
+            node = &self.nodes[main_line_id];
+        }
+    }
 
-                pgn.push_str(") ");
+    /// Write the tokens for a node's move, returning false if it can't be
+    /// written, in which case the line ends before it
+    fn write_move_tokens(
+        &self,
+        node_id: DefaultKey,
+        tokens: &mut Vec<PgnToken>,
+    ) -> bool {
+        let Some(ref game_move) = self.nodes[node_id].game_move else {
+            return true;
+        };
+        match game_move.to_pgn_tokens() {
+            Ok(move_tokens) => {
+                tokens.extend(move_tokens);
+                true
             }
-
-            // First child is the main line
-            if let Some(&main_line_id) = node.children_ids.first() {
-                self.write_pgn_moves_recursive(
-                    main_line_id,
-                    pgn,
-                    move_number,
-                    is_white,
-                );
+            Err(e) => {
+                warn!("Can't write move '{}' as PGN: {}", game_move.san, e);
+                false
             }
         }
     }
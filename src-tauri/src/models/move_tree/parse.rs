@@ -6,17 +6,63 @@ use log::{debug, info};
 use sea_orm::{
     ColumnTrait, DatabaseConnection, EntityTrait, LoaderTrait, QueryFilter,
 };
-use shakmaty::Chess;
+use serde::Serialize;
 use slotmap::{DefaultKey, SlotMap};
 
-use crate::entities::{annotation, position, r#move};
-use crate::models::{generate_uci, ChessAnnotation, ChessMove, ChessPosition};
-use ok_parse::pgn::PgnToken;
+use crate::entities::{
+    annotation, evaluation, move_nag, move_time_tracking, position, r#move,
+};
+use crate::models::{
+    generate_uci, ChessAnnotation, ChessEvaluation, ChessMove, ChessMoveTime,
+    ChessPosition,
+};
+use ok_parse::pgn::commands::EvalScore;
+use ok_parse::pgn::nag::symbol_to_nag;
+use ok_parse::pgn::{
+    parse_comment_commands, CommentCommands, PgnToken, SourceSpan,
+};
 
 use super::{ChessMoveTree, ChessTreeNode};
 
+/**
+ * A move in the movetext that can't be played in its position
+ */
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error, Serialize)]
+#[error(
+    "Illegal move {move_number}{} {san} at {span} ({side} to move, FEN \"{fen}\"): {reason}",
+    if side == "white" { "." } else { "..." }
+)]
+pub struct IllegalMove {
+    /// The full move number the move was played at
+    pub move_number: u32,
+    /// The side playing the move, "white" or "black"
+    pub side: String,
+    pub san: String,
+    /// The position the move was played in
+    pub fen: String,
+    /// Why the move can't be played
+    pub reason: String,
+    /// Where the move is in the PGN source
+    pub span: SourceSpan,
+}
+
+/**
+ * The result of replaying movetext that may contain illegal moves
+ */
+pub struct MoveTreeReplay {
+    /// The moves up to the first illegal move of each line
+    pub tree: ChessMoveTree,
+    /// The first illegal move of each line that was cut short
+    pub illegal_moves: Vec<IllegalMove>,
+    /// Whether the main line was replayed to the end
+    pub complete: bool,
+}
+
 /**
  * Given a list of PGN move related tokens, parse them into a ChessMoveTree
+ *
+ * Every move, including those in variations, is played with shakmaty,
+ * the first that can't be played fails the whole tree.
  * @param tokens - The list of PGN move related tokens
  * @param game_id - The ID of the game
  * @param root_position - The starting position of the game
@@ -26,47 +72,78 @@ pub fn pgn_tokens_to_move_tree(
     game_id: i32,
     root_position: ChessPosition,
     tokens: &[PgnToken],
-) -> Result<ChessMoveTree, Box<dyn Error>> {
-    // Create a new tree with just the root node
-    let mut nodes = SlotMap::new();
-
-    // Create the root node
-    let root_node = ChessTreeNode {
-        position: root_position.clone(),
-        game_move: None,
-        parent_id: None,
-        children_ids: Vec::new(),
-    };
-
-    let root_id = nodes.insert(root_node);
-
-    let mut tree = ChessMoveTree {
-        game_id,
-        nodes,
-        root_id: Some(root_id),
-        current_node_id: Some(root_id),
-    };
-
-    // Initialize the parsing state
-    let current_position = root_position;
-    let mut full_move_count = 0;
-    let mut is_white = true;
-
-    // Process tokens recursively
+) -> Result<ChessMoveTree, IllegalMove> {
+    let (mut tree, root_id) = new_tree(game_id, &root_position);
     parse_pgn_tokens_recursive(
         tokens,
         &mut tree,
-        current_position,
+        root_position,
         root_id,
-        &mut full_move_count,
-        &mut is_white,
+        &mut 0,
+        &mut true,
+        None,
     )?;
-
     Ok(tree)
 }
 
+/**
+ * Parse PGN move tokens into a ChessMoveTree, keeping the legal moves
+ * before an illegal one
+ *
+ * An illegal move cuts its line short: a variation ends at the move
+ * before it and the main line continues, while an illegal main line move
+ * ends the game there.
+ * @param tokens - The list of PGN move related tokens
+ * @param game_id - The ID of the game
+ * @param root_position - The starting position of the game
+ * @returns the moves that could be played and the illegal moves found
+ */
+pub fn replay_pgn_tokens(
+    game_id: i32,
+    root_position: ChessPosition,
+    tokens: &[PgnToken],
+) -> MoveTreeReplay {
+    let (mut tree, root_id) = new_tree(game_id, &root_position);
+    let mut illegal_moves = Vec::new();
+    let result = parse_pgn_tokens_recursive(
+        tokens,
+        &mut tree,
+        root_position,
+        root_id,
+        &mut 0,
+        &mut true,
+        Some(&mut illegal_moves),
+    );
+    let complete = result.is_ok();
+    if let Err(illegal_move) = result {
+        illegal_moves.push(illegal_move);
+    }
+    MoveTreeReplay {
+        tree,
+        illegal_moves,
+        complete,
+    }
+}
+
+/**
+ * Create a tree holding only the root position
+ * @returns the tree and the key of its root node
+ */
+fn new_tree(
+    game_id: i32,
+    root_position: &ChessPosition,
+) -> (ChessMoveTree, DefaultKey) {
+    let tree = ChessMoveTree::new(game_id, root_position.clone());
+    let root_id = tree.root_id.unwrap();
+    (tree, root_id)
+}
+
 /**
  * A recursive function for parse_pgn_tokens
+ *
+ * Stops at the first move that can't be played. If `illegal_moves` is
+ * given, variations that stop early are recorded there instead of
+ * stopping their parent line.
  */
 fn parse_pgn_tokens_recursive(
     tokens: &[PgnToken],
@@ -75,12 +152,13 @@ fn parse_pgn_tokens_recursive(
     current_node_id: DefaultKey,
     full_move_count: &mut i32,
     is_white: &mut bool,
-) -> Result<(), Box<dyn Error>> {
+    mut illegal_moves: Option<&mut Vec<IllegalMove>>,
+) -> Result<(), IllegalMove> {
     let mut current_node_id = current_node_id;
 
     for token in tokens {
         match token {
-            PgnToken::MoveNumber { number } => {
+            PgnToken::MoveNumber { number, .. } => {
                 let new_move_count = *number as i32;
                 // If the new move count is different from the previous move count, we have a new full move
                 // therefore the next move is white, if it's the same, the next move is black
@@ -88,29 +166,24 @@ fn parse_pgn_tokens_recursive(
                 *is_white = new_move_count != *full_move_count;
                 *full_move_count = new_move_count;
             }
-            PgnToken::Move { notation } => {
+            PgnToken::Move { san, span } => {
+                let notation = san.to_string();
                 // ply number
                 let new_move_ply =
                     *full_move_count * 2 + if *is_white { -1 } else { 0 };
 
-                // post move position
-                let new_move_position_result =
-                    current_position.make_san_move(notation);
-                if let Err(e) = new_move_position_result {
-                    eprintln!(
-                        "Error making move.\nNotation: {}\nError:\n{}",
-                        notation, e
-                    );
-                    return Err(e);
-                }
-
-                let new_move_position = new_move_position_result.unwrap();
-
-                // UCI
-                let uci = generate_uci(
-                    notation,
-                    &Chess::from(current_position.clone()),
-                )?;
+                // post move position and UCI
+                let (new_move_position, uci) =
+                    play_san_move(&current_position, &notation).map_err(
+                        |reason| {
+                            IllegalMove::new(
+                                &current_position,
+                                &notation,
+                                reason,
+                                *span,
+                            )
+                        },
+                    )?;
 
                 let new_move = ChessMove {
                     id: 0,
@@ -120,6 +193,7 @@ fn parse_pgn_tokens_recursive(
                     uci,
                     position: Some(new_move_position.clone()),
                     annotations: Vec::new(),
+                    nags: Vec::new(),
                     time_info: None,
                     parent_move_id: None, // Make sure to set this once the moves have database ids
                 };
@@ -144,7 +218,9 @@ fn parse_pgn_tokens_recursive(
 
                 *is_white = !*is_white;
             }
-            PgnToken::Variation { moves: var_tokens } => {
+            PgnToken::Variation {
+                moves: var_tokens, ..
+            } => {
                 // Per PGN spec: RAV is played by "first unplaying the move that appears immediately prior to the RAV"
                 // This means variations branch from the parent position, not the current position
                 let parent_node_id = tree.nodes[current_node_id].parent_id;
@@ -175,14 +251,22 @@ fn parse_pgn_tokens_recursive(
                 let saved_is_white = *is_white;
 
                 // Process the variation recursively from the parent position
-                parse_pgn_tokens_recursive(
+                let result = parse_pgn_tokens_recursive(
                     var_tokens,
                     tree,
                     parent_position,
                     parent_node_id.unwrap_or(current_node_id),
                     &mut parent_move_count.clone(),
                     &mut parent_is_white.clone(),
-                )?;
+                    illegal_moves.as_deref_mut(),
+                );
+                // An illegal move either ends the variation or the game
+                match (result, illegal_moves.as_deref_mut()) {
+                    (Err(illegal_move), Some(illegal_moves)) => {
+                        illegal_moves.push(illegal_move)
+                    }
+                    (result, _) => result?,
+                }
 
                 // Restore the current state after processing the variation
                 current_node_id = saved_node_id;
@@ -190,17 +274,33 @@ fn parse_pgn_tokens_recursive(
                 *full_move_count = saved_move_count;
                 *is_white = saved_is_white;
             }
-            PgnToken::Comment { text } => {
+            PgnToken::Comment { text, .. } => {
                 // If we're at a move node (not the root), add the comment to the move
-                if let Some(ref mut game_move) =
+                let node = &mut tree.nodes[current_node_id];
+                if let Some(game_move) = node.game_move.as_mut() {
+                    let commands = parse_comment_commands(text);
+                    apply_comment_commands(game_move, &commands);
+
+                    // Keep the node's copy of the position in sync
+                    if let Some(position) = &game_move.position {
+                        node.position.evaluations =
+                            position.evaluations.clone();
+                    }
+                }
+            }
+            PgnToken::MoveSuffixNotation { suffix, .. } => {
+                if let (Some(game_move), Some(code)) = (
+                    tree.nodes[current_node_id].game_move.as_mut(),
+                    symbol_to_nag(suffix),
+                ) {
+                    game_move.add_nag(code);
+                }
+            }
+            PgnToken::NAG { code, .. } => {
+                if let Some(game_move) =
                     tree.nodes[current_node_id].game_move.as_mut()
                 {
-                    game_move.annotations.push(ChessAnnotation {
-                        id: 0,
-                        comment: Some(text.clone()),
-                        arrows: None,
-                        highlights: None,
-                    });
+                    game_move.add_nag(*code);
                 }
             }
             _ => {}
@@ -210,6 +310,147 @@ fn parse_pgn_tokens_recursive(
     Ok(())
 }
 
+/**
+ * Play a move given in SAN
+ * @param position - The position to play the move in
+ * @param notation - The move in SAN
+ * @returns the resulting position and the move in UCI, or why the move
+ * can't be played
+ */
+fn play_san_move(
+    position: &ChessPosition,
+    notation: &str,
+) -> Result<(ChessPosition, String), String> {
+    let new_position = position
+        .make_san_move(notation)
+        .map_err(|e| e.to_string())?;
+    let pos = position.to_position().map_err(|e| e.to_string())?;
+    let uci = generate_uci(notation, &pos).map_err(|e| e.to_string())?;
+    Ok((new_position, uci))
+}
+
+impl IllegalMove {
+    /**
+     * Describe a move that can't be played
+     * @param position - The position the move was played in
+     * @param san - The move
+     * @param reason - Why the move can't be played
+     * @param span - Where the move is in the PGN source
+     */
+    fn new(
+        position: &ChessPosition,
+        san: &str,
+        reason: String,
+        span: SourceSpan,
+    ) -> Self {
+        // The side to move and move number are the second and last
+        // fields of the FEN
+        let fields: Vec<&str> = position.fen.split_whitespace().collect();
+        let side = match fields.get(1) {
+            Some(&"b") => "black",
+            _ => "white",
+        };
+        let move_number = fields
+            .get(5)
+            .and_then(|number| number.parse().ok())
+            .unwrap_or(1);
+
+        IllegalMove {
+            move_number,
+            side: side.to_string(),
+            san: san.to_string(),
+            fen: position.fen.clone(),
+            reason,
+            span,
+        }
+    }
+}
+
+/**
+ * Apply the commands and text of a PGN comment to the move it follows
+ * @param game_move - The move the comment follows
+ * @param commands - The parsed comment
+ */
+fn apply_comment_commands(
+    game_move: &mut ChessMove,
+    commands: &CommentCommands,
+) {
+    if commands.clock_ms.is_some() || commands.elapsed_ms.is_some() {
+        let time_info = game_move.time_info.get_or_insert(ChessMoveTime {
+            time_spent_ms: None,
+            time_left_ms: None,
+        });
+        if let Some(ms) = commands.clock_ms {
+            time_info.time_left_ms = i32::try_from(ms).ok();
+        }
+        if let Some(ms) = commands.elapsed_ms {
+            time_info.time_spent_ms = i32::try_from(ms).ok();
+        }
+    }
+
+    if let (Some(eval), Some(position)) =
+        (commands.eval, game_move.position.as_mut())
+    {
+        let (score, is_mate) = match eval.score {
+            EvalScore::Centipawns(cp) => (cp, false),
+            EvalScore::Mate(moves) => (moves, true),
+        };
+        position.evaluations.push(ChessEvaluation {
+            score: Some(score as f32),
+            eval_type: Some(if is_mate { "mate" } else { "cp" }.to_string()),
+            is_mate,
+            depth: eval.depth.map(|depth| depth as i32),
+            engine: None,
+            principal_variation: Vec::new(),
+        });
+    }
+
+    let arrows = join_commands(&commands.arrows);
+    let highlights = join_commands(&commands.highlights);
+    if !commands.text.is_empty() || arrows.is_some() || highlights.is_some() {
+        game_move.annotations.push(ChessAnnotation {
+            id: 0,
+            comment: (!commands.text.is_empty()).then(|| commands.text.clone()),
+            arrows,
+            highlights,
+        });
+    }
+}
+
+/**
+ * Join arrows or highlights into the comma separated form used by
+ * `[%cal]` and `[%csl]`, e.g. "Ge2e4,Rd1d8"
+ */
+fn join_commands<T: ToString>(items: &[T]) -> Option<String> {
+    if items.is_empty() {
+        return None;
+    }
+    Some(
+        items
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(","),
+    )
+}
+
+/**
+ * Convert a saved evaluation into a ChessEvaluation
+ */
+fn chess_evaluation_from_model(eval: evaluation::Model) -> ChessEvaluation {
+    ChessEvaluation {
+        score: eval.score.map(|score| score as f32),
+        is_mate: eval.r#type.as_deref() == Some("mate"),
+        eval_type: eval.r#type,
+        depth: eval.depth,
+        engine: (!eval.engine_name.is_empty()).then_some(eval.engine_name),
+        principal_variation: eval
+            .best_line
+            .map(|line| line.split_whitespace().map(String::from).collect())
+            .unwrap_or_default(),
+    }
+}
+
 /**
  * Given a game ID, load the moves from the database and parse them into a ChessMoveTree
  * @param game_id - The ID of the game
@@ -229,9 +470,35 @@ pub async fn load_moves_from_db(
         .await?;
     let move_positions = all_db_moves.load_one(position::Entity, db).await?;
     let annotations = all_db_moves.load_many(annotation::Entity, db).await?;
+    let time_infos = all_db_moves
+        .load_many(move_time_tracking::Entity, db)
+        .await?;
+    let nags = all_db_moves.load_many(move_nag::Entity, db).await?;
+
+    // Evaluations belong to positions, so group them by position
+    let position_ids = move_positions
+        .iter()
+        .flatten()
+        .map(|p| p.position_id)
+        .collect::<Vec<_>>();
+    let mut position_evaluations: HashMap<i32, Vec<ChessEvaluation>> =
+        HashMap::new();
+    for eval in evaluation::Entity::find()
+        .filter(evaluation::Column::PositionId.is_in(position_ids))
+        .all(db)
+        .await?
+    {
+        position_evaluations
+            .entry(eval.position_id)
+            .or_default()
+            .push(chess_evaluation_from_model(eval));
+    }
 
     debug!("Found {} moves in database", all_db_moves.len());
 
+    // Every move is played in the variant of the starting position
+    let variant = root_position.variant.clone();
+
     let mut nodes = SlotMap::new();
     let mut db_id_to_key: HashMap<i32, DefaultKey> = HashMap::new();
 
@@ -245,24 +512,40 @@ pub async fn load_moves_from_db(
 
     let root_id = nodes.insert(root_node);
 
-    let data = izip!(all_db_moves.clone(), move_positions, annotations);
+    let data = izip!(
+        all_db_moves.clone(),
+        move_positions,
+        annotations,
+        time_infos,
+        nags
+    );
 
     debug!("Starting first pass to create move nodes");
 
     // First pass: Create all move nodes and build the ID mapping
-    for (move_entity, position_entity, annotation_entities) in data {
+    for (
+        move_entity,
+        position_entity,
+        annotation_entities,
+        time_entities,
+        nag_entities,
+    ) in data
+    {
         let move_position = position_entity.as_ref().map_or(
             ChessPosition {
                 id: 0,
                 fen: "".to_string(),
                 evaluations: Vec::new(),
-                variant: None,
+                variant: variant.clone(),
             },
             |p| ChessPosition {
                 id: p.position_id,
                 fen: p.fen.clone(),
-                evaluations: Vec::new(),
-                variant: None,
+                evaluations: position_evaluations
+                    .get(&p.position_id)
+                    .cloned()
+                    .unwrap_or_default(),
+                variant: variant.clone(),
             },
         );
 
@@ -286,7 +569,14 @@ pub async fn load_moves_from_db(
                 uci: move_entity.uci.clone(),
                 position: Some(move_position),
                 annotations,
-                time_info: None,
+                nags: nag_entities
+                    .iter()
+                    .filter_map(|n| u8::try_from(n.nag).ok())
+                    .collect(),
+                time_info: time_entities.first().map(|t| ChessMoveTime {
+                    time_spent_ms: t.time_spent_ms,
+                    time_left_ms: t.time_left_ms,
+                }),
                 parent_move_id: move_entity.parent_move_id,
             }),
             parent_id: None, // Will be set in second pass
@@ -337,23 +627,34 @@ pub async fn load_moves_from_db(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::{variant_name, ChessGame};
 
     #[test]
     fn parse_pgn_tokens_simple_game() {
         let tokens = vec![
-            PgnToken::MoveNumber { number: 1 },
+            PgnToken::MoveNumber {
+                number: 1,
+                span: SourceSpan::default(),
+            },
             PgnToken::Move {
-                notation: "e4".to_string(),
+                san: "e4".parse().unwrap(),
+                span: SourceSpan::default(),
             },
             PgnToken::Move {
-                notation: "e5".to_string(),
+                san: "e5".parse().unwrap(),
+                span: SourceSpan::default(),
+            },
+            PgnToken::MoveNumber {
+                number: 2,
+                span: SourceSpan::default(),
             },
-            PgnToken::MoveNumber { number: 2 },
             PgnToken::Move {
-                notation: "d4".to_string(),
+                san: "d4".parse().unwrap(),
+                span: SourceSpan::default(),
             },
             PgnToken::Move {
-                notation: "d5".to_string(),
+                san: "d5".parse().unwrap(),
+                span: SourceSpan::default(),
             },
         ];
 
@@ -411,17 +712,24 @@ mod tests {
         // - Variation: White plays d4 instead of e4 from starting position
 
         let tokens = vec![
-            PgnToken::MoveNumber { number: 1 },
+            PgnToken::MoveNumber {
+                number: 1,
+                span: SourceSpan::default(),
+            },
             PgnToken::Move {
-                notation: "e4".to_string(),
+                san: "e4".parse().unwrap(),
+                span: SourceSpan::default(),
             },
             PgnToken::Variation {
                 moves: vec![PgnToken::Move {
-                    notation: "d4".to_string(),
+                    san: "d4".parse().unwrap(),
+                    span: SourceSpan::default(),
                 }],
+                span: SourceSpan::default(),
             },
             PgnToken::Move {
-                notation: "e5".to_string(),
+                san: "e5".parse().unwrap(),
+                span: SourceSpan::default(),
             },
         ];
 
@@ -458,5 +766,317 @@ mod tests {
         assert_eq!(fourth_node.game_move.as_ref().unwrap().san, "e5");
         assert_eq!(fourth_node.game_move.as_ref().unwrap().uci, "e7e5");
         assert_eq!(fourth_node.game_move.as_ref().unwrap().ply_number, 2);
+
+        // The variation's position comes from the root, not from e5
+        let positions = tree.extract_positions(true);
+        let parents: Vec<_> = positions.iter().map(|p| p.parent).collect();
+        assert_eq!(parents, [None, Some(0), Some(1), Some(0)]);
+        assert_eq!(positions[3].position.fen, third_node.position.fen);
+        let main_line = tree.extract_positions(false);
+        assert_eq!(main_line.len(), 3);
+        assert_eq!(main_line[2].position.fen, fourth_node.position.fen);
+    }
+
+    #[test]
+    fn parse_pgn_tokens_comment_commands() {
+        // Clock, eval and arrow commands fill the move, leaving only the text
+        let tokens = vec![
+            PgnToken::MoveNumber {
+                number: 1,
+                span: SourceSpan::default(),
+            },
+            PgnToken::Move {
+                san: "e4".parse().unwrap(),
+                span: SourceSpan::default(),
+            },
+            PgnToken::Comment {
+                text: "[%clk 0:05:00] [%emt 0:00:02.5] [%eval 0.32,20] [%cal Ge2e4] Best by test".to_string(),
+                span: SourceSpan::default(),
+            },
+        ];
+
+        let tree =
+            pgn_tokens_to_move_tree(1, ChessPosition::default(), &tokens)
+                .unwrap();
+        let root = &tree.nodes[tree.root_id.unwrap()];
+        let node = &tree.nodes[root.children_ids[0]];
+        let game_move = node.game_move.as_ref().unwrap();
+
+        let time_info = game_move.time_info.as_ref().unwrap();
+        assert_eq!(time_info.time_left_ms, Some(300_000));
+        assert_eq!(time_info.time_spent_ms, Some(2_500));
+
+        let evaluation = &node.position.evaluations[0];
+        assert_eq!(evaluation.score, Some(32.0));
+        assert_eq!(evaluation.depth, Some(20));
+        assert!(!evaluation.is_mate);
+
+        assert_eq!(game_move.annotations.len(), 1);
+        let annotation = &game_move.annotations[0];
+        assert_eq!(annotation.comment.as_deref(), Some("Best by test"));
+        assert_eq!(annotation.arrows.as_deref(), Some("Ge2e4"));
+        assert_eq!(annotation.highlights, None);
+    }
+
+    #[test]
+    fn parse_pgn_tokens_suffixes_and_nags() {
+        // Suffixes become NAGs, and each NAG is only kept once
+        let tokens = vec![
+            PgnToken::MoveNumber {
+                number: 1,
+                span: SourceSpan::default(),
+            },
+            PgnToken::Move {
+                san: "e4".parse().unwrap(),
+                span: SourceSpan::default(),
+            },
+            PgnToken::MoveSuffixNotation {
+                suffix: "!?".to_string(),
+                span: SourceSpan::default(),
+            },
+            PgnToken::NAG {
+                code: 14,
+                span: SourceSpan::default(),
+            },
+            PgnToken::NAG {
+                code: 5,
+                span: SourceSpan::default(),
+            },
+        ];
+
+        let tree =
+            pgn_tokens_to_move_tree(1, ChessPosition::default(), &tokens)
+                .unwrap();
+        let root = &tree.nodes[tree.root_id.unwrap()];
+        let game_move =
+            tree.nodes[root.children_ids[0]].game_move.as_ref().unwrap();
+
+        assert_eq!(game_move.nags, vec![5, 14]);
+    }
+
+    /// Tokens for a game with an illegal move in a variation and in the
+    /// main line: 1. e4 e5 (1... d6 2. Ke3) 2. Nf3 Nc6 3. Bb5 Qxh8
+    fn tokens_with_illegal_moves() -> Vec<PgnToken> {
+        let moves = |sans: &[&str]| -> Vec<PgnToken> {
+            sans.iter()
+                .map(|san| PgnToken::Move {
+                    san: san.parse().unwrap(),
+                    span: SourceSpan::default(),
+                })
+                .collect()
+        };
+        let mut tokens = moves(&["e4", "e5"]);
+        tokens.push(PgnToken::Variation {
+            moves: moves(&["d6", "Ke3"]),
+            span: SourceSpan::default(),
+        });
+        tokens.extend(moves(&["Nf3", "Nc6", "Bb5", "Qxh8"]));
+        tokens
+    }
+
+    #[test]
+    fn parse_pgn_tokens_illegal_move() {
+        let illegal_move = pgn_tokens_to_move_tree(
+            1,
+            ChessPosition::default(),
+            &tokens_with_illegal_moves(),
+        )
+        .unwrap_err();
+
+        assert_eq!(illegal_move.san, "Ke3");
+        assert_eq!(illegal_move.move_number, 2);
+        assert_eq!(illegal_move.side, "white");
+        assert_eq!(
+            illegal_move.fen,
+            "rnbqkbnr/ppp1pppp/3p4/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2"
+        );
+    }
+
+    #[test]
+    fn replay_pgn_tokens_keeps_legal_moves() {
+        let replay = replay_pgn_tokens(
+            1,
+            ChessPosition::default(),
+            &tokens_with_illegal_moves(),
+        );
+
+        assert!(!replay.complete);
+        let illegal: Vec<(&str, u32, &str)> = replay
+            .illegal_moves
+            .iter()
+            .map(|m| (m.san.as_str(), m.move_number, m.side.as_str()))
+            .collect();
+        assert_eq!(illegal, vec![("Ke3", 2, "white"), ("Qxh8", 3, "black")]);
+
+        // The main line up to 3. Bb5 and the variation's 1... d6
+        let sans: Vec<&str> = replay
+            .tree
+            .nodes
+            .values()
+            .filter_map(|node| node.game_move.as_ref())
+            .map(|game_move| game_move.san.as_str())
+            .collect();
+        assert_eq!(sans.len(), 6);
+        assert!(sans.contains(&"d6") && sans.contains(&"Bb5"));
+    }
+
+    #[test]
+    fn parse_pgn_tokens_chess960_castling() {
+        let castle = |san: &str| PgnToken::Move {
+            san: san.parse().unwrap(),
+            span: SourceSpan::default(),
+        };
+        let tokens = vec![castle("O-O"), castle("O-O-O")];
+        // Shredder-FEN castling rights, with the kings on the b-file
+        let start = ChessPosition::from_fen(
+            Some(
+                "rk2r3/pppppppp/8/8/8/8/PPPPPPPP/RK2R3 w EAea - 0 1"
+                    .to_string(),
+            ),
+            Some("Chess960".to_string()),
+        )
+        .unwrap();
+
+        let tree = pgn_tokens_to_move_tree(1, start, &tokens).unwrap();
+
+        // Castling is written as the king taking its own rook
+        let moves: Vec<(&str, &str)> = tree
+            .nodes
+            .values()
+            .filter_map(|node| node.game_move.as_ref())
+            .map(|game_move| (game_move.san.as_str(), game_move.uci.as_str()))
+            .collect();
+        assert_eq!(moves, vec![("O-O", "b1e1"), ("O-O-O", "b8a8")]);
+        let last_position = tree
+            .nodes
+            .values()
+            .filter_map(|node| node.game_move.as_ref())
+            .last()
+            .and_then(|game_move| game_move.position.as_ref())
+            .unwrap();
+        assert_eq!(
+            last_position.fen,
+            "2krr3/pppppppp/8/8/8/8/PPPPPPPP/R4RK1 w - - 2 2"
+        );
+        assert_eq!(last_position.variant.as_deref(), Some("Chess960"));
+    }
+
+    #[test]
+    fn parse_pgn_tokens_crazyhouse_drops() {
+        let tokens: Vec<PgnToken> = ["e4", "d5", "exd5", "Qxd5", "P@e4"]
+            .into_iter()
+            .map(|san| PgnToken::Move {
+                san: san.parse().unwrap(),
+                span: SourceSpan::default(),
+            })
+            .collect();
+        let start =
+            ChessPosition::starting(&variant_name("crazyhouse")).unwrap();
+        assert_eq!(
+            start.fen,
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR[] w KQkq - 0 1"
+        );
+
+        let tree = pgn_tokens_to_move_tree(1, start, &tokens).unwrap();
+
+        // The captured pawn is dropped back onto the board from the pocket
+        let last_move = tree
+            .nodes
+            .values()
+            .filter_map(|node| node.game_move.as_ref())
+            .last()
+            .unwrap();
+        assert_eq!(last_move.uci, "P@e4");
+        let last_position = last_move.position.as_ref().unwrap();
+        assert_eq!(
+            last_position.fen,
+            "rnb1kbnr/ppp1pppp/8/3q4/4P3/8/PPPP1PPP/RNBQKBNR[p] b KQkq - 0 3"
+        );
+        assert_eq!(last_position.variant.as_deref(), Some("Crazyhouse"));
+
+        // Moves that are only legal in standard chess aren't played
+        let standard = ChessPosition::default();
+        assert!(pgn_tokens_to_move_tree(1, standard, &tokens).is_err());
+    }
+
+    /// Import a PGN game in a variant, returning the FEN after the last
+    /// main line move
+    fn variant_game_fen(
+        variant: &str,
+        fen: Option<&str>,
+        movetext: &str,
+    ) -> String {
+        let setup = fen
+            .map(|fen| format!("[SetUp \"1\"]\n[FEN \"{}\"]\n", fen))
+            .unwrap_or_default();
+        let pgn = format!(
+            "[Event \"?\"]\n[Site \"?\"]\n[Date \"?\"]\n[Round \"?\"]\n\
+             [White \"?\"]\n[Black \"?\"]\n[Result \"*\"]\n\
+             [Variant \"{}\"]\n{}\n{} *\n",
+            variant, setup, movetext
+        );
+        let pgn_game = ok_parse::pgn::parse_pgn_game(&pgn).unwrap();
+        let (game, _) = ChessGame::from_pgn_game(pgn_game, false)
+            .unwrap_or_else(|e| panic!("{} game failed: {}", variant, e));
+
+        let tree = &game.move_tree;
+        let mut node = &tree.nodes[tree.root_id.unwrap()];
+        while let Some(&child) = node.children_ids.first() {
+            node = &tree.nodes[child];
+        }
+        assert_eq!(node.position.variant.as_deref(), Some(variant));
+        node.position.fen.clone()
+    }
+
+    #[test]
+    fn variant_pgn_games() {
+        // Captured pieces are dropped back onto the board from the pocket
+        assert_eq!(
+            variant_game_fen(
+                "Crazyhouse",
+                None,
+                "1. e4 d5 2. exd5 Qxd5 3. P@e4 Qa5 4. Nc3 P@d4"
+            ),
+            "rnb1kbnr/ppp1pppp/8/q7/3pP3/2N5/PPPP1PPP/R1BQKBNR[] w KQkq - 0 5"
+        );
+        // Capturing explodes the pieces around the capture, kings included
+        assert_eq!(
+            variant_game_fen("Atomic", None, "1. e4 d5 2. exd5 Qxd2#"),
+            "rnb1kbnr/ppp1pppp/8/8/8/8/PPP2PPP/RN3BNR w kq - 0 3"
+        );
+        assert_eq!(
+            variant_game_fen(
+                "King of the Hill",
+                None,
+                "1. e4 d6 2. Ke2 Nf6 3. Kd3 Nfd7 4. Kd4"
+            ),
+            "rnbqkb1r/pppnpppp/3p4/8/3KP3/8/PPPP1PPP/RNBQ1BNR b kq - 5 4"
+        );
+        assert_eq!(
+            variant_game_fen(
+                "Three-check",
+                None,
+                "1. e4 f6 2. Qh5+ g6 3. Qxg6+ hxg6"
+            ),
+            "rnbqkbnr/ppppp3/5pp1/8/4P3/8/PPPP1PPP/RNB1KBNR w KQkq - 1+3 0 4"
+        );
+        // Pawns can promote to kings
+        assert_eq!(
+            variant_game_fen(
+                "Antichess",
+                Some("8/P7/8/8/8/8/6p1/K7 b - - 0 1"),
+                "1... g1=K 2. a8=K Kf2"
+            ),
+            "K7/8/8/8/8/8/5k2/K7 w - - 1 3"
+        );
+        // Games without a FEN start from the variant's own position
+        assert_eq!(
+            variant_game_fen("Horde", None, "1. f6 exf6 2. gxf6 Nxf6"),
+            "rnbqkb1r/pppp1ppp/5n2/1PP5/PPPPPPPP/PPPPPPPP/PPPPPPPP/PPPPPPPP w kq - 0 3"
+        );
+        assert_eq!(
+            variant_game_fen("Racing Kings", None, "1. Kh3 Ka3 2. Kg4 Kb4"),
+            "8/8/8/8/1k4K1/8/1rbnNBR1/qrbnNBRQ w - - 4 3"
+        );
     }
 }
@@ -1,26 +1,51 @@
+use slotmap::DefaultKey;
+
 use crate::models::{ChessMoveTree, ChessPosition};
 
+/// A position extracted from a move tree
+#[derive(Debug, Clone)]
+pub struct ExtractedPosition {
+    pub position: ChessPosition,
+    /// Index of the position the move leading here was played from, in
+    /// the same list. None for the tree's root.
+    pub parent: Option<usize>,
+}
+
 impl ChessMoveTree {
     /// Extract all positions from the move tree
     ///
     /// Parameters:
     /// - `include_variations`: Whether to include variations in the extraction
     ///
-    /// Returns a vector of ChessPosition objects
+    /// Returns the positions from the root, each after the position it's
+    /// reached from, and a line's continuation before its variations.
     pub fn extract_positions(
         &self,
         include_variations: bool,
-    ) -> Vec<ChessPosition> {
-        let mut positions: Vec<ChessPosition> = Vec::new();
-
-        let tree_nodes = if include_variations {
-            self.nodes.values().cloned().collect()
-        } else {
-            self.main_line()
+    ) -> Vec<ExtractedPosition> {
+        let mut positions: Vec<ExtractedPosition> = Vec::new();
+        let Some(root_id) = self.root_id else {
+            return positions;
         };
 
-        for node in tree_nodes {
-            positions.push(node.position.clone());
+        // Nodes still to visit, each with its parent's index
+        let mut pending: Vec<(DefaultKey, Option<usize>)> =
+            vec![(root_id, None)];
+        while let Some((id, parent)) = pending.pop() {
+            let node = &self.nodes[id];
+            let index = positions.len();
+            positions.push(ExtractedPosition {
+                position: node.position.clone(),
+                parent,
+            });
+
+            let children = if include_variations {
+                node.children_ids.as_slice()
+            } else {
+                &node.children_ids[..node.children_ids.len().min(1)]
+            };
+            // Pushed in reverse so the main line is visited first
+            pending.extend(children.iter().rev().map(|&id| (id, Some(index))));
         }
 
         positions
@@ -51,6 +51,20 @@ impl GameSession {
         Ok(())
     }
 
+    /// Replace the NAGs of the current move
+    pub fn set_move_nags(
+        &mut self,
+        nags: &[u8],
+    ) -> Result<(), AppError> {
+        if !self.game.move_tree.set_current_move_nags(nags) {
+            return Err(AppError::SessionError(
+                "No move selected to annotate".to_string(),
+            ));
+        }
+        self.dirty = true;
+        Ok(())
+    }
+
     pub fn undo_move(&mut self) -> Result<(), AppError> {
         // TODO: Implement undo_move in ChessGame
         // For now, return an error indicating it's not implemented
@@ -137,7 +151,7 @@ impl GameSession {
     pub fn extract_positions(
         &self,
         include_variations: bool,
-    ) -> Vec<crate::models::ChessPosition> {
+    ) -> Vec<crate::models::ExtractedPosition> {
         self.game.move_tree.extract_positions(include_variations)
     }
 }
@@ -79,6 +79,19 @@ impl GameSessionManager {
         session.make_move(move_notation).await
     }
 
+    pub fn set_move_nags(
+        &mut self,
+        board_id: i32,
+        nags: &[u8],
+    ) -> Result<(), AppError> {
+        let session =
+            self.get_session_mut(board_id)
+                .ok_or(AppError::SessionError(
+                    "Game session not found".to_string(),
+                ))?;
+        session.set_move_nags(nags)
+    }
+
     pub fn undo_move(
         &mut self,
         board_id: i32,
@@ -1,3 +1,4 @@
+use ok_engine_manager::utils::EngineManagerError;
 use ok_parse::pgn::PgnParseError;
 use serde::Serialize;
 use thiserror::Error;
@@ -20,6 +21,9 @@ pub enum AppError {
     #[error("Engine error: {0}")]
     EngineError(String),
 
+    #[error(transparent)]
+    EngineManagerError(#[from] EngineManagerError),
+
     #[error("Session error: {0}")]
     SessionError(String),
 
@@ -1,4 +1,5 @@
 import type { InjectionKey } from "vue";
+import type { PgnImportReport } from "../shared/bindings";
 import type {
 	ActiveGameState,
 	AlertToast,
@@ -6,6 +7,7 @@ import type {
 	FilterOption,
 	OperationResult,
 	SortOption,
+	ValidationPolicy,
 } from "../shared/types";
 import {
 	UITheme,
@@ -16,7 +18,8 @@ import {
 
 // Define interfaces for the services we want to inject
 export interface IGlobalStore {
-	importPGNGames(pgn: string): Promise<void>;
+	importPGNGames(filePath: string): Promise<void>;
+	importPGNText(pgn: string): Promise<void>;
 	updateGameProperty(
 		gameId: number,
 		property: string,
@@ -48,7 +51,16 @@ export interface IGamesStore {
 
 export interface IImportExportService {
 	validatePGNFormat(pgn: string): { isValid: boolean; error?: string };
-	importPGNGames(pgn: string): Promise<OperationResult>;
+	importPGNGames(
+		filePath: string,
+		policy?: ValidationPolicy,
+		keepIncomplete?: boolean,
+	): Promise<OperationResult<PgnImportReport>>;
+	importPGNText(
+		pgn: string,
+		policy?: ValidationPolicy,
+		keepIncomplete?: boolean,
+	): Promise<OperationResult<PgnImportReport>>;
 	fetchExplorerGames(): Promise<ExplorerGame[]>;
 	searchGames(games: ExplorerGame[], query: string): ExplorerGame[];
 	filterGames(
@@ -72,7 +84,11 @@ export const GamesStoreKey: InjectionKey<IGamesStore> = Symbol("GamesStore");
 
 // Helper function to create a mock implementation
 export const createMockGlobalStore = (): IGlobalStore => ({
-	importPGNGames: async (pgn: string) => {
+	importPGNGames: async (filePath: string) => {
+		console.log("Mock: Would import PGN file:", filePath);
+		await new Promise((resolve) => setTimeout(resolve, 1000));
+	},
+	importPGNText: async (pgn: string) => {
 		console.log("Mock: Would import PGN:", pgn);
 		await new Promise((resolve) => setTimeout(resolve, 1000));
 	},
@@ -92,12 +108,14 @@ export const createMockGlobalStore = (): IGlobalStore => ({
 				name: "Player 1",
 				elo: 1000,
 				country: "USA",
+				title: null,
 			},
 			black_player: {
 				id: 2,
 				name: "Player 2",
 				elo: 1000,
 				country: "USA",
+				title: null,
 			},
 			tournament: {
 				id: 1,
@@ -174,7 +192,12 @@ export const createMockImportExportService = (): IImportExportService => ({
 
 		return { isValid: true };
 	},
-	importPGNGames: async (pgn: string) => {
+	importPGNGames: async (filePath: string) => {
+		console.log("Mock service: Would import PGN file:", filePath);
+		await new Promise((resolve) => setTimeout(resolve, 500));
+		return { success: true, data: undefined, error: undefined };
+	},
+	importPGNText: async (pgn: string) => {
 		console.log("Mock service: Would import PGN:", pgn);
 		await new Promise((resolve) => setTimeout(resolve, 500));
 		return { success: true, data: undefined, error: undefined };
@@ -15,7 +15,9 @@ async function buildRealImplementations() {
 	const globalStore = useGlobalStore();
 
 	const globalStoreImpl: IGlobalStore = {
-		importPGNGames: (pgn: string) => globalStore.importPGNGames(pgn),
+		importPGNGames: (filePath: string) =>
+			globalStore.importPGNGames(filePath),
+		importPGNText: (pgn: string) => globalStore.importPGNText(pgn),
 		updateGameProperty: (gameId, property, value) =>
 			globalStore.updateGameProperty(gameId, property, value),
 		fetchExplorerGames: async () => {
@@ -28,6 +30,7 @@ async function buildRealImplementations() {
 	const importExportServiceImpl: IImportExportService = {
 		validatePGNFormat: ImportExportService.validatePGNFormat,
 		importPGNGames: ImportExportService.importPGNGames,
+		importPGNText: ImportExportService.importPGNText,
 		fetchExplorerGames: async () => {
 			const result = await ImportExportService.fetchExplorerGames();
 			return result && result.success && Array.isArray(result.data)
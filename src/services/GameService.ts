@@ -6,7 +6,7 @@ import type {
 	ChessTreeNode,
 	LegalMove,
 } from "../shared/bindings";
-import type { OperationResult } from "../shared/types";
+import type { GameVariant, OperationResult } from "../shared/types";
 import { ErrorCategory, withErrorHandling } from "./ErrorService";
 
 /**
@@ -45,15 +45,17 @@ export function getTurnFromFen(fen: string): "white" | "black" | null {
 }
 
 /**
- * Get valid moves for a position
+ * Get valid moves for a position, using Chess960 castling for "Chess960"
+ * games
  */
 export async function getValidMoves(
 	position?: string,
+	variant?: string,
 ): Promise<LegalMove[] | null> {
 	if (!position) return null;
 
 	const result = await withErrorHandling(
-		() => API.analysis.getValidMoves(position),
+		() => API.analysis.getValidMoves(position, variant),
 		ErrorCategory.CHESS_GAME,
 		"INVALID_POSITION",
 		`Failed to get valid moves for position: ${position}`,
@@ -96,7 +98,7 @@ export function moveExistsInTree(
  */
 export async function createGameSession(
 	boardId: number,
-	type: "standard" | "puzzle" | "960" = "standard",
+	type: GameVariant = "standard",
 ): Promise<OperationResult<GameSession>> {
 	const result = await withErrorHandling(
 		async () => {
@@ -1,18 +1,43 @@
 import API from "../shared/api";
+import type { PgnImportReport } from "../shared/bindings";
 import type {
 	ExplorerGame,
 	FilterOption,
 	OperationResult,
 	SortOption,
+	ValidationPolicy,
 } from "../shared/types";
 import { ErrorCategory, withErrorHandling } from "./ErrorService";
 
 /**
- * Import PGN games into the database
+ * Import the games of a PGN file into the database
  */
-export async function importPGNGames(pgn: string): Promise<OperationResult> {
+export async function importPGNGames(
+	filePath: string,
+	policy?: ValidationPolicy,
+	keepIncomplete?: boolean,
+): Promise<OperationResult<PgnImportReport>> {
 	return await withErrorHandling(
-		() => API.games.import(pgn),
+		() => API.games.import(filePath, policy, keepIncomplete),
+		ErrorCategory.DATABASE,
+		"INSERT_ERROR",
+		"Failed to import PGN games",
+		{
+			metadata: { filePath },
+		},
+	);
+}
+
+/**
+ * Import games from PGN text, like pasted games, into the database
+ */
+export async function importPGNText(
+	pgn: string,
+	policy?: ValidationPolicy,
+	keepIncomplete?: boolean,
+): Promise<OperationResult<PgnImportReport>> {
+	return await withErrorHandling(
+		() => API.games.importText(pgn, policy, keepIncomplete),
 		ErrorCategory.DATABASE,
 		"INSERT_ERROR",
 		"Failed to import PGN games",
@@ -1,6 +1,20 @@
 import { invoke } from "@tauri-apps/api/core";
-import type { ChessGame, LegalMove, QueryParams } from "./bindings";
-import { parseChessGame } from "./types";
+import type {
+	BookBuildOptions,
+	BookBuildReport,
+	BookGameFilter,
+	BookMove,
+	ChessGame,
+	LegalMove,
+	PgnImportReport,
+	QueryParams,
+	SuiteResult,
+} from "./bindings";
+import {
+	type GameVariant,
+	parseChessGame,
+	type ValidationPolicy,
+} from "./types";
 
 /**
  * Wraps an API call in error handling
@@ -63,7 +77,7 @@ export const API = {
 
 		create: (
 			boardId: number,
-			variant: "standard" | "puzzle" | "960" = "standard",
+			variant: GameVariant = "standard",
 		) =>
 			wrapAPICall(async () => {
 				const response = await invoke<string>("create_session", {
@@ -79,12 +93,62 @@ export const API = {
 				`delete game ${gameId}`,
 			),
 
-		import: (pgn: string) =>
-			wrapAPICall<void>(
-				() => invoke<void>("import_pgn_games", { pgn }),
+		import: (
+			filePath: string,
+			policy?: ValidationPolicy,
+			keepIncomplete?: boolean,
+		) =>
+			wrapAPICall<PgnImportReport>(
+				() =>
+					invoke<PgnImportReport>("import_pgn_games", {
+						filePath,
+						policy,
+						keepIncomplete,
+					}),
 				"import PGN",
 			),
 
+		importText: (
+			pgn: string,
+			policy?: ValidationPolicy,
+			keepIncomplete?: boolean,
+		) =>
+			wrapAPICall<PgnImportReport>(
+				() =>
+					invoke<PgnImportReport>("import_pgn_text", {
+						pgn,
+						policy,
+						keepIncomplete,
+					}),
+				"import PGN text",
+			),
+
+		importEpd: (epd: string, suiteName?: string) =>
+			wrapAPICall<PgnImportReport>(
+				() => invoke<PgnImportReport>("import_epd", { epd, suiteName }),
+				"import EPD",
+			),
+
+		importLichess: (filePath: string) =>
+			wrapAPICall<PgnImportReport>(
+				() =>
+					invoke<PgnImportReport>("import_lichess_games", { filePath }),
+				"import lichess games",
+			),
+
+		importChessCom: (filePath: string) =>
+			wrapAPICall<PgnImportReport>(
+				() =>
+					invoke<PgnImportReport>("import_chesscom_games", { filePath }),
+				"import chess.com games",
+			),
+
+		importScid: (filePath: string) =>
+			wrapAPICall<PgnImportReport>(
+				() => invoke<PgnImportReport>("import_scid_database", { filePath }),
+				"import SCID database",
+			),
+
 		update: (gameId: number, property: string, value: string) =>
 			wrapAPICall<void>(
 				() => invoke<void>("update_game_property", { gameId, property, value }),
@@ -170,6 +234,15 @@ export const API = {
 				return JSON.parse(response) as ChessGame;
 			}, `jump to move ${moveId} on board ${boardId}`),
 
+		setNags: (boardId: number, nags: number[]) =>
+			wrapAPICall(async () => {
+				const response = await invoke<string>("set_move_nags", {
+					boardId,
+					nags,
+				});
+				return JSON.parse(response) as ChessGame;
+			}, `set NAGs on board ${boardId}`),
+
 		toStart: (boardId: number) =>
 			wrapAPICall(async () => {
 				const response = await invoke<string>("navigate_to_start", { boardId });
@@ -190,13 +263,41 @@ export const API = {
 	},
 
 	analysis: {
-		getValidMoves: (fen: string) =>
+		getValidMoves: (fen: string, variant?: string) =>
 			wrapAPICall(async () => {
-				const response = await invoke<string>("get_legal_moves", { fen });
+				const response = await invoke<string>("get_legal_moves", {
+					fen,
+					variant,
+				});
 				// Parse with proper validation
 				return JSON.parse(response) as LegalMove[];
 			}, "get valid moves"),
 
+		getBookMoves: (fen: string, bookPath: string, variant?: string) =>
+			wrapAPICall(async () => {
+				const response = await invoke<string>("get_book_moves", {
+					fen,
+					variant,
+					bookPath,
+				});
+				return JSON.parse(response) as BookMove[];
+			}, "get book moves"),
+
+		buildOpeningBook: (
+			bookPath: string,
+			filter?: BookGameFilter,
+			options?: BookBuildOptions,
+		) =>
+			wrapAPICall<BookBuildReport>(
+				() =>
+					invoke<BookBuildReport>("build_opening_book", {
+						bookPath,
+						filter,
+						options,
+					}),
+				"build opening book",
+			),
+
 		getMoveTree: (gameId: number) =>
 			wrapAPICall(async () => {
 				const response = await invoke<string>("get_move_tree", { id: gameId });
@@ -240,6 +341,22 @@ export const API = {
 				`analyze game ${boardId}`,
 			),
 
+		runTestSuite: (
+			engineName: string,
+			epd: string,
+			options?: { movetimeMs?: number; importResults?: boolean },
+		) =>
+			wrapAPICall<SuiteResult>(
+				() =>
+					invoke<SuiteResult>("run_test_suite", {
+						engineName,
+						epd,
+						movetimeMs: options?.movetimeMs,
+						importResults: options?.importResults,
+					}),
+				`run test suite with ${engineName}`,
+			),
+
 		stopAnalysis: (engineName: string) =>
 			wrapAPICall<void>(
 				() => invoke<void>("stop_analysis", { engineName }),
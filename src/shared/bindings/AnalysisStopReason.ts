@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Why an analysis stopped
+ */
+export type AnalysisStopReason = { "Converged": { depth: number, } } | { "MateFound": { depth: number, mate_in: number, } } | "LimitReached";
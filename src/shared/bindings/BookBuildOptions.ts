@@ -0,0 +1,17 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { BookColor } from "./BookColor";
+import type { BookWeighting } from "./BookWeighting";
+
+/**
+ * Options for building a book
+ */
+export type BookBuildOptions = { 
+/**
+ * Moves played in fewer games are left out
+ */
+min_games: number, weighting: BookWeighting, 
+/**
+ * Only keep the moves of one side, for a book that plays that side.
+ * Both sides' moves are kept if not given.
+ */
+color: BookColor | null, };
@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * What went into a built book
+ */
+export type BookBuildReport = { 
+/**
+ * Number of games the book was built from
+ */
+games: number, 
+/**
+ * Number of entries in the book
+ */
+entries: number, };
@@ -0,0 +1,28 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Which games of the database a book is built from
+ */
+export type BookGameFilter = { 
+/**
+ * Only games played by one of these players, by name. When the book
+ * is for one side, the player has to be playing that side.
+ */
+players: Array<string> | null, 
+/**
+ * Only games played on or after this date, as a PGN date like
+ * "2024.01.31"
+ */
+date_from: string | null, 
+/**
+ * Only games played on or before this date
+ */
+date_to: string | null, 
+/**
+ * Only games where both players are rated at least this much
+ */
+min_rating: number | null, 
+/**
+ * Only the first moves of each game, the whole main line if not given
+ */
+max_plies: number | null, };
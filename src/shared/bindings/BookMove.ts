@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A move found in an opening book
+ */
+export type BookMove = { san: string, uci: string, 
+/**
+ * How often the move should be played relative to the position's
+ * other book moves
+ */
+weight: number, 
+/**
+ * The entry's learn value
+ */
+learn: number, };
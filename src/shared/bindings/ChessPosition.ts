@@ -1,4 +1,9 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { ChessEvaluation } from "./ChessEvaluation";
 
-export type ChessPosition = { id: number, fen: string, evaluations: Array<ChessEvaluation>, variant: string | null, };
+export type ChessPosition = { id: number, fen: string, evaluations: Array<ChessEvaluation>, 
+/**
+ * The variant the position is played in, like "Crazyhouse", named as
+ * in PGN `Variant` tags. "Chess960" positions use Chess960 castling rules
+ */
+variant: string | null, };
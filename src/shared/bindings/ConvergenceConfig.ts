@@ -0,0 +1,30 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Settings for stopping a search once its result stops changing
+ */
+export type ConvergenceConfig = { 
+/**
+ * Number of consecutive depths the best move and score must agree for
+ */
+stable_depths: number, 
+/**
+ * Largest score change (in centipawns) between depths still considered stable
+ */
+score_tolerance: number, 
+/**
+ * Updates below this depth are too noisy to be considered stable
+ */
+min_depth: number, 
+/**
+ * Number of consecutive depths a mate score must be reported for
+ */
+mate_confirmation_depths: number, 
+/**
+ * Depth at which the engine stops if the result never settles
+ */
+max_depth: number | null, 
+/**
+ * Time (in milliseconds) after which the engine stops if the result never settles
+ */
+max_time_ms: number | null, };
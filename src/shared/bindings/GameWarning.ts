@@ -0,0 +1,22 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A problem with an imported game that didn't stop it being saved
+ */
+export type GameWarning = { 
+/**
+ * Index of the game in the source, starting at 0
+ */
+game_index: number, 
+/**
+ * Line of the source the warning is about
+ */
+line: number, 
+/**
+ * Column of the source the warning is about
+ */
+column: number, 
+/**
+ * What the problem is
+ */
+message: string, };
@@ -0,0 +1,32 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { GameWarning } from "./GameWarning";
+import type { SkippedGame } from "./SkippedGame";
+
+/**
+ * The outcome of importing a PGN source
+ */
+export type PgnImportReport = { 
+/**
+ * Number of games saved to the database
+ */
+imported: number, 
+/**
+ * Games that were not saved, in source order
+ */
+skipped: Array<SkippedGame>, 
+/**
+ * Problems with saved games that didn't stop them being saved,
+ * like tags filled with defaults, in source order
+ *
+ * Only the first `MAX_REPORT_WARNINGS` are kept.
+ */
+warnings: Array<GameWarning>, 
+/**
+ * Number of warnings left out of `warnings` once it was full
+ */
+omitted_warnings: number, 
+/**
+ * Encoding the source was read as, like "UTF-8" or "Windows-1252",
+ * for sources read as text
+ */
+encoding: string | null, };
@@ -0,0 +1,26 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A game left out of an import
+ */
+export type SkippedGame = { 
+/**
+ * Index of the game in the source, starting at 0
+ */
+game_index: number, 
+/**
+ * Line the game failed to parse at, if it failed to parse
+ */
+line: number | null, 
+/**
+ * Column the game failed to parse at, if it failed to parse
+ */
+column: number | null, 
+/**
+ * Excerpt of the source line the game failed to parse at
+ */
+snippet: string | null, 
+/**
+ * Why the game was skipped
+ */
+reason: string, };
@@ -0,0 +1,46 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * How an engine did on one position of a test suite
+ */
+export type SuitePositionResult = { 
+/**
+ * The position's `id`, like "WAC.001"
+ */
+id: string | null, 
+/**
+ * The position as a full FEN
+ */
+fen: string, 
+/**
+ * The moves that solve the position, in SAN
+ */
+best_moves: Array<string>, 
+/**
+ * The moves that fail the position, in SAN
+ */
+avoid_moves: Array<string>, 
+/**
+ * The engine's best move in SAN, if it found a legal one
+ */
+engine_move: string | null, 
+/**
+ * Whether the engine's move solved the position
+ */
+solved: boolean, 
+/**
+ * Depth of the engine's last main line update
+ */
+depth: number | null, 
+/**
+ * The engine's score in centipawns, from the side to move's view
+ */
+score_cp: number | null, 
+/**
+ * Moves to mate, negative when the side to move is being mated
+ */
+mate: number | null, 
+/**
+ * Why the position couldn't be checked, like an engine timeout
+ */
+error: string | null, };
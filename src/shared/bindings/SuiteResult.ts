@@ -0,0 +1,19 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SuitePositionResult } from "./SuitePositionResult";
+
+/**
+ * How an engine did on a test suite
+ */
+export type SuiteResult = { engine_name: string, 
+/**
+ * Number of positions solved
+ */
+solved: number, 
+/**
+ * Number of positions in the suite
+ */
+total: number, 
+/**
+ * Results of each position, in suite order
+ */
+positions: Array<SuitePositionResult>, };
@@ -1,8 +1,9 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ConvergenceConfig } from "./ConvergenceConfig";
 
 /**
  * Time management strategies for analysis
  *
  * For a single position analysis, total and fixed result in the same behavior.
  */
-export type TimeStrategy = { "TotalBudget": { total_seconds: bigint, } } | { "FixedPerMove": { seconds_per_move: bigint, } } | { "FixedDepth": { depth: number, } };
+export type TimeStrategy = { "TotalBudget": { total_seconds: bigint, } } | { "FixedPerMove": { seconds_per_move: bigint, } } | { "FixedDepth": { depth: number, } } | { "UntilStable": { convergence: ConvergenceConfig, } } | { "FixedNodes": { nodes: bigint, } } | { "Adaptive": { total_seconds: bigint, } };
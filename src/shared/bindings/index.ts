@@ -10,7 +10,20 @@ export * from "./ChessTournament";
 export * from "./ChessTreeNode";
 export * from "./ChessMoveTree";
 
+export * from "./GameWarning";
+export * from "./PgnImportReport";
+export * from "./SkippedGame";
+
 export * from "./QueryParams";
 export * from "./QueryResult";
 
 export * from "./LegalMove";
+export * from "./BookMove";
+export * from "./BookBuildOptions";
+export * from "./BookBuildReport";
+export * from "./BookColor";
+export * from "./BookGameFilter";
+export * from "./BookWeighting";
+
+export * from "./SuitePositionResult";
+export * from "./SuiteResult";
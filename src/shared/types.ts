@@ -123,6 +123,29 @@ export interface SortConfig {
  */
 export type FilterOption = "all" | "favorites" | "tags";
 
+/**
+ * Which tags imported PGN games must have
+ */
+export type ValidationPolicy =
+	| { mode: "strict" }
+	| { mode: "lenient" }
+	| { mode: "required_tags"; tags: string[] };
+
+/**
+ * The kinds of game a new session can start, including the lichess variants
+ */
+export type GameVariant =
+	| "standard"
+	| "puzzle"
+	| "960"
+	| "Crazyhouse"
+	| "Atomic"
+	| "King of the Hill"
+	| "Three-check"
+	| "Antichess"
+	| "Horde"
+	| "Racing Kings";
+
 ////////////////////////////////////////////////////////////
 // Application Interfaces
 // Api interfaces are mostly sub-sets of backend models (bindings)
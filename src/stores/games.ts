@@ -19,7 +19,7 @@ import {
 	refreshGameState,
 	saveGameSession,
 } from "../services/GameService";
-import type { ActiveGameState } from "../shared/types";
+import type { ActiveGameState, GameVariant } from "../shared/types";
 
 /**
  * A store for managing the states of ALL open games using session-focused API
@@ -57,7 +57,9 @@ export const useGamesStore = defineStore("games", {
 			if (!gameState) return null;
 			const position = getCurrentPosition(gameState.game);
 			// Note: This getter returns a promise, consider using a computed property with async data
-			return position ? getValidMoves(position.fen) : Promise.resolve(null);
+			return position
+				? getValidMoves(position.fen, gameState.game.variant)
+				: Promise.resolve(null);
 		},
 	},
 
@@ -87,7 +89,7 @@ export const useGamesStore = defineStore("games", {
 		 */
 		async newGame(
 			boardId: number,
-			type: "standard" | "puzzle" | "960" = "standard",
+			type: GameVariant = "standard",
 		): Promise<ActiveGameState | null> {
 			// Close existing game if any
 			if (this.activeGameMap.has(boardId)) {
@@ -49,8 +49,14 @@ export const useGlobalStore = defineStore("global", {
 				await this.fetchExplorerGames();
 			}
 		},
-		async importPGNGames(pgn: string) {
-			const result = await ImportExportService.importPGNGames(pgn);
+		async importPGNGames(filePath: string) {
+			const result = await ImportExportService.importPGNGames(filePath);
+			if (result.success) {
+				await this.fetchExplorerGames();
+			}
+		},
+		async importPGNText(pgn: string) {
+			const result = await ImportExportService.importPGNText(pgn);
 			if (result.success) {
 				await this.fetchExplorerGames();
 			}